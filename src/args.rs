@@ -0,0 +1,272 @@
+//! This module provides command line argument parsing.
+//!
+//! ## License
+//!
+//! Copyright (C) Josh Bassett. All rights reserved.
+//!
+//! Apache 2.0
+//!
+
+use std::env;
+
+use crate::config;
+
+/// Holds the options passed to WCLI on the command line.
+///
+pub struct Args {
+    pub host: String,
+    pub multiplex: bool,
+    pub version: bool,
+    pub help: bool,
+    pub exec: Option<String>,
+    pub broadcast: Option<String>,
+    pub diff: Option<(String, String, String)>,
+    pub sudo: bool,
+    pub no_color: bool,
+    pub dry_run: bool,
+    pub env_file: Option<String>,
+    pub verbose: u8,
+    pub no_pager: bool,
+    pub json: bool,
+    pub config_check: bool,
+    pub timeout: Option<u64>,
+}
+
+impl Args {
+    /// Parses the process arguments.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if an argument is unknown or is missing its value.
+    /// - Returns [`Err`](Result) if `--timeout` isn't a whole number of seconds.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// wcli --host staging --multiplex
+    /// wcli exec --sudo "yum update -y"
+    /// wcli --format json exec "uptime"
+    /// wcli --timeout 1800 exec "make build"
+    /// wcli broadcast "df -h /"
+    /// wcli diff staging prod "cat /etc/nginx/nginx.conf"
+    /// ```
+    pub fn parse() -> Result<Args, String> {
+        Args::parse_from(env::args().skip(1))
+    }
+
+    /// Parses the arguments after the program name, see [`Args::parse`].
+    ///
+    fn parse_from(argv: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut args: Args = Args {
+            host: "default".to_string(),
+            multiplex: false,
+            version: false,
+            help: false,
+            exec: None,
+            broadcast: None,
+            diff: None,
+            sudo: false,
+            no_color: false,
+            dry_run: false,
+            env_file: None,
+            verbose: 0,
+            no_pager: false,
+            json: false,
+            config_check: false,
+            timeout: None,
+        };
+
+        let mut exec: bool = false;
+        let mut broadcast: bool = false;
+        let mut diff: bool = false;
+        let mut diff_args: Vec<String> = Vec::new();
+
+        let mut iter = argv.into_iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--host" => args.host = iter.next().ok_or("--host requires a host name")?,
+                "--multiplex" => args.multiplex = true,
+                "--version" | "-V" => args.version = true,
+                "--help" | "-h" => args.help = true,
+                "exec" => exec = true,
+                "broadcast" => broadcast = true,
+                "diff" => diff = true,
+                "--sudo" => args.sudo = true,
+                "--no-color" => args.no_color = true,
+                "--dry-run" => args.dry_run = true,
+                "--env-file" => {
+                    args.env_file = Some(iter.next().ok_or("--env-file requires a path")?)
+                }
+                "--verbose" | "-v" => args.verbose = args.verbose.saturating_add(1),
+                "-vv" => args.verbose = args.verbose.saturating_add(2),
+                "--no-pager" => args.no_pager = true,
+                "--config-check" => args.config_check = true,
+                "--timeout" => {
+                    let secs: String = iter
+                        .next()
+                        .ok_or("--timeout requires a number of seconds")?;
+                    args.timeout = Some(config::parse_timeout("--timeout", &secs)?);
+                }
+                "--format" => match iter.next().as_deref() {
+                    Some("json") => args.json = true,
+                    Some("text") => args.json = false,
+                    _ => return Err("--format must be text or json".to_string()),
+                },
+                _ if exec && args.exec.is_none() && !arg.starts_with('-') => args.exec = Some(arg),
+                _ if broadcast && args.broadcast.is_none() && !arg.starts_with('-') => {
+                    args.broadcast = Some(arg)
+                }
+                _ if diff && diff_args.len() < 3 && !arg.starts_with('-') => diff_args.push(arg),
+                _ => return Err(format!("unknown argument '{arg}'")),
+            }
+        }
+
+        if [exec, broadcast, diff].iter().filter(|&&mode| mode).count() > 1 {
+            return Err("exec, broadcast and diff can't be used together".to_string());
+        }
+
+        if exec && args.exec.is_none() {
+            return Err("exec requires a command".to_string());
+        }
+
+        if broadcast && args.broadcast.is_none() {
+            return Err("broadcast requires a command".to_string());
+        }
+
+        if diff {
+            let [old, new, command]: [String; 3] = diff_args
+                .try_into()
+                .map_err(|_| "diff requires two hosts and a command")?;
+            args.diff = Some((old, new, command));
+        }
+
+        if args.config_check && (exec || broadcast || args.diff.is_some()) {
+            return Err("--config-check can't be used with exec, broadcast or diff".to_string());
+        }
+
+        if args.json && !exec {
+            return Err("--format json can only be used with exec".to_string());
+        }
+
+        if args.timeout.is_some() && !exec {
+            return Err("--timeout can only be used with exec".to_string());
+        }
+
+        Ok(args)
+    }
+}
+
+/// Prints the command line usage.
+///
+pub fn usage() {
+    println!("Usage: wcli [OPTIONS]");
+    println!("       wcli [OPTIONS] exec [--sudo] \"<command>\"");
+    println!("       wcli [OPTIONS] broadcast [--sudo] \"<command>\"");
+    println!("       wcli [OPTIONS] diff [--sudo] <host> <host> \"<command>\"");
+    println!("\nOPTIONS");
+    println!("'--host <name>'     -> connect to a host from ~/.wcli/config.toml");
+    println!("'--multiplex'       -> reuse one ssh connection for every command");
+    println!("'--sudo'            -> run the exec, broadcast or diff command with sudo");
+    println!("'--no-color'        -> disable colored output, also set by NO_COLOR");
+    println!("'--dry-run'         -> print each ssh command instead of running it");
+    println!("'--env-file <path>' -> read the credentials from another .env file");
+    println!("'-v', '--verbose'   -> print the config file, each ssh command and its timing");
+    println!("'-vv'               -> also print each exit code and the size of the output");
+    println!("'--no-pager'        -> print long output in full instead of a page at a time");
+    println!("'--format json'     -> print the exec result as a JSON object");
+    println!("'--timeout <secs>'  -> stop the exec command after this long, 0 for never");
+    println!("'--config-check'    -> check the config, PEM file and ssh without connecting");
+    println!("'--version'         -> print the version and exit");
+    println!("'--help'            -> print this message and exit");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Args;
+
+    /// Parses the arguments as if they were typed after `wcli`.
+    ///
+    fn parse(argv: &[&str]) -> Result<Args, String> {
+        Args::parse_from(argv.iter().map(|arg: &&str| arg.to_string()))
+    }
+
+    /// Returns the error the arguments are refused with, empty if they parse.
+    ///
+    fn error(argv: &[&str]) -> String {
+        parse(argv).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn modes_need_their_arguments() {
+        assert_eq!(error(&["exec"]), "exec requires a command");
+        assert_eq!(
+            error(&["broadcast", "--sudo"]),
+            "broadcast requires a command"
+        );
+
+        let two_hosts: &str = "diff requires two hosts and a command";
+        assert_eq!(error(&["diff", "staging", "prod"]), two_hosts);
+        assert_eq!(error(&["diff", "staging", "uptime"]), two_hosts);
+        assert_eq!(
+            error(&["diff", "a", "b", "uptime", "df"]),
+            "unknown argument 'df'"
+        );
+
+        let args: Args = parse(&["diff", "staging", "prod", "uptime"]).unwrap();
+        let (old, new, command) = args.diff.unwrap();
+        assert_eq!([old, new, command], ["staging", "prod", "uptime"]);
+    }
+
+    #[test]
+    fn modes_cant_be_used_together() {
+        let together: &str = "exec, broadcast and diff can't be used together";
+        assert_eq!(error(&["exec", "uptime", "broadcast", "df"]), together);
+        assert_eq!(
+            error(&["broadcast", "df", "diff", "a", "b", "df"]),
+            together
+        );
+
+        let config_check: String = error(&["--config-check", "exec", "uptime"]);
+        assert_eq!(
+            config_check,
+            "--config-check can't be used with exec, broadcast or diff"
+        );
+        assert_eq!(error(&["--config-check", "--host", "staging"]), "");
+    }
+
+    #[test]
+    fn json_is_only_for_exec() {
+        assert_eq!(
+            error(&["--format", "json"]),
+            "--format json can only be used with exec"
+        );
+        assert_eq!(
+            error(&["--format", "yaml", "exec", "df"]),
+            "--format must be text or json"
+        );
+
+        let args: Args = parse(&["--format", "json", "exec", "uptime"]).unwrap();
+        assert!(args.json);
+    }
+
+    #[test]
+    fn flags_take_their_values() {
+        assert_eq!(error(&["--env-file"]), "--env-file requires a path");
+
+        let args: Args = parse(&["-v", "-vv", "--env-file", "deploy/.env"]).unwrap();
+        assert_eq!(args.verbose, 3);
+        assert_eq!(args.env_file.as_deref(), Some("deploy/.env"));
+    }
+
+    #[test]
+    fn timeout_is_only_for_exec() {
+        let args: Result<Args, String> = parse(&["--timeout", "1800", "exec", "make build"]);
+        assert_eq!(args.map(|args: Args| args.timeout), Ok(Some(1800)));
+
+        assert_eq!(
+            parse(&["--timeout", "30"]).err(),
+            Some("--timeout can only be used with exec".to_string())
+        );
+        assert!(parse(&["--timeout", "-5", "exec", "uptime"]).is_err());
+        assert!(parse(&["exec", "uptime", "--timeout"]).is_err());
+    }
+}
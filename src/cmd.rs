@@ -8,12 +8,34 @@
 //!
 
 use std::{
-    io::{self, Write},
-    process::{Command, Output},
+    collections::{BTreeMap, VecDeque},
+    fs,
+    io::{self, BufRead, BufReader, IsTerminal, Write},
+    os::unix::process::ExitStatusExt,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use colored::Colorize;
-use indicatif::ProgressBar;
+use rustyline::{
+    Context, Editor, Helper, completion::Completer, error::ReadlineError, highlight::Highlighter,
+    hint::Hinter, history::DefaultHistory, validate::Validator,
+};
+use serde::Serialize;
+
+use crate::{
+    cmd::package::PackageManager,
+    config::{self, Config},
+    logging,
+    session::Session,
+};
 
 /// Runs a Linux command remotely on an EC2.
 ///
@@ -27,403 +49,6801 @@ use indicatif::ProgressBar;
 ///
 /// # Examples
 /// ```rust
-/// let password: String = var("PASS").expect("Password is needed to run sudo commands");
-/// cmd(&password)
+/// let mut session: Session = Session::new(config, user, None);
+/// cmd(&mut session)
 /// ```
 ///
-pub fn cmd(password: &str) {
+pub fn cmd(session: &mut Session) {
+    logging::set_shell("cmd");
+    session.open_cmd();
+    let Session {
+        config,
+        user,
+        cwd,
+        vars,
+        timings,
+        ..
+    } = session;
+    let mut editor: LineEditor = LineEditor::new("cmd");
+    let mut aliases: BTreeMap<String, String> = alias::load(&config.aliases);
+
     println!("Run 'help' for commands\n");
     loop {
-        let bash_cmd = input();
+        let prompt_vars: PromptVars = PromptVars {
+            user,
+            cwd: cwd.as_deref().unwrap_or("~"),
+            shell: "cmd",
+            ..PromptVars::default()
+        };
+        let prompt: String = shell_prompt(config, ">>> ", &prompt_vars);
+
+        let Some(bash_cmd) = or_exit(editor.multiline_input(&prompt)) else {
+            break;
+        };
+        if is_blank_or_comment(&bash_cmd) {
+            continue;
+        }
+
+        let bash_cmd: String = alias::expand(&aliases, &bash_cmd);
 
         let mut parts: std::str::SplitN<'_, char> = bash_cmd.splitn(2, ' ');
         let first: &str = parts.next().unwrap_or("");
+        let rest: &str = parts.next().unwrap_or("").trim();
+
+        if !is_builtin(first.trim(), rest) && !confirm_dangerous(config, &bash_cmd) {
+            continue;
+        }
 
         match first.trim() {
-            "sudo" => helpers::print_cmd(&run_cmd_sudo(&bash_cmd, password)),
-            "install" => install(password),
-            "remove" => remove(password),
+            "history" if rest.is_empty() => editor.print_history(),
+            "alias" => alias::alias(&mut aliases, &config.aliases, rest),
+            "unalias" => alias::unalias(&mut aliases, &config.aliases, rest),
+            "cd" if !rest.contains(['&', ';', '|']) => *cwd = change_dir(config, cwd.take(), rest),
+            "pwd" => println!("{}\n", cwd.as_deref().unwrap_or("~")),
+            "local" => local(config, rest),
+            "env" if is_env_cmd(rest) => env_cmd(vars, rest),
+            "timings" if matches!(rest, "on" | "off") => {
+                *timings = rest == "on";
+                println!("Timings {rest}\n");
+            }
+            "timestamps" if matches!(rest, "on" | "off") => helpers::set_timestamps(rest == "on"),
+            "timeout" if !rest.contains(char::is_whitespace) => set_timeout(config, rest),
+            "sudo" => {
+                let start: Instant = Instant::now();
+                let (sudo_cmd, save) = parse_save(&bash_cmd);
+                let sudo_cmd: String = with_env(vars, &in_cwd(cwd, &sudo_stdin(sudo_cmd)));
+                let output: Output = run_cmd_sudo(config, &sudo_cmd);
+                let elapsed: Duration = start.elapsed();
+                helpers::print_cmd(&output);
+                save_output(config, &output, save);
+                print_elapsed(*timings, elapsed);
+            }
+            "install" => install(&Ssh, config),
+            "remove" => remove(&Ssh, config),
+            "update" => update(&Ssh, config),
+            "upgrade" => upgrade(&Ssh, config),
+            "push" => scp::push(config),
+            "pull" => scp::pull(config),
+            "view" => view::view(config, cwd, rest),
+            "ps" if process::is_filter(rest) => process::ps(&Ssh, config, rest),
+            "kill" if process::is_target(rest) => process::kill(&Ssh, config, rest),
+            "stream" => {
+                let start: Instant = Instant::now();
+                let bash_cmd: String = with_env(vars, &in_cwd(cwd, rest));
+                if !run_cmd_streaming(config, &bash_cmd, Stdio::inherit()).success() {
+                    fail_script();
+                }
+                println!();
+                print_elapsed(*timings, start.elapsed());
+            }
+            "follow" => follow(config, cwd, rest),
+            "-t" if rest.is_empty() => println!("{} usage: -t <command>\n", "error:".red()),
+            "-t" => {
+                let start: Instant = Instant::now();
+                let bash_cmd: String = with_env(vars, &in_cwd(cwd, rest));
+                if !run_cmd_tty(config, &bash_cmd).success() {
+                    fail_script();
+                }
+                println!();
+                print_elapsed(*timings, start.elapsed());
+            }
+            "retry" => match parse_retry(rest) {
+                Ok((attempts, command)) => {
+                    let start: Instant = Instant::now();
+                    let bash_cmd: String = with_env(vars, &in_cwd(cwd, command));
+                    let output: Output = retry(config, attempts, &bash_cmd);
+                    let elapsed: Duration = start.elapsed();
+                    helpers::print_cmd(&output);
+                    print_elapsed(*timings, elapsed);
+                }
+                Err(e) => println!("{} {e}\n", "error:".red()),
+            },
+            "watch" => match parse_watch(rest) {
+                Ok((interval, command)) => {
+                    let bash_cmd: String = with_env(vars, &in_cwd(cwd, command));
+                    watch(config, interval, &bash_cmd, command);
+                }
+                Err(e) => println!("{} {e}\n", "error:".red()),
+            },
             "clear" => helpers::clear(),
             "help" => cmd_help(),
             "exit" => break,
-            _ => helpers::print_cmd(&run_cmd(bash_cmd.trim())),
+            _ => {
+                let start: Instant = Instant::now();
+                let (command, save) = parse_save(&bash_cmd);
+                let bash_cmd: String = with_env(vars, &in_cwd(cwd, command.trim()));
+                let output: Output = run_cmd(config, &bash_cmd);
+                let elapsed: Duration = start.elapsed();
+                match listing::render(command, &output) {
+                    Some(listing) => helpers::print_cmd(&listing),
+                    None => helpers::print_cmd(&output),
+                }
+                save_output(config, &output, save);
+                if needs_tty(command, output.status.success(), &output.stderr) {
+                    println!(
+                        "{} this looks like it needs a terminal, run it as '-t {}'\n",
+                        "hint:".cyan(),
+                        command.trim()
+                    );
+                }
+                print_elapsed(*timings, elapsed);
+            }
         }
     }
 }
 
-/// Returns user input.
+/// Prints how long a command took as a dim `(done in 1.3s)` line, if timings are on.
 ///
-/// # Errors
+/// The time is taken before the output is printed, so time spent paging isn't counted.
 ///
-/// - Returns [`expect`](core::result::Result<Error>) if stdout fails to flush.
-/// - Returns [`expect`](core::result::Result<Error>) if input is unreadable.
+fn print_elapsed(timings: bool, elapsed: Duration) {
+    if timings {
+        let elapsed: String = format!("(done in {})", helpers::format_duration(elapsed));
+        println!("{}\n", elapsed.dimmed());
+    }
+}
+
+/// Returns true if the command is handled by the cmd shell itself, or run locally, so nothing
+/// is sent to the EC2 and there is nothing to confirm.
 ///
-fn input() -> String {
-    print!("{} ", ">>> ".purple());
-    io::stdout().flush().expect("failed to flush stdout");
+/// # Examples
+/// ```rust
+/// assert!(is_builtin("alias", "clean = \"rm -rf build\""));
+/// assert!(!is_builtin("rm", "-rf build"));
+/// ```
+fn is_builtin(first: &str, rest: &str) -> bool {
+    match first {
+        "alias" | "unalias" | "pwd" | "local" | "clear" | "help" | "exit" => true,
+        "history" => rest.is_empty(),
+        "env" => is_env_cmd(rest),
+        "timings" | "timestamps" => matches!(rest, "on" | "off"),
+        "timeout" => !rest.contains(char::is_whitespace),
+        _ => false,
+    }
+}
 
-    let mut input: String = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("failed to read input");
+/// Returns true if the arguments to `env` are one of the shell's own `set`, `unset` or `list`
+/// commands rather than the Linux `env` command.
+///
+fn is_env_cmd(args: &str) -> bool {
+    matches!(
+        args.split_whitespace().next(),
+        Some("set" | "unset" | "list")
+    )
+}
+
+/// Sets, unsets or lists the environment variables sent with every command.
+///
+/// # Examples
+/// ```plaintext
+/// >>>  env set FLASK_ENV=dev
+/// >>>  env set GREETING="hello world"
+/// >>>  env unset FLASK_ENV
+/// >>>  env list
+/// GREETING=hello world
+/// ```
+fn env_cmd(vars: &mut BTreeMap<String, String>, args: &str) {
+    let (action, arg) = args.split_once(' ').unwrap_or((args, ""));
+    let arg: &str = arg.trim();
+
+    match action {
+        "set" => match parse_var(arg) {
+            Ok((key, value)) => {
+                vars.insert(key, value);
+            }
+            Err(e) => println!("{} {e}", "error:".red()),
+        },
+        "unset" => {
+            if vars.remove(arg).is_none() {
+                println!("{} {arg} is not set", "error:".red());
+            }
+        }
+        _ if vars.is_empty() => println!("no variables set"),
+        _ => {
+            for (key, value) in vars.iter() {
+                println!("{key}={value}");
+            }
+        }
+    }
 
-    input
+    println!();
 }
 
-/// Returns user input with a prompt.
+/// Returns the key and value of a `KEY=VALUE` assignment, with any quotes around the value
+/// removed.
 ///
 /// # Errors
 ///
-/// - Returns [`expect`](core::result::Result) if stdout fails to flush.
-/// - Returns [`expect`](core::result::Result<Error>) if input is unreadable.
+/// - Returns [`Err`](Result) if there's no `=` or the key isn't a valid variable name.
 ///
-fn msg_input(msg: &str) -> String {
-    print!("{msg}: ");
-    io::stdout().flush().expect("failed to flush stdout");
+fn parse_var(assignment: &str) -> Result<(String, String), String> {
+    let (key, value) = assignment
+        .split_once('=')
+        .ok_or("env set requires KEY=VALUE")?;
 
-    let mut input: String = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("failed to read input");
+    let valid: bool = key
+        .chars()
+        .all(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        && key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
 
-    input
+    if !valid {
+        return Err(format!("invalid variable name '{key}'"));
+    }
+
+    let unquoted: &str = ['"', '\'']
+        .iter()
+        .find_map(|quote: &char| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value: &str| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value);
+
+    Ok((key.to_string(), unquoted.to_string()))
 }
 
-/// Returns Output of bash command from EC2.
+/// Returns the command with the session's environment variables exported before it.
 ///
-/// # Errors
+/// Values are double quoted with `\`, `"`, `$` and `` ` `` escaped so they reach the command
+/// as typed. The whole command is sent inside single quotes by [`connect::ssh`], so a single
+/// quote in a value closes and reopens them.
 ///
-/// - Returns [`expect`](Result<Error>) if input is unreadable.
+/// # Examples
+/// ```rust
+/// let vars = BTreeMap::from([("GREETING".to_string(), "hello world".to_string())]);
+/// assert_eq!(
+///     with_env(&vars, "echo $GREETING"),
+///     "export GREETING=\"hello world\"; echo $GREETING"
+/// );
+/// ```
+fn with_env(vars: &BTreeMap<String, String>, bash_cmd: &str) -> String {
+    if vars.is_empty() {
+        return bash_cmd.to_string();
+    }
+
+    let exports: Vec<String> = vars
+        .iter()
+        .map(|(key, value)| format!("{key}={}", quote_value(value)))
+        .collect();
+
+    format!("export {}; {bash_cmd}", exports.join(" "))
+}
+
+/// Returns the value double quoted for the remote shell, see [`with_env`].
 ///
-fn run_cmd(bash_cmd: &str) -> Output {
-    let bar: ProgressBar = helpers::new_bar();
-    bar.enable_steady_tick(std::time::Duration::from_millis(80));
+fn quote_value(value: &str) -> String {
+    let mut quoted: String = String::from("\"");
 
-    let output: Output = Command::new("bash")
-        .arg("-c")
-        .arg(connect::ssh(bash_cmd))
-        .output()
-        .expect("failed to execute remote command");
+    for c in value.chars() {
+        match c {
+            '\\' | '"' | '$' | '`' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\'' => quoted.push_str("'\\''"),
+            _ => quoted.push(c),
+        }
+    }
 
-    bar.finish_and_clear();
+    quoted.push('"');
+    quoted
+}
 
-    output
+/// Returns true for blank lines and `#` comments, which the sub-shells skip so that annotated
+/// snippets can be pasted in.
+///
+/// # Examples
+/// ```rust
+/// assert!(is_blank_or_comment("  # restart the service"));
+/// ```
+fn is_blank_or_comment(input: &str) -> bool {
+    let input: &str = input.trim();
+
+    input.is_empty() || input.starts_with('#')
 }
 
-/// Retruns Output of sudo bash command from EC3
+/// Returns the interval and command of `watch <seconds> <command>`.
 ///
 /// # Errors
 ///
-/// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+/// - Returns [`Err`](Result) if the command is missing.
+/// - Returns [`Err`](Result) if the interval isn't a positive number of seconds.
 ///
-fn run_cmd_sudo(sudo_cmd: &str, password: &str) -> Output {
-    let bash_cmd: String = format!("echo {password} | {sudo_cmd}");
+/// # Examples
+/// ```rust
+/// assert_eq!(parse_watch("2 docker ps"), Ok((Duration::from_secs(2), "docker ps")));
+/// ```
+fn parse_watch(args: &str) -> Result<(Duration, &str), String> {
+    let (interval, command) = args
+        .split_once(char::is_whitespace)
+        .filter(|(_, command)| !command.trim().is_empty())
+        .ok_or("usage: watch <seconds> <command>")?;
 
-    run_cmd(&bash_cmd)
+    match interval.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+            Ok((Duration::from_secs_f64(seconds), command.trim()))
+        }
+        _ => Err(format!(
+            "interval must be a positive number of seconds, got '{interval}'"
+        )),
+    }
 }
 
-/// Provides an abstraction for installing packages with yum.
+/// Runs a command every `interval`, clearing the screen between runs, until a key or Ctrl-C
+/// is pressed.
 ///
-/// # Errors
-///
-/// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
-/// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+/// Each run is headed by the command and the time it ran. `display` is the command as typed,
+/// without the `cd` and environment variables added to it.
 ///
-fn install(password: &str) {
-    let package: String = msg_input("Package");
+/// # Examples
+/// ```plaintext
+/// >>>  watch 2 docker ps
+/// Every 2s: docker ps  14:03:05
+/// press any key to stop
+/// ```
+fn watch(config: &Config, interval: Duration, bash_cmd: &str, display: &str) {
+    if config.dry_run {
+        run_cmd(config, bash_cmd);
+        return;
+    }
+
+    if !io::stdin().is_terminal() {
+        println!("{} watch needs an interactive terminal\n", "error:".red());
+        return;
+    }
+
+    clear_interrupt();
+    loop {
+        let output: Output = run_cmd(config, bash_cmd);
+        let header: String = format!("Every {}s: {display}", interval.as_secs_f64());
+        let time: String = chrono::offset::Local::now().format("%H:%M:%S").to_string();
+
+        helpers::clear();
+        println!("{}  {}", header.bold(), time.dimmed());
+        println!("{}\n", "press any key to stop".dimmed());
+        print!("{}", String::from_utf8_lossy(&output.stdout));
 
-    let bash_cmd: String = format!("echo {password} | sudo yum install -y {package}");
+        let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+        if !output.status.success() && !stderr.trim().is_empty() {
+            println!("{} {}", "error:".red().bold(), stderr.trim_end().red());
+        }
+
+        if helpers::wait_for_key(interval) {
+            break;
+        }
+    }
 
-    helpers::print_cmd(&run_cmd(&bash_cmd));
+    clear_interrupt();
+    println!();
 }
 
-/// Provides an abstraction for removing packages with yum.
+/// Most attempts `retry` will make, so a typo like `retry 100` can't loop for minutes.
+///
+const RETRY_LIMIT: u32 = 10;
+
+/// How long `retry` waits between attempts.
+///
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Returns the number of attempts and the command from the arguments to `retry`.
 ///
 /// # Errors
 ///
-/// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
-/// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+/// - Returns [`Err`](Result) if the command is missing.
+/// - Returns [`Err`](Result) if the attempts aren't a number from 1 to [`RETRY_LIMIT`].
 ///
-fn remove(password: &str) {
-    let package = msg_input("Package");
-    let bash_cmd: String = format!("echo {password} | sudo yum remove -y {package}");
+/// # Examples
+/// ```rust
+/// assert_eq!(parse_retry("3 yum install -y nginx"), Ok((3, "yum install -y nginx")));
+/// ```
+fn parse_retry(args: &str) -> Result<(u32, &str), String> {
+    let (attempts, command) = args
+        .split_once(char::is_whitespace)
+        .filter(|(_, command)| !command.trim().is_empty())
+        .ok_or("usage: retry <attempts> <command>")?;
 
-    helpers::print_cmd(&run_cmd(&bash_cmd));
+    match attempts.parse::<u32>() {
+        Ok(attempts) if (1..=RETRY_LIMIT).contains(&attempts) => Ok((attempts, command.trim())),
+        _ => Err(format!(
+            "attempts must be a number from 1 to {RETRY_LIMIT}, got '{attempts}'"
+        )),
+    }
 }
 
-/// Displays a help message.
+/// Runs a command until it succeeds, up to `attempts` times, waiting [`RETRY_DELAY`] between
+/// attempts, and returns the output of the last one.
 ///
-fn cmd_help() {
-    println!("\nCOMMANDS");
-    println!("'any'         -> run a Linux cmd, ensure syntax is correct");
-    println!("'install'     -> install a package");
-    println!("'remove'      -> uninstall a package");
-    println!("'clear'       -> clears the terminal");
-    println!("'exit'        -> exit cmd");
+/// Ctrl-C stops retrying.
+///
+/// # Examples
+/// ```plaintext
+/// >>>  retry 3 curl -fsS localhost:8080/health
+/// retrying (2/3)...
+/// Succeeded on attempt 2/3
+/// ok
+/// ```
+fn retry(config: &Config, attempts: u32, bash_cmd: &str) -> Output {
+    clear_interrupt();
+
+    let mut attempt: u32 = 1;
+    loop {
+        let output: Output = run_cmd(config, bash_cmd);
+
+        if output.status.success() {
+            if attempt > 1 {
+                println!("{} on attempt {attempt}/{attempts}", "Succeeded".green());
+            }
+            return output;
+        }
+
+        if attempt >= attempts || interrupted() {
+            println!("{} after {attempt} attempts", "Failed".red());
+            return output;
+        }
+
+        thread::sleep(RETRY_DELAY);
+        attempt += 1;
+        println!("{} ({attempt}/{attempts})...", "retrying".yellow());
+    }
 }
 
-pub mod connect {
-    //! This module provides an API for ssh login to an EC2.
-    //!
-    //! ## License
-    //!
-    //! Copyright (C) Josh Bassett. All rights reserved.
-    //!
-    //! Apache 2.0
-    //!
+/// Prints lines as they are appended to a file on the EC2, like `tail -f`, until Ctrl-C is
+/// pressed.
+///
+/// Killing ssh doesn't stop a remote command without a terminal, so `tail` is watched by a
+/// subshell blocked reading stdin, which is held open for as long as the follow runs. Ctrl-C
+/// kills ssh, the channel closes, the read returns and the subshell kills `tail`. stdin is
+/// passed to the subshell as fd 3, since background commands otherwise read `/dev/null`.
+///
+/// # Examples
+/// ```plaintext
+/// >>>  follow /var/log/nginx/access.log
+/// ```
+fn follow(config: &Config, cwd: &Option<String>, path: &str) {
+    if path.is_empty() {
+        return println!("{} follow needs a path\n", "error:".red());
+    }
 
-    // Rust analyser genrates warning as env files are only known at compile time
-    include!(concat!(env!("OUT_DIR"), "/config.rs"));
+    let bash_cmd: String = format!(
+        "{{ exec 3<&0; tail -f {path} & tail=$!; (read -r _ <&3; kill $tail) >/dev/null 2>&1 & \
+        wait $tail; status=$?; kill $! 2>/dev/null; exit $status; }}"
+    );
 
-    use std::time::SystemTime;
+    println!("{}", "press Ctrl-C to stop".dimmed());
+    clear_interrupt();
 
-    use colored::Colorize;
+    let status: ExitStatus = run_cmd_streaming(config, &in_cwd(cwd, &bash_cmd), Stdio::piped());
+    if !status.success() && !interrupted() {
+        fail_script();
+    }
 
-    use crate::cmd::run_cmd;
+    clear_interrupt();
+    println!();
+}
 
-    /// Returns an ssh connection string.
-    ///
-    /// # Errors
-    ///
-    /// - Returns [`expect`](Result<Error>) if an env variable isn't reachable.
-    ///
-    pub fn ssh(bash_cmd: &str) -> String {
-        let pem: String = PEM.to_string();
-        let ec2: String = EC2.to_string();
+/// A local file that a command's output is saved to.
+///
+#[derive(Debug, PartialEq)]
+struct SaveTo<'a> {
+    path: &'a str,
+    append: bool,
+}
+
+/// Splits a trailing `| save <path>` or `|>> <path>` off a command, so only the command is run
+/// on the EC2. `|>>` appends to the file rather than replacing it.
+///
+/// Any other pipe, including one into a remote command that isn't `save`, is left alone.
+///
+/// # Examples
+/// ```rust
+/// let (command, save) = parse_save("du -sh * | sort -h | save sizes.txt");
+/// assert_eq!(command, "du -sh * | sort -h");
+/// assert_eq!(save, Some(SaveTo { path: "sizes.txt", append: false }));
+/// ```
+fn parse_save(bash_cmd: &str) -> (&str, Option<SaveTo<'_>>) {
+    let bash_cmd: &str = bash_cmd.trim_end();
+    let single_path =
+        |path: &str| -> bool { !path.is_empty() && !path.contains(char::is_whitespace) };
 
-        format!("ssh -i {} {} '{}'", pem, ec2, bash_cmd)
+    if let Some((command, path)) = bash_cmd.rsplit_once("|>>")
+        && !command.trim().is_empty()
+        && !command.ends_with('|')
+        && single_path(path.trim())
+    {
+        let save: SaveTo = SaveTo {
+            path: path.trim(),
+            append: true,
+        };
+        return (command.trim_end(), Some(save));
     }
 
-    /// Tests connection to EC2 using ssh.
-    ///
-    /// # Errors
-    ///
-    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
-    /// - Returns [`except`](std::process::Output) if current time is unreachable.
-    /// - Panics [`panic`](std::macros) if connection is unsuccessful.
-    ///
-    pub fn test_connection() {
-        let output: std::process::Output = run_cmd("echo test");
-        let now = SystemTime::now().elapsed().expect("unable to get time");
-        if output.status.success() {
-            println!(
-                "{} to EC2 on {} in {:?}\n",
-                "Connected".green(),
-                chrono::offset::Local::now().format("%a %b %e at %T"),
-                now
-            )
-        } else {
-            panic!("unable to connect to EC2")
-        }
+    if let Some((command, rest)) = bash_cmd.rsplit_once('|')
+        && !command.trim().is_empty()
+        && !command.ends_with('|')
+        && let Some(path) = rest.trim_start().strip_prefix("save ")
+        && single_path(path.trim())
+    {
+        let save: SaveTo = SaveTo {
+            path: path.trim(),
+            append: false,
+        };
+        return (command.trim_end(), Some(save));
     }
-}
 
-pub mod git {
-    //! This module provides an API for running git commands.
-    //!
-    //! ## License
-    //!
-    //! Copyright (C) Josh Bassett. All rights reserved.
-    //!
-    //! Apache 2.0
-    //!
+    (bash_cmd, None)
+}
 
-    use crate::cmd::{
-        helpers::{self, clear},
-        input, msg_input, run_cmd,
+/// Writes a command's stdout to the local file from a `| save` suffix and reports how many
+/// bytes were written.
+///
+/// Nothing is written under `--dry-run`, since the command didn't run.
+///
+/// # Examples
+/// ```plaintext
+/// >>>  journalctl -u nginx --since today | save nginx.log
+/// Saved 18204 bytes to nginx.log
+/// ```
+fn save_output(config: &Config, output: &Output, save: Option<SaveTo>) {
+    let Some(save) = save else {
+        return;
     };
 
-    /// Provides an API for running git commands.
-    ///
-    /// # Errors
-    ///
-    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
-    ///
-    /// # Examples
-    /// ```rust
-    /// run_git();
-    /// ```
-    /// Output
-    /// ```plaintext
-    /// [user@wcli ~]$ git   
-    /// Repo path: directory/repository
-    /// >>>  git status
-    /// On branch dev
-    /// ...
-    /// ```
-    pub fn run_git() {
-        let directory: String = msg_input("Repo path");
-        println!("Run 'help' for commands\n");
+    if config.dry_run {
+        return;
+    }
 
-        loop {
-            let user_cmd: String = input();
+    let written: io::Result<()> = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(save.append)
+        .truncate(!save.append)
+        .open(save.path)
+        .and_then(|mut file: fs::File| file.write_all(&output.stdout));
 
-            match user_cmd.trim() {
-                "exit" => break,
-                "change" => run_git(),
-                "clear" => clear(),
-                "help" => git_help(),
-                _ => git_cmd(directory.as_str().trim(), user_cmd.as_str()),
-            }
+    match written {
+        Ok(()) if save.append => {
+            println!("Appended {} bytes to {}\n", output.stdout.len(), save.path)
+        }
+        Ok(()) => println!("Saved {} bytes to {}\n", output.stdout.len(), save.path),
+        Err(e) => {
+            println!("{} unable to save to {}: {e}\n", "error:".red(), save.path);
+            fail_script();
         }
     }
+}
 
-    /// Runs a git command on the EC2 instance.
-    ///
-    /// # Errors
-    ///
-    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
-    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
-    ///
-    /// # Examples
-    /// ```rust
-    /// run_git();
-    /// ```
-    /// Output
-    /// ```plaintext
-    /// [user@wcli ~]$ git   
-    /// Repo path: directory/repository
-    /// >>>  git status
-    /// On branch dev
-    /// ...
-    /// ```
-    fn git_cmd(directory: &str, user_cmd: &str) {
-        let git_cmd: String = format!("cd {directory} && {user_cmd}");
+/// Returns the command prefixed with a `cd` into the tracked working directory, if there is
+/// one.
+///
+/// # Examples
+/// ```rust
+/// let cwd: Option<String> = Some("/var/log".to_string());
+/// assert_eq!(in_cwd(&cwd, "ls"), "cd \"/var/log\" && ls");
+/// ```
+fn in_cwd(cwd: &Option<String>, bash_cmd: &str) -> String {
+    match cwd {
+        Some(cwd) => format!("cd {} && {bash_cmd}", quote_value(cwd)),
+        None => bash_cmd.to_string(),
+    }
+}
 
-        let output = run_cmd(&git_cmd);
+/// Returns the path typed after `cd` quoted for the remote shell, so `cd my dir` is one
+/// directory and nothing in it is expanded. Quotes around the whole path are dropped and a
+/// leading `~/` is left unquoted so it still means the login directory.
+///
+/// # Examples
+/// ```rust
+/// assert_eq!(cd_target("~/my app"), "~/\"my app\"");
+/// ```
+fn cd_target(path: &str) -> String {
+    let unquoted: &str = ['"', '\'']
+        .iter()
+        .find_map(|&quote: &char| path.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(path);
 
-        helpers::print_cmd(&output);
+    match unquoted.strip_prefix("~/") {
+        Some(rest) => format!("~/{}", quote_value(rest)),
+        None => quote_value(unquoted),
     }
+}
 
-    /// Prints out a help message.
-    ///
-    fn git_help() {
-        println!("\nCOMMANDS");
-        println!("'any'     -> run a git command, ensure syntax is correct");
-        println!("'change'  -> change git directory");
-        println!("'clear'   -> clears the terminal");
-        println!("'exit'    -> exit git");
+/// Returns the new working directory after a `cd`.
+///
+/// The path is resolved on the EC2 relative to the current working directory, so `..`,
+/// absolute paths and `~` all behave as they would in a real shell. A bare `cd` or `cd ~` goes
+/// back to the login directory. With `--dry-run` nothing answers with the new directory, so the
+/// current one is kept.
+///
+/// # Errors
+///
+/// - Returns [`stderr`](std::process::Output) and keeps the current directory if the path
+///   doesn't exist.
+///
+fn change_dir(config: &Config, cwd: Option<String>, path: &str) -> Option<String> {
+    if path.is_empty() || path == "~" {
+        return None;
+    }
+
+    let bash_cmd: String = format!("cd {} && pwd", cd_target(path));
+    let output: Output = run_cmd(config, &in_cwd(&cwd, &bash_cmd));
+    let dir: String = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    if !output.status.success() {
+        helpers::print_cmd(&output);
+        cwd
+    } else if dir.is_empty() {
+        cwd
+    } else {
+        Some(dir)
     }
 }
 
-pub mod sql {
-    //! This module provides an API for running sql commands.
-    //!
-    //! ## License
+/// Runs a command on the local machine instead of the EC2, such as a quick `git status`
+/// without switching terminals.
+///
+/// The output is headed by a `[local]` tag so it can't be mistaken for the EC2's.
+///
+/// # Examples
+/// ```plaintext
+/// >>>  local git status --short
+/// [local] git status --short
+///  M src/cmd.rs
+/// ```
+pub fn local(config: &Config, command: &str) {
+    if command.is_empty() {
+        println!("{} usage: local <command>\n", "error:".red());
+        return;
+    }
+
+    println!("{} {command}", "[local]".magenta().bold());
+
+    let local_cmd: String = format!("bash -c '{command}'");
+    let output: Output = if config.dry_run {
+        dry_run(config, &local_cmd, None)
+    } else {
+        match Command::new("bash").arg("-c").arg(command).output() {
+            Ok(output) => output,
+            Err(e) => {
+                println!("{} unable to run bash: {e}\n", "error:".red());
+                fail_script();
+                return;
+            }
+        }
+    };
+
+    helpers::print_cmd(&output);
+}
+
+/// Opens a full interactive shell on the EC2 and returns to WCLI when it exits, for tools like
+/// `vim` or `top` that one command at a time can't handle.
+///
+/// # Examples
+/// ```plaintext
+/// [user@wcli ~]$ shell
+/// Opening a shell on staging, run 'exit' to come back to WCLI
+/// ```
+pub fn shell(config: &Config) {
+    let ssh_cmd: String = connect::interactive(config);
+    if config.dry_run {
+        dry_run(config, &ssh_cmd, None);
+        println!();
+        return;
+    }
+
+    if !io::stdin().is_terminal() {
+        println!("{} shell needs an interactive terminal\n", "error:".red());
+        return;
+    }
+
+    println!(
+        "Opening a shell on {}, run 'exit' to come back to WCLI\n",
+        config.host.bold()
+    );
+
+    let start: Instant = helpers::trace_command(config, &ssh_cmd);
+    let mut child: Child = match Command::new("bash").arg("-c").arg(&ssh_cmd).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            println!("{} unable to start ssh: {e}\n", "error:".red());
+            return;
+        }
+    };
+    helpers::trace_spawn(config, &child, start);
+
+    let status: ExitStatus = child.wait().expect("failed to wait for ssh");
+    helpers::trace_exit(config, start, &status, None);
+    logging::record(config, "shell", None, status.code());
+
+    if status.code() == Some(helpers::SSH_ERROR) {
+        println!("\n{} the ssh connection failed\n", "error:".red());
+    } else {
+        println!("\nBack in WCLI\n");
+    }
+}
+
+/// Runs a command from the `[commands]` section of the config file, with the arguments typed
+/// after its name filled into the template, see [`expand_template`].
+///
+/// The expanded command is printed before it runs, and one starting with `sudo` is given the
+/// password.
+///
+/// # Examples
+/// ```plaintext
+/// [user@wcli ~]$ tail-log nginx/error.log 20
+/// tail -n 20 /var/log/nginx/error.log
+/// ...
+/// ```
+pub fn custom(runner: &dyn CommandRunner, config: &Config, name: &str, template: &str, args: &str) {
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let bash_cmd: String = match expand_template(template, &args) {
+        Ok(bash_cmd) => bash_cmd,
+        Err(e) => return println!("{} '{name}' {e}\n", "error:".red()),
+    };
+
+    if !confirm_dangerous(config, &bash_cmd) {
+        return;
+    }
+
+    println!("{}", bash_cmd.dimmed());
+    let output: Output = if bash_cmd.starts_with("sudo ") {
+        runner.run_sudo(config, &bash_cmd)
+    } else {
+        runner.run(config, &bash_cmd)
+    };
+
+    helpers::print_cmd(&output);
+}
+
+/// Returns the template with each `{N}` replaced by the Nth argument, counting from 1. Other
+/// braces, like `awk '{print $1}'`, are kept as they are.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the template uses `{0}` or a placeholder with no argument.
+/// - Returns [`Err`](Result) if there are more arguments than the template uses.
+///
+/// # Examples
+/// ```rust
+/// let bash_cmd: String = expand_template("tail -n {2} /var/log/{1}", &["syslog", "20"])?;
+/// assert_eq!(bash_cmd, "tail -n 20 /var/log/syslog");
+/// ```
+fn expand_template(template: &str, args: &[&str]) -> Result<String, String> {
+    let mut expanded: String = String::new();
+    let mut used: usize = 0;
+    let mut rest: &str = template;
+
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let after: &str = &rest[start + 1..];
+        let digits: usize = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+
+        let index: Option<usize> = if after[digits..].starts_with('}') {
+            after[..digits].parse().ok()
+        } else {
+            None
+        };
+        match index {
+            Some(0) => return Err("uses {0}, arguments start at {1}".to_string()),
+            Some(index) => {
+                let arg: &str = args
+                    .get(index - 1)
+                    .ok_or(format!("has no argument for {{{index}}}"))?;
+                expanded.push_str(arg);
+                used = used.max(index);
+                rest = &after[digits + 1..];
+            }
+            None => {
+                expanded.push('{');
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+
+    if args.len() > used {
+        return Err("was given more arguments than it uses".to_string());
+    }
+
+    Ok(expanded)
+}
+
+/// The result of a `wcli exec` command, printed with `--format json`.
+///
+/// `exit_code` is [`None`] if the command was killed by a signal.
+///
+#[derive(Serialize)]
+struct ExecResult<'a> {
+    host: &'a str,
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    duration_ms: u128,
+}
+
+/// Runs a single command non-interactively, for `wcli exec`.
+///
+/// The remote stdout and stderr are passed through as is and the remote exit code is returned
+/// so it can become the exit code of WCLI. With `json` they are printed as a single
+/// [`ExecResult`] object instead, with any invalid UTF-8 replaced.
+///
+/// # Examples
+/// ```plaintext
+/// wcli exec "ls -l /var/log"
+/// wcli exec --sudo "yum update -y"
+/// wcli --format json exec "uptime"
+/// ```
+pub fn exec(config: &Config, bash_cmd: &str, sudo: bool, json: bool) -> i32 {
+    logging::set_shell("exec");
+
+    let start: Instant = Instant::now();
+    let output: Output = if sudo {
+        let sudo_cmd: String = if bash_cmd.trim().starts_with("sudo ") {
+            bash_cmd.to_string()
+        } else {
+            format!("sudo {}", bash_cmd.trim())
+        };
+
+        run_cmd_sudo(config, &sudo_cmd)
+    } else {
+        run_cmd(config, bash_cmd)
+    };
+    let elapsed: Duration = start.elapsed();
+
+    let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr: String = helpers::redact(&String::from_utf8_lossy(&output.stderr), &config.pass);
+
+    if json {
+        let result: ExecResult = ExecResult {
+            host: &config.host,
+            stdout,
+            stderr,
+            exit_code: output.status.code(),
+            duration_ms: elapsed.as_millis(),
+        };
+
+        match serde_json::to_string(&result) {
+            Ok(line) => println!("{line}"),
+            Err(e) => eprintln!("{} {e}", "error:".red()),
+        }
+    } else {
+        print!("{stdout}");
+        eprint!("{stderr}");
+    }
+
+    helpers::exit_with(&output)
+}
+
+/// Most hosts a broadcast runs on at once, so a large config doesn't spawn an ssh per host.
+///
+const BROADCAST_LIMIT: usize = 8;
+
+/// Runs a single command on every host at once and prints each host's output under its name,
+/// followed by a summary of which hosts succeeded and which failed.
+///
+/// Up to [`BROADCAST_LIMIT`] hosts are run concurrently. Returns 0 if the command succeeded
+/// on every host and 1 otherwise.
+///
+/// # Examples
+/// ```plaintext
+/// wcli broadcast "df -h /"
+/// ```
+pub fn broadcast(configs: &[Config], bash_cmd: &str, sudo: bool) -> i32 {
+    logging::set_shell("broadcast");
+    let bash_cmd: String = broadcast_cmd(bash_cmd, sudo);
+
+    if configs.iter().any(|config: &Config| config.dry_run) {
+        for config in configs {
+            let input: Option<String> = sudo.then(|| format!("{}\n", config.pass));
+            dry_run(config, &connect::ssh(config, &bash_cmd), input.as_deref());
+        }
+        return 0;
+    }
+
+    let next: Mutex<usize> = Mutex::new(0);
+    let outputs: Mutex<Vec<Option<Output>>> = Mutex::new(vec![None; configs.len()]);
+
+    helpers::with_spinner(|| {
+        thread::scope(|scope| {
+            for _ in 0..configs.len().min(BROADCAST_LIMIT) {
+                scope.spawn(|| {
+                    loop {
+                        let index: usize = {
+                            let mut next = next.lock().unwrap_or_else(|e| e.into_inner());
+                            *next += 1;
+                            *next - 1
+                        };
+                        let Some(config) = configs.get(index) else {
+                            break;
+                        };
+
+                        let output: Output = broadcast_to(config, &bash_cmd, sudo);
+                        outputs.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(output);
+                    }
+                });
+            }
+        })
+    });
+
+    let outputs: Vec<Option<Output>> = outputs.into_inner().unwrap_or_else(|e| e.into_inner());
+    let mut succeeded: Vec<&str> = Vec::new();
+    let mut failed: Vec<String> = Vec::new();
+
+    for (config, output) in configs.iter().zip(outputs.iter().flatten()) {
+        println!("{}", format!("==> {} <==", config.host).cyan().bold());
+        helpers::print_cmd(output);
+
+        if output.status.success() {
+            succeeded.push(&config.host);
+        } else {
+            let code: String = output
+                .status
+                .code()
+                .map_or("signal".to_string(), |code: i32| code.to_string());
+            failed.push(format!("{} (exit {code})", config.host));
+        }
+    }
+
+    if !succeeded.is_empty() {
+        println!("{} {}", "Succeeded:".green(), succeeded.join(", "));
+    }
+    if !failed.is_empty() {
+        println!("{} {}", "Failed:".red(), failed.join(", "));
+    }
+
+    if failed.is_empty() { 0 } else { 1 }
+}
+
+/// Runs a single command on two hosts at once and prints a unified diff of their stdout, or
+/// that they are identical, to catch drift between hosts like staging and prod.
+///
+/// Returns 0 if the output is identical, 1 if it differs and 2 if the command failed on
+/// either host, following `diff`.
+///
+/// # Examples
+/// ```plaintext
+/// wcli diff staging prod "cat /etc/nginx/nginx.conf"
+/// ```
+pub fn diff(configs: &[Config; 2], bash_cmd: &str, sudo: bool) -> i32 {
+    logging::set_shell("diff");
+    let bash_cmd: String = broadcast_cmd(bash_cmd, sudo);
+
+    if configs.iter().any(|config: &Config| config.dry_run) {
+        for config in configs {
+            let input: Option<String> = sudo.then(|| format!("{}\n", config.pass));
+            dry_run(config, &connect::ssh(config, &bash_cmd), input.as_deref());
+        }
+        return 0;
+    }
+
+    let [old, new]: [Output; 2] = helpers::with_spinner(|| {
+        thread::scope(|scope| {
+            let old = scope.spawn(|| broadcast_to(&configs[0], &bash_cmd, sudo));
+            let new: Output = broadcast_to(&configs[1], &bash_cmd, sudo);
+            let old: Output = old.join().unwrap_or_else(|e| std::panic::resume_unwind(e));
+            [old, new]
+        })
+    });
+
+    let mut failed: Vec<String> = Vec::new();
+    for (config, output) in configs.iter().zip([&old, &new]) {
+        if !output.status.success() {
+            println!("{}", format!("==> {} <==", config.host).cyan().bold());
+            helpers::print_cmd(output);
+
+            let code: String = output
+                .status
+                .code()
+                .map_or("signal".to_string(), |code: i32| code.to_string());
+            failed.push(format!("{} (exit {code})", config.host));
+        }
+    }
+    if !failed.is_empty() {
+        println!(
+            "{} {}, nothing was compared",
+            "Failed:".red(),
+            failed.join(", ")
+        );
+        return 2;
+    }
+
+    let lines: Vec<String> = helpers::unified_diff(
+        &String::from_utf8_lossy(&old.stdout),
+        &String::from_utf8_lossy(&new.stdout),
+    );
+    let (old_host, new_host) = (&configs[0].host, &configs[1].host);
+
+    if lines.is_empty() {
+        println!("{} {old_host} and {new_host} are identical", "✓".green());
+        return 0;
+    }
+
+    println!("{}", format!("--- {old_host}").red().bold());
+    println!("{}", format!("+++ {new_host}").green().bold());
+    for line in &lines {
+        match line.chars().next() {
+            Some('@') => println!("{}", line.cyan()),
+            Some('-') => println!("{}", line.red()),
+            Some('+') => println!("{}", line.green()),
+            _ => println!("{line}"),
+        }
+    }
+
+    1
+}
+
+/// Returns the command to run on each host, passing the password to `sudo` on stdin if asked.
+///
+fn broadcast_cmd(command: &str, sudo: bool) -> String {
+    let command: &str = command.trim();
+
+    if sudo {
+        sudo_stdin(&format!("sudo {}", command.trim_start_matches("sudo ")))
+    } else {
+        command.to_string()
+    }
+}
+
+/// Runs the broadcast command on one host, recording it in the audit log.
+///
+/// The password is redacted from stderr when the command was run with sudo.
+///
+fn broadcast_to(config: &Config, bash_cmd: &str, sudo: bool) -> Output {
+    let input: Option<String> = sudo.then(|| format!("{}\n", config.pass));
+    let mut output: Output = ssh_output(config, bash_cmd, input.as_deref());
+
+    if sudo {
+        let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+        output.stderr = helpers::redact(&stderr, &config.pass).into_bytes();
+    }
+
+    logging::record(config, bash_cmd, input.as_deref(), output.status.code());
+
+    output
+}
+
+/// Values filled into the placeholders of the `prompt` template.
+///
+/// `branch` is only set in the git shell and `status` only in the main prompt.
+///
+#[derive(Default)]
+pub struct PromptVars<'a> {
+    pub user: &'a str,
+    pub cwd: &'a str,
+    pub branch: &'a str,
+    pub shell: &'a str,
+    pub status: &'a str,
+}
+
+/// Returns the prompt for a shell, rendered from the `prompt` template when one is set and
+/// `default` otherwise.
+///
+/// # Examples
+/// ```rust
+/// let vars: PromptVars = PromptVars { user: "josh", shell: "sql", ..PromptVars::default() };
+/// let prompt: String = shell_prompt(&config, ">>> ", &vars);
+/// ```
+pub fn shell_prompt(config: &Config, default: &str, vars: &PromptVars) -> String {
+    match &config.prompt {
+        Some(template) => render_prompt(template, &config.host, vars),
+        None => default.to_string(),
+    }
+}
+
+/// Fills in the placeholders of a prompt template.
+///
+/// `{user}`, `{host}`, `{cwd}`, `{branch}`, `{shell}` and `{status}` are replaced, anything
+/// else is kept as typed.
+///
+/// # Examples
+/// ```rust
+/// let vars: PromptVars = PromptVars { user: "josh", cwd: "~", ..PromptVars::default() };
+/// assert_eq!(render_prompt("{user}@{host}:{cwd}$", "staging", &vars), "josh@staging:~$");
+/// ```
+pub fn render_prompt(template: &str, host: &str, vars: &PromptVars) -> String {
+    template
+        .replace("{user}", vars.user)
+        .replace("{host}", host)
+        .replace("{cwd}", vars.cwd)
+        .replace("{branch}", vars.branch)
+        .replace("{shell}", vars.shell)
+        .replace("{status}", vars.status)
+}
+
+/// Line editor for a sub-shell, with history persisted to a file in the home directory.
+///
+/// Each sub-shell keeps its own history file so that, for example, sql queries don't show up
+/// in the cmd history. New entries are flushed to the file when the editor is dropped.
+///
+/// `idle_timeout` is how long a terminal is waited on for input before giving up, see
+/// [`LineEditor::set_idle_timeout`].
+///
+pub struct LineEditor {
+    editor: Editor<ShellHelper, DefaultHistory>,
+    history: Option<PathBuf>,
+    idle_timeout: Option<Duration>,
+}
+
+/// Tab completes the commands of a sub-shell.
+///
+pub struct ShellHelper {
+    commands: &'static [&'static str],
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let typed: &str = line[..pos].trim_start();
+
+        Ok((pos - typed.len(), complete(self.commands, typed)))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+impl LineEditor {
+    /// Creates a line editor and loads the history for the sub-shell.
+    ///
+    /// Tab completes the commands of the sub-shell, see [`shell_commands`].
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](core::result::Result<Error>) if the terminal can't be set up.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let mut editor: LineEditor = LineEditor::new("git");
+    /// ```
+    pub fn new(shell: &str) -> LineEditor {
+        let mut editor: Editor<ShellHelper, DefaultHistory> =
+            Editor::new().expect("failed to create editor");
+        let history: Option<PathBuf> = history_path(shell);
+
+        editor.set_helper(Some(ShellHelper {
+            commands: shell_commands(shell),
+        }));
+
+        if let Some(path) = &history {
+            editor.load_history(path).ok();
+        }
+
+        LineEditor {
+            editor,
+            history,
+            idle_timeout: None,
+        }
+    }
+
+    /// Makes reading from the terminal fail with [`io::ErrorKind::TimedOut`] once nothing has
+    /// been typed for `timeout`, or never if it's [`None`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// editor.set_idle_timeout(Some(Duration::from_secs(30 * 60)));
+    /// ```
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Returns user input read with the given prompt and adds it to the history.
+    ///
+    /// Like [`read_line`](std::io::Stdin::read_line), the line keeps its trailing newline and
+    /// an empty string is returned at the end of input.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](io::Result) if the terminal can't be read.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let input: String = editor.prompt_input("(dev) >>> ")?;
+    /// ```
+    pub fn prompt_input(&mut self, prompt: &str) -> io::Result<String> {
+        let input: String = self.read(prompt)?;
+        let input: String = self.recall(&input).unwrap_or(input);
+        self.add_history(&input);
+
+        Ok(input)
+    }
+
+    /// Returns a command that may span several lines of user input and adds it to the history.
+    ///
+    /// A line ending with `\` continues onto the next line, and a `<<EOF` heredoc collects
+    /// lines until a lone `EOF`. Newlines are preserved so the remote shell sees the command as
+    /// typed.
+    ///
+    /// An empty string is returned at the end of input, while a blank line is returned as `\n`.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](io::Result) if the terminal can't be read.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  cd Directory && \
+    /// ...  ls -l
+    /// >>>  cat <<EOF > notes.txt
+    /// ...  first line
+    /// ...  EOF
+    /// ```
+    pub fn multiline_input(&mut self, prompt: &str) -> io::Result<String> {
+        let first: String = self.read(prompt)?;
+        if first.is_empty() {
+            return Ok(first);
+        }
+
+        if let Some(recalled) = self.recall(&first) {
+            self.add_history(&recalled);
+            return Ok(recalled);
+        }
+
+        let mut lines: Vec<String> = vec![first.trim_end().to_string()];
+
+        while lines.last().is_some_and(|line| line.ends_with('\\')) {
+            let line: String = self.read("... ")?;
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line.trim_end().to_string());
+        }
+
+        if let Some(delimiter) = heredoc_delimiter(&lines.join("\n")) {
+            loop {
+                let line: String = self.read("... ")?;
+                if line.is_empty() {
+                    break;
+                }
+                lines.push(line.trim_end().to_string());
+                if line.trim() == delimiter {
+                    break;
+                }
+            }
+        }
+
+        let input: String = format!("{}\n", lines.join("\n"));
+        self.add_history(&input);
+
+        Ok(input)
+    }
+
+    /// Returns a line of user input after printing the given prompt.
+    ///
+    /// Ctrl-C discards the current line and Ctrl-D ends the input. When several lines are
+    /// pasted at once, the first is returned and the rest are queued, see [`queue_paste`].
+    ///
+    /// With an idle timeout the prompt is printed while waiting for the first key, which the
+    /// editor then draws over.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](io::Result) if the terminal can't be read.
+    /// - Returns [`Err`](io::Result) of kind [`io::ErrorKind::TimedOut`] if nothing was typed
+    ///   within the idle timeout.
+    ///
+    fn read(&mut self, prompt: &str) -> io::Result<String> {
+        if let Some(line) = next_script_line() {
+            println!("{} {line}", prompt.purple());
+            return Ok(format!("{line}\n"));
+        }
+
+        if let Some(timeout) = self.idle_timeout
+            && io::stdin().is_terminal()
+        {
+            print!("{} ", prompt.purple());
+            io::stdout().flush()?;
+
+            if !helpers::wait_for_input(timeout) {
+                println!();
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "no input"));
+            }
+            print!("\r");
+        }
+
+        match self.editor.readline(&format!("{} ", prompt.purple())) {
+            Ok(line) if line.contains('\n') => Ok(format!("{}\n", queue_paste(&line))),
+            Ok(line) => Ok(format!("{line}\n")),
+            Err(ReadlineError::Interrupted) => Ok("\n".to_string()),
+            Err(ReadlineError::Eof) => {
+                println!();
+                Ok(String::new())
+            }
+            Err(ReadlineError::Io(e)) => Err(e),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    /// Prints the history of the sub-shell numbered from the oldest entry, for `!N`.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  history
+    ///     1  cd /var/log
+    ///     2  ls -l
+    ///     3  history
+    /// ```
+    pub fn print_history(&self) {
+        for (index, entry) in self.editor.history().iter().enumerate() {
+            println!("{:>5}  {}", index + 1, entry.replace('\n', "\n       "));
+        }
+
+        println!();
+    }
+
+    /// Returns the command a `!!` or `!N` line recalls from the history, after printing it, or
+    /// [`None`] if the line doesn't start with one.
+    ///
+    /// A reference to a command that isn't in the history is reported and returned as a blank
+    /// line, so the shell skips it.
+    ///
+    fn recall(&self, line: &str) -> Option<String> {
+        let entries: Vec<&str> = self.editor.history().iter().map(String::as_str).collect();
+
+        match expand_history(&entries, line) {
+            Ok(Some(command)) => {
+                println!("{}", command.trim_end());
+                Some(command)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                println!("{} {e}\n", "error:".red());
+                Some("\n".to_string())
+            }
+        }
+    }
+
+    /// Adds a non-empty line to the history.
+    ///
+    fn add_history(&mut self, line: &str) {
+        if !line.trim().is_empty() {
+            self.editor.add_history_entry(line.trim()).ok();
+        }
+    }
+}
+
+impl Drop for LineEditor {
+    fn drop(&mut self) {
+        if let Some(path) = &self.history {
+            self.editor.append_history(path).ok();
+        }
+    }
+}
+
+/// Returns the line with a leading `!!` replaced by the last history entry, or `!N` by entry
+/// `N` counting from 1, keeping anything typed after it. Returns [`None`] if the line doesn't
+/// start with either.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the history is empty or has no entry `N`.
+///
+/// # Examples
+/// ```rust
+/// assert_eq!(expand_history(&["ls", "pwd"], "!1 -l\n"), Ok(Some("ls -l\n".to_string())));
+/// ```
+fn expand_history(entries: &[&str], line: &str) -> Result<Option<String>, String> {
+    let Some(reference) = line.trim().strip_prefix('!') else {
+        return Ok(None);
+    };
+    let (token, rest) = reference
+        .split_once(char::is_whitespace)
+        .unwrap_or((reference, ""));
+
+    let entry: &str = if token == "!" {
+        entries.last().ok_or("no previous command in history")?
+    } else if let Ok(index) = token.parse::<usize>() {
+        index
+            .checked_sub(1)
+            .and_then(|index: usize| entries.get(index))
+            .ok_or(format!(
+                "!{index}: no such command, history has {} entries",
+                entries.len()
+            ))?
+    } else {
+        return Ok(None);
+    };
+
+    match rest.trim() {
+        "" => Ok(Some(format!("{entry}\n"))),
+        rest => Ok(Some(format!("{entry} {rest}\n"))),
+    }
+}
+
+/// Returns the commands built into a shell, `wcli` being the main loop.
+///
+pub fn shell_commands(shell: &str) -> &'static [&'static str] {
+    match shell {
+        "wcli" => &[
+            "cmd",
+            "git",
+            "sql",
+            "test",
+            "status",
+            "ping",
+            "reconnect",
+            "load",
+            "local",
+            "shell",
+            "profile",
+            "cheatsheet",
+            "history",
+            "clear",
+            "help",
+            "exit",
+        ],
+        "cmd" => &[
+            "cd",
+            "pwd",
+            "local",
+            "env",
+            "alias",
+            "unalias",
+            "sudo",
+            "install",
+            "remove",
+            "update",
+            "upgrade",
+            "push",
+            "pull",
+            "view",
+            "ps",
+            "kill",
+            "stream",
+            "follow",
+            "retry",
+            "timings",
+            "timestamps",
+            "timeout",
+            "watch",
+            "history",
+            "clear",
+            "help",
+            "exit",
+        ],
+        "git" => &[
+            "change",
+            "clone",
+            "branches",
+            "checkout",
+            "timestamps",
+            "history",
+            "clear",
+            "help",
+            "exit",
+        ],
+        "sql" => &[
+            "database",
+            "change",
+            "raw",
+            "tables",
+            "describe",
+            "rerun",
+            "edit",
+            "export",
+            "source",
+            "begin",
+            "commit",
+            "rollback",
+            "force",
+            "timestamps",
+            "history",
+            "clear",
+            "help",
+            "exit",
+        ],
+        _ => &[],
+    }
+}
+
+/// Returns the commands starting with the typed word, or none once the line has an argument.
+///
+/// # Examples
+/// ```rust
+/// assert_eq!(complete(&["change", "clear", "exit"], "c"), vec!["change", "clear"]);
+/// ```
+fn complete(commands: &[&str], line: &str) -> Vec<String> {
+    if line.contains(char::is_whitespace) {
+        return Vec::new();
+    }
+
+    commands
+        .iter()
+        .filter(|command: &&&str| command.starts_with(line))
+        .map(|command: &&str| command.to_string())
+        .collect()
+}
+
+/// Returns the history file for a sub-shell, `~/.wcli_history` for cmd and
+/// `~/.wcli_history_<shell>` for the others, including `wcli` for the main loop.
+///
+fn history_path(shell: &str) -> Option<PathBuf> {
+    let name: String = match shell {
+        "cmd" => ".wcli_history".to_string(),
+        _ => format!(".wcli_history_{shell}"),
+    };
+
+    config::home_dir().map(|home: PathBuf| home.join(name))
+}
+
+/// Returns the delimiter of a heredoc opened in the command, e.g. `EOF` for `cat <<EOF`.
+///
+fn heredoc_delimiter(bash_cmd: &str) -> Option<String> {
+    let (_, rest) = bash_cmd.split_once("<<")?;
+
+    let delimiter: String = rest
+        .trim_start_matches('-')
+        .split_whitespace()
+        .next()?
+        .trim_matches(|c: char| c == '\'' || c == '"')
+        .to_string();
+
+    let is_word: bool = delimiter
+        .chars()
+        .all(|c: char| c.is_alphanumeric() || c == '_');
+
+    if delimiter.is_empty() || !is_word {
+        None
+    } else {
+        Some(delimiter)
+    }
+}
+
+/// Returns user input with a prompt, or an empty string at the end of input.
+///
+/// # Errors
+///
+/// - Returns [`Err`](io::Result) if stdout fails to flush.
+/// - Returns [`Err`](io::Result) if input is unreadable.
+///
+fn msg_input(msg: &str) -> io::Result<String> {
+    if let Some(line) = next_script_line() {
+        println!("{msg}: {line}");
+        return Ok(format!("{line}\n"));
+    }
+
+    print!("{msg}: ");
+    io::stdout().flush()?;
+
+    let mut input: String = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        println!();
+    }
+
+    Ok(input)
+}
+
+/// Returns the input, or [`None`] at the end of input or if it couldn't be read, which callers
+/// treat as `exit`.
+///
+/// # Examples
+/// ```rust
+/// let Some(input) = or_exit(editor.prompt_input(">>> ")) else {
+///     break;
+/// };
+/// ```
+pub fn or_exit(input: io::Result<String>) -> Option<String> {
+    match input {
+        Ok(line) if line.is_empty() => None,
+        Ok(line) => Some(line),
+        Err(e) => {
+            eprintln!("{} unable to read input: {e}", "error:".red());
+            None
+        }
+    }
+}
+
+/// Lines queued by [`load`] or pasted several at once, which are answered before reading from
+/// the terminal.
+///
+/// `keep_going` is set by `load --continue` to run the rest of the script after a failure.
+/// `pasted` is set when the lines came from a paste, so a failure asks whether to carry on
+/// instead of stopping.
+///
+struct Script {
+    lines: VecDeque<String>,
+    keep_going: bool,
+    pasted: bool,
+}
+
+static SCRIPT: Mutex<Script> = Mutex::new(Script {
+    lines: VecDeque::new(),
+    keep_going: false,
+    pasted: false,
+});
+
+/// Queues the lines of a local file to be run as if they were typed, for the `load` command.
+///
+/// Each line goes through the same prompts as interactive input, so a script can switch into
+/// sub-shells and answer their questions. The script stops at the first command that fails
+/// unless `keep_going` is set.
+///
+/// # Errors
+///
+/// - Prints an error if the file can't be read.
+///
+/// # Examples
+/// ```plaintext
+/// [user@wcli ~]$ load deploy.wcli --continue
+/// ```
+pub fn load(path: &str, keep_going: bool) {
+    if path.is_empty() {
+        println!("{} load requires a file name\n", "error:".red());
+        return;
+    }
+
+    let contents: String = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            println!("{} unable to read {path}: {e}\n", "error:".red());
+            return;
+        }
+    };
+
+    let mut script = SCRIPT.lock().unwrap_or_else(|e| e.into_inner());
+    script.keep_going = keep_going;
+    script.pasted = false;
+
+    for line in contents.lines().rev() {
+        script.lines.push_front(line.to_string());
+    }
+}
+
+/// Queues every line of a paste after the first to run one at a time, and returns the first.
+///
+/// A terminal with bracketed paste hands the whole paste over as one line, which would
+/// otherwise be sent to the EC2 as a single command.
+///
+fn queue_paste(paste: &str) -> String {
+    let mut lines: std::str::Lines<'_> = paste.lines();
+    let first: String = lines.next().unwrap_or("").to_string();
+
+    let mut script = SCRIPT.lock().unwrap_or_else(|e| e.into_inner());
+    script.keep_going = false;
+    script.pasted = true;
+
+    for line in lines.rev() {
+        script.lines.push_front(line.to_string());
+    }
+
+    first
+}
+
+/// Asks for `yes` before a command matching one of the dangerous patterns is run, naming the
+/// host so it isn't run against the wrong one. Returns whether to go ahead.
+///
+/// Commands that don't match, and every command under `--dry-run`, go ahead without asking. A
+/// cancelled command stops a loaded script.
+///
+/// # Examples
+/// ```plaintext
+/// >>>  rm -rf build
+/// warning: 'rm -rf build' matches 'rm -rf' and may be destructive
+/// It will run on staging (ec2-user@ec2-yyyyyyyy.compute.amazonaws.com)
+/// Type 'yes' to run it on staging: yes
+/// ```
+fn confirm_dangerous(config: &Config, command: &str) -> bool {
+    if config.dry_run {
+        return true;
+    }
+
+    let Some(pattern) = dangerous_pattern(&config.dangerous_patterns, command) else {
+        return true;
+    };
+
+    println!(
+        "{} '{}' matches '{pattern}' and may be destructive",
+        "warning:".yellow().bold(),
+        command.trim()
+    );
+
+    confirm_host(config)
+}
+
+/// Asks the user to type `yes` to run something on the host, saying which host it is.
+/// Returns whether to go ahead, stopping a loaded script if not.
+///
+fn confirm_host(config: &Config) -> bool {
+    println!(
+        "It will run on {} ({})",
+        config.host.red().bold(),
+        connect::target(&config.ssh_user, &config.ec2)
+    );
+
+    let question: String = format!("Type 'yes' to run it on {}", config.host);
+    let answer: String = or_exit(msg_input(&question)).unwrap_or_default();
+    if answer.trim() == "yes" {
+        return true;
+    }
+
+    println!("Cancelled\n");
+    fail_script();
+    false
+}
+
+/// Returns the first pattern the command contains, ignoring case and repeated spaces.
+///
+/// # Examples
+/// ```rust
+/// let patterns: Vec<String> = vec!["drop table".to_string()];
+/// assert_eq!(dangerous_pattern(&patterns, "DROP  TABLE Users;"), Some("drop table"));
+/// ```
+fn dangerous_pattern<'a>(patterns: &'a [String], command: &str) -> Option<&'a str> {
+    let normalise = |text: &str| -> String {
+        text.split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .to_lowercase()
+    };
+    let command: String = normalise(command);
+
+    patterns
+        .iter()
+        .map(String::as_str)
+        .find(|pattern: &&str| !pattern.trim().is_empty() && command.contains(&normalise(pattern)))
+}
+
+/// Returns the next line of a loaded script, if one is running.
+///
+fn next_script_line() -> Option<String> {
+    SCRIPT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .lines
+        .pop_front()
+}
+
+/// Stops a loaded script after a failed command, unless it was loaded with `--continue`.
+///
+/// The rest of a paste is only run if the user asks to continue.
+///
+fn fail_script() {
+    let mut script = SCRIPT.lock().unwrap_or_else(|e| e.into_inner());
+
+    if script.keep_going || script.lines.is_empty() {
+        return;
+    }
+
+    if !script.pasted {
+        println!(
+            "{} load stopped, skipping the remaining {} lines\n",
+            "error:".red(),
+            script.lines.len()
+        );
+        script.lines.clear();
+        return;
+    }
+
+    let remaining: VecDeque<String> = std::mem::take(&mut script.lines);
+    drop(script);
+
+    let question: String = format!(
+        "Continue with the remaining {} pasted lines? [y/N]",
+        remaining.len()
+    );
+    let answer: String = or_exit(msg_input(&question)).unwrap_or_default();
+
+    if answer.trim().eq_ignore_ascii_case("y") {
+        let mut script = SCRIPT.lock().unwrap_or_else(|e| e.into_inner());
+        for line in remaining.into_iter().rev() {
+            script.lines.push_front(line);
+        }
+    } else {
+        println!("Skipped the remaining {} pasted lines\n", remaining.len());
+    }
+}
+
+/// Runs commands on the EC2 for the command builders, so tests can swap ssh for a runner that
+/// records each command instead of sending it.
+///
+pub trait CommandRunner {
+    /// Returns Output of bash command, see [`run_cmd`].
+    ///
+    fn run(&self, config: &Config, bash_cmd: &str) -> Output;
+
+    /// Returns Output of sudo command, see [`run_cmd_sudo`].
+    ///
+    fn run_sudo(&self, config: &Config, sudo_cmd: &str) -> Output;
+
+    /// Returns Output of bash command with `input` written to its stdin, see
+    /// [`run_cmd_input`].
+    ///
+    fn run_input(&self, config: &Config, bash_cmd: &str, input: &str) -> Output;
+}
+
+/// Runs commands on the EC2 over ssh.
+///
+pub struct Ssh;
+
+impl CommandRunner for Ssh {
+    fn run(&self, config: &Config, bash_cmd: &str) -> Output {
+        run_cmd(config, bash_cmd)
+    }
+
+    fn run_sudo(&self, config: &Config, sudo_cmd: &str) -> Output {
+        run_cmd_sudo(config, sudo_cmd)
+    }
+
+    fn run_input(&self, config: &Config, bash_cmd: &str, input: &str) -> Output {
+        run_cmd_input(config, bash_cmd, input)
+    }
+}
+
+/// Returns Output of bash command from EC2.
+///
+/// # Errors
+///
+/// - Returns [`expect`](Result<Error>) if input is unreadable.
+///
+fn run_cmd(config: &Config, bash_cmd: &str) -> Output {
+    if config.dry_run {
+        return dry_run(config, &connect::ssh(config, bash_cmd), None);
+    }
+
+    let output: Output = helpers::with_spinner(|| ssh_output(config, bash_cmd, None));
+    logging::record(config, bash_cmd, None, output.status.code());
+
+    output
+}
+
+/// Runs a bash command on the EC2 over ssh and waits for its output, writing `input` to its
+/// stdin if given.
+///
+/// # Errors
+///
+/// - Returns [`expect`](Result<Error>) if the command can't be spawned or waited on.
+/// - Returns [`expect`](Result<Error>) if the input can't be written to stdin.
+///
+fn ssh_output(config: &Config, bash_cmd: &str, input: Option<&str>) -> Output {
+    let ssh_cmd: String = match config.command_timeout_secs {
+        0 => connect::ssh(config, bash_cmd),
+        secs => connect::ssh(config, &with_remote_timeout(bash_cmd, secs)),
+    };
+    let start: Instant = helpers::trace_command(config, &ssh_cmd);
+    let mut child: Child = Command::new("bash")
+        .arg("-c")
+        .arg(&ssh_cmd)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to execute remote command");
+    helpers::trace_spawn(config, &child, start);
+
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .expect("failed to open stdin")
+            .write_all(input.as_bytes())
+            .expect("failed to write to stdin");
+    }
+
+    let _running: Running = Running::track(&child);
+    let output: Output = wait_with_timeout(child, config.command_timeout_secs);
+    helpers::trace_exit(config, start, &output.status, Some(&output));
+
+    output
+}
+
+/// How much longer than the timeout the local ssh is given before it is stopped too, so the
+/// EC2 has time to stop the command itself and say so.
+///
+const TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+/// Returns the command wrapped so the EC2 stops it after `secs`, since stopping the local ssh
+/// doesn't reach a command running without a terminal.
+///
+/// A background timer sends `SIGTERM` to the command's process group, which sshd makes its
+/// own, after printing a `timed out` line to stderr. The command runs in a subshell so an `exit`
+/// in it still reaches the end, where the timer is stopped if the command finished first, so
+/// nothing it started in the background is killed later. The timer's
+/// `sleep` doesn't hold stdout open, so ssh returns as soon as the command is done.
+///
+/// # Examples
+/// ```rust
+/// let ssh_cmd: String = connect::ssh(config, &with_remote_timeout("make build", 1800));
+/// ```
+fn with_remote_timeout(bash_cmd: &str, secs: u64) -> String {
+    format!(
+        "(sleep {secs} >/dev/null 2>&1; echo \"{}\" >&2; kill -TERM 0) & wcli_timer=$!; \
+         ( {bash_cmd}\n); wcli_status=$?; kill $wcli_timer 2>/dev/null; exit $wcli_status",
+        timed_out(secs)
+    )
+}
+
+/// Returns the line a command stopped after `secs` has for its stderr.
+///
+fn timed_out(secs: u64) -> String {
+    format!("timed out after {secs}s")
+}
+
+/// Waits for the child's output. If the EC2 stopped the command after `timeout_secs`, see
+/// [`with_remote_timeout`], or the local ssh is still running [`TIMEOUT_GRACE`] later and is
+/// stopped with `SIGTERM`, the command timed out. It never does if `timeout_secs` is `0`.
+///
+/// A stopped command exits with [`helpers::EXIT_TIMEOUT`] and says so in its stderr.
+///
+/// # Errors
+///
+/// - Returns [`expect`](Result<Error>) if the child can't be waited on.
+///
+fn wait_with_timeout(child: Child, timeout_secs: u64) -> Output {
+    if timeout_secs == 0 {
+        return child
+            .wait_with_output()
+            .expect("failed to execute remote command");
+    }
+
+    let pid: u32 = child.id();
+    let (done, finished) = mpsc::channel::<()>();
+    let (mut output, killed) = thread::scope(|scope| {
+        let watchdog = scope.spawn(move || {
+            if finished.recv_timeout(Duration::from_secs(timeout_secs) + TIMEOUT_GRACE)
+                != Err(RecvTimeoutError::Timeout)
+            {
+                return false;
+            }
+
+            // SAFETY: kill has no memory safety requirements, at worst the process has exited.
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGTERM);
+            }
+            true
+        });
+
+        let output: Output = child
+            .wait_with_output()
+            .expect("failed to execute remote command");
+        done.send(()).ok();
+
+        (output, watchdog.join().unwrap_or(false))
+    });
+
+    mark_timed_out(&mut output, killed, timeout_secs);
+    output
+}
+
+/// Gives the output of a command that timed out the [`helpers::EXIT_TIMEOUT`] exit code, keeping
+/// what it wrote to stderr before it was stopped. `killed` is whether the local ssh was stopped.
+///
+/// The `timed out` line is added to stderr unless the EC2's timer already printed it.
+///
+fn mark_timed_out(output: &mut Output, killed: bool, timeout_secs: u64) {
+    let message: String = timed_out(timeout_secs);
+    let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+    let stopped: bool = stderr.lines().any(|line: &str| line == message);
+    if !(killed || (stopped && !output.status.success())) {
+        return;
+    }
+
+    output.status = ExitStatus::from_raw(helpers::EXIT_TIMEOUT << 8);
+    if !stopped {
+        if !output.stderr.is_empty() && !output.stderr.ends_with(b"\n") {
+            output.stderr.push(b'\n');
+        }
+        let line: String = format!("{message}\n");
+        output.stderr.extend_from_slice(line.as_bytes());
+    }
+}
+
+/// Sets how long each cmd may run for the rest of the session, or prints it when `secs` is
+/// empty.
+///
+/// # Examples
+/// ```plaintext
+/// >>>  timeout 600
+/// Timeout set to 600s
+/// ```
+fn set_timeout(config: &mut Config, secs: &str) {
+    if secs.is_empty() {
+        return match config.command_timeout_secs {
+            0 => println!("No timeout\n"),
+            secs => println!("Timeout is {secs}s\n"),
+        };
+    }
+
+    match config::parse_timeout("timeout", secs) {
+        Ok(0) => {
+            config.command_timeout_secs = 0;
+            println!("Timeout off\n");
+        }
+        Ok(secs) => {
+            config.command_timeout_secs = secs;
+            println!("Timeout set to {secs}s\n");
+        }
+        Err(e) => println!("{} {e}\n", "error:".red()),
+    }
+}
+
+/// Runs a bash command on the EC2, printing stdout and stderr line by line as they arrive.
+///
+/// Unlike [`run_cmd`] nothing is buffered, so long running commands like `tail -f` or a slow
+/// build show their progress. The spinner isn't shown since the output is the progress.
+///
+/// A piped `stdin` is held open until the command exits, see [`follow`].
+///
+/// # Errors
+///
+/// - Returns [`expect`](Result<Error>) if the command can't be spawned or waited on.
+///
+fn run_cmd_streaming(config: &Config, bash_cmd: &str, stdin: Stdio) -> ExitStatus {
+    if config.dry_run {
+        return dry_run(config, &connect::ssh(config, bash_cmd), None).status;
+    }
+
+    let ssh_cmd: String = connect::ssh(config, bash_cmd);
+    let start: Instant = helpers::trace_command(config, &ssh_cmd);
+    let mut child: Child = Command::new("bash")
+        .arg("-c")
+        .arg(&ssh_cmd)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to execute remote command");
+    helpers::trace_spawn(config, &child, start);
+    let _running: Running = Running::track(&child);
+
+    let _stdin: Option<std::process::ChildStdin> = child.stdin.take();
+    let stdout = child.stdout.take().expect("failed to open stdout");
+    let stderr = child.stderr.take().expect("failed to open stderr");
+
+    let out: thread::JoinHandle<()> = thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            println!("{line}");
+        }
+    });
+    let err: thread::JoinHandle<()> = thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            eprintln!("{line}");
+        }
+    });
+
+    out.join().ok();
+    err.join().ok();
+
+    let status: ExitStatus = child.wait().expect("failed to wait for remote command");
+    helpers::trace_exit(config, start, &status, None);
+    logging::record(config, bash_cmd, None, status.code());
+
+    status
+}
+
+/// Runs a command with a terminal allocated and the local terminal attached, for `-t top` and
+/// anything else that checks `isatty`. The output isn't captured, so it can't be paged or saved.
+///
+fn run_cmd_tty(config: &Config, bash_cmd: &str) -> ExitStatus {
+    let ssh_cmd: String = connect::ssh_tty(config, bash_cmd);
+    if config.dry_run {
+        return dry_run(config, &ssh_cmd, None).status;
+    }
+
+    let start: Instant = helpers::trace_command(config, &ssh_cmd);
+    let mut child: Child = Command::new("bash")
+        .arg("-c")
+        .arg(&ssh_cmd)
+        .spawn()
+        .expect("failed to execute remote command");
+    helpers::trace_spawn(config, &child, start);
+
+    let status: ExitStatus = child.wait().expect("failed to wait for remote command");
+    helpers::trace_exit(config, start, &status, None);
+    logging::record(config, bash_cmd, None, status.code());
+
+    status
+}
+
+/// Commands that need a terminal to work properly, so a failed run suggests `-t`.
+///
+const TTY_COMMANDS: [&str; 12] = [
+    "top", "htop", "vim", "vi", "nano", "less", "more", "man", "tmux", "screen", "watch", "iotop",
+];
+
+/// Returns true if a command's failure looks like it was caused by having no terminal, either
+/// because it is one of [`TTY_COMMANDS`] or because its stderr says so.
+///
+/// # Examples
+/// ```rust
+/// assert!(needs_tty("top", false, b"top: failed tty get"));
+/// ```
+fn needs_tty(command: &str, success: bool, stderr: &[u8]) -> bool {
+    let stderr: String = String::from_utf8_lossy(stderr).to_lowercase();
+    let says_so: bool = [
+        "not a terminal",
+        "not a tty",
+        "tty get",
+        "inappropriate ioctl for device",
+        "term environment variable not set",
+    ]
+    .iter()
+    .any(|marker: &&str| stderr.contains(marker));
+
+    let program: &str = command
+        .split_whitespace()
+        .find(|word: &&str| *word != "sudo")
+        .unwrap_or("");
+
+    says_so || (!success && TTY_COMMANDS.contains(&program))
+}
+
+/// How long a sudo command runs before the spinner warns that it may be waiting for input.
+///
+const SUDO_HANG_AFTER: Duration = Duration::from_secs(30);
+
+/// Returns Output of sudo bash command from EC2.
+///
+/// The command is run with `sudo -S` and the password is written to the ssh stdin, so it never
+/// appears in the command string or the remote process list.
+///
+/// Since stdin only holds the password, a command that asks a question, like `yum` without
+/// `-y`, can't be answered. If it is still running after [`SUDO_HANG_AFTER`] the spinner says
+/// it may be waiting for input.
+///
+/// # Errors
+///
+/// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+/// - Returns [`expect`](Result<Error>) if the password can't be written to stdin.
+///
+fn run_cmd_sudo(config: &Config, sudo_cmd: &str) -> Output {
+    let (bash_cmd, input) = sudo_request(config, sudo_cmd);
+
+    if config.dry_run {
+        return dry_run(config, &connect::ssh(config, &bash_cmd), Some(&input));
+    }
+
+    let hint: String = format!(
+        "still running after {}s, it may be waiting for input, add -y or press Ctrl-C",
+        SUDO_HANG_AFTER.as_secs()
+    );
+    let output: Output = helpers::with_hang_hint(SUDO_HANG_AFTER, &hint, || {
+        ssh_output(config, &bash_cmd, Some(&input))
+    });
+
+    redacted(config, &bash_cmd, &input, output)
+}
+
+/// Returns Output of bash command from EC2, with `input` written to its stdin.
+///
+/// The input usually starts with the password, so it is redacted from stderr in case the
+/// remote command echoes its input back in an error.
+///
+/// # Errors
+///
+/// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs.
+/// - Returns [`expect`](Result<Error>) if the input can't be written to stdin.
+///
+fn run_cmd_input(config: &Config, bash_cmd: &str, input: &str) -> Output {
+    if config.dry_run {
+        return dry_run(config, &connect::ssh(config, bash_cmd), Some(input));
+    }
+
+    let output: Output = helpers::with_spinner(|| ssh_output(config, bash_cmd, Some(input)));
+
+    redacted(config, bash_cmd, input, output)
+}
+
+/// Returns the output of a command run with `input` on its stdin, with the password redacted
+/// from stderr, and records it in the audit log.
+///
+fn redacted(config: &Config, bash_cmd: &str, input: &str, mut output: Output) -> Output {
+    let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+    output.stderr = helpers::redact_secrets(&stderr, config).into_bytes();
+
+    logging::record(config, bash_cmd, Some(input), output.status.code());
+
+    output
+}
+
+/// The process id of the command currently running on the EC2, which Ctrl-C is forwarded to.
+///
+static RUNNING: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Set when Ctrl-C is pressed, for loops like `watch` that run until interrupted.
+///
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Marks a child process as the running command until it is dropped.
+///
+struct Running;
+
+impl Running {
+    /// Tracks the child so that Ctrl-C interrupts it rather than WCLI.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let _running: Running = Running::track(&child);
+    /// let output: Output = child.wait_with_output()?;
+    /// ```
+    fn track(child: &Child) -> Running {
+        *RUNNING.lock().unwrap_or_else(|e| e.into_inner()) = Some(child.id());
+        Running
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        *RUNNING.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Installs a Ctrl-C handler that sends `SIGINT` to the running command and returns to the
+/// prompt, instead of killing WCLI.
+///
+/// At a prompt the line editor handles Ctrl-C itself by discarding the line.
+///
+pub fn handle_interrupt() {
+    let handler = ctrlc::set_handler(|| {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+
+        if let Some(pid) = *RUNNING.lock().unwrap_or_else(|e| e.into_inner()) {
+            // SAFETY: kill has no memory safety requirements, at worst the process has exited.
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGINT);
+            }
+        }
+    });
+
+    if let Err(e) = handler {
+        eprintln!("{} unable to handle Ctrl-C: {e}", "warning:".yellow());
+    }
+}
+
+/// Returns true if Ctrl-C was pressed since the last [`clear_interrupt`].
+///
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Forgets any earlier Ctrl-C, before starting a loop that runs until interrupted.
+///
+fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::Relaxed);
+}
+
+/// Prints the command that would have been run, and anything written to its stdin, with the
+/// password redacted. Used by `--dry-run` in place of running the command.
+///
+/// Returns an empty successful [`Output`] so callers carry on as if the command had worked.
+///
+/// # Examples
+/// ```plaintext
+/// dry-run: ssh -i /home/user/key.pem ec2-user@ec2-xxxxxxxx.compute.amazonaws.com 'ls -l'
+/// ```
+fn dry_run(config: &Config, shell_cmd: &str, input: Option<&str>) -> Output {
+    println!(
+        "{} {}",
+        "dry-run:".cyan(),
+        helpers::redact_secrets(shell_cmd, config)
+    );
+
+    if let Some(input) = input {
+        println!(
+            "{} {}",
+            "stdin:".cyan(),
+            helpers::redact_secrets(input, config).trim_end()
+        );
+    }
+
+    Output {
+        status: ExitStatus::from_raw(0),
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    }
+}
+
+/// Returns the command [`run_cmd_sudo`] runs and the stdin it writes, the password on a line
+/// of its own, so the password is never quoted into the command.
+///
+fn sudo_request(config: &Config, sudo_cmd: &str) -> (String, String) {
+    (sudo_stdin(sudo_cmd), format!("{}\n", config.pass))
+}
+
+/// Returns the sudo command with the `-S` flag so sudo reads the password from stdin.
+///
+/// # Examples
+/// ```rust
+/// assert_eq!(sudo_stdin("sudo yum update"), "sudo -S yum update");
+/// ```
+fn sudo_stdin(sudo_cmd: &str) -> String {
+    let sudo_cmd: &str = sudo_cmd.trim();
+
+    match sudo_cmd.strip_prefix("sudo ") {
+        Some(rest) if !rest.trim_start().starts_with("-S") => {
+            format!("sudo -S {}", rest.trim_start())
+        }
+        _ => sudo_cmd.to_string(),
+    }
+}
+
+/// Provides an abstraction for installing packages with the EC2's package manager, see
+/// [`package::manager`].
+///
+/// # Errors
+///
+/// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+/// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+///
+fn install(runner: &dyn CommandRunner, config: &Config) {
+    let manager: PackageManager = match package::manager(runner, config) {
+        Ok(manager) => manager,
+        Err(e) => return println!("{} {e}\n", "error:".red()),
+    };
+
+    let Some(package) = or_exit(msg_input("Package")) else {
+        return;
+    };
+
+    if confirm_disk_space(runner, config) {
+        install_package(runner, config, manager, &package);
+    }
+}
+
+/// Returns true if there's room to install, otherwise warns that a filesystem the install
+/// writes to is [`package::DISK_FULL_PERCENT`] full and asks whether to go ahead.
+///
+/// If the usage can't be read the install goes ahead, since `df` failing says nothing about
+/// the package.
+///
+fn confirm_disk_space(runner: &dyn CommandRunner, config: &Config) -> bool {
+    let Some((mount, percent)) = package::fullest_disk(runner, config) else {
+        return true;
+    };
+
+    if percent < package::DISK_FULL_PERCENT {
+        return true;
+    }
+
+    println!(
+        "{} {mount} is {percent}% full, the install may run out of space",
+        "warning:".yellow()
+    );
+    let answer: String = or_exit(msg_input("Install anyway? [y/N]")).unwrap_or_default();
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        println!();
+        return false;
+    }
+
+    true
+}
+
+/// Installs `package` with `manager` and prints the output.
+///
+fn install_package(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    manager: PackageManager,
+    package: &str,
+) {
+    helpers::print_cmd(&runner.run_sudo(config, &manager.install(package)));
+}
+
+/// Provides an abstraction for removing packages with the EC2's package manager, see
+/// [`package::manager`].
+///
+/// # Errors
+///
+/// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+/// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+///
+fn remove(runner: &dyn CommandRunner, config: &Config) {
+    let manager: PackageManager = match package::manager(runner, config) {
+        Ok(manager) => manager,
+        Err(e) => return println!("{} {e}\n", "error:".red()),
+    };
+
+    let Some(package) = or_exit(msg_input("Package")) else {
+        return;
+    };
+
+    let bash_cmd: String = manager.remove(&package);
+    if confirm_dangerous(config, &bash_cmd) {
+        helpers::print_cmd(&runner.run_sudo(config, &bash_cmd));
+    }
+}
+
+/// Refreshes the package metadata on the EC2 with its package manager, see
+/// [`package::manager`].
+///
+fn update(runner: &dyn CommandRunner, config: &Config) {
+    let manager: PackageManager = match package::manager(runner, config) {
+        Ok(manager) => manager,
+        Err(e) => return println!("{} {e}\n", "error:".red()),
+    };
+
+    helpers::print_cmd(&runner.run_sudo(config, &manager.update()));
+}
+
+/// Applies every available update on the EC2 after asking for confirmation, then prints how
+/// many packages were upgraded.
+///
+fn upgrade(runner: &dyn CommandRunner, config: &Config) {
+    let manager: PackageManager = match package::manager(runner, config) {
+        Ok(manager) => manager,
+        Err(e) => return println!("{} {e}\n", "error:".red()),
+    };
+
+    let question: &str = "Upgrade every package on the EC2? [y/N]";
+    let answer: String = or_exit(msg_input(question)).unwrap_or_default();
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        println!();
+        return;
+    }
+
+    let output: Output = runner.run_sudo(config, &manager.upgrade());
+    helpers::print_cmd(&output);
+
+    if output.status.success() && !config.dry_run {
+        let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+        match package::upgraded(&stdout) {
+            Some(0) => println!("{}\n", "Everything is up to date".green()),
+            Some(count) => println!("{} {count} packages\n", "Upgraded".green()),
+            None => {}
+        }
+    }
+}
+
+/// Displays a help message.
+///
+fn cmd_help() {
+    println!("\nCOMMANDS");
+    println!("'any'         -> run a Linux cmd, ensure syntax is correct");
+    println!("'\\'           -> end a line with '\\' to continue it on the next line");
+    println!("'<<EOF'       -> start a heredoc, finish it with a lone 'EOF'");
+    println!("'cd'          -> change the working directory for the following cmds");
+    println!("'pwd'         -> show the working directory");
+    println!("'local'       -> run a cmd on this machine instead, e.g. 'local git status'");
+    println!("'env set'     -> send KEY=VALUE with the following cmds");
+    println!("'env unset'   -> stop sending KEY");
+    println!("'env list'    -> show the variables being sent");
+    println!("'alias'       -> save a shortcut, e.g. 'alias ll = \"ls -l\"', or list them");
+    println!("'unalias'     -> delete a shortcut");
+    println!("'install'     -> install a package with apt-get, dnf or yum");
+    println!("'remove'      -> uninstall a package");
+    println!("'update'      -> refresh the package lists");
+    println!("'upgrade'     -> apply every available update, after asking");
+    println!("'push'        -> upload a local file to the EC2");
+    println!("'pull'        -> download a file from the EC2");
+    println!("'view'        -> print a file with syntax highlighting, 'view --all' for all of it");
+    println!("'ps'          -> list processes as a table, 'ps nginx' for only matching ones");
+    println!("'kill'        -> stop a process by PID or name, e.g. 'kill node', after asking");
+    println!("'stream'      -> run a cmd and print its output as it arrives, e.g. 'stream make'");
+    println!("'follow'      -> print lines added to a file until Ctrl-C, like 'tail -f'");
+    println!("'-t'          -> run a cmd in a terminal, for 'top', 'htop', 'vim' or 'less'");
+    println!("'retry'       -> rerun a failing cmd up to N times, e.g. 'retry 3 make test'");
+    println!("'| save'      -> end a cmd with '| save out.txt' to keep its output, '|>>' appends");
+    println!("'timings'     -> show how long each cmd took, 'timings on' or 'timings off'");
+    println!("'timestamps'  -> head each output with the time, 'timestamps on' or 'off'");
+    println!("'timeout'     -> stop cmds after N seconds for this session, 'timeout 0' for never");
+    println!("'watch'       -> rerun a cmd every few seconds, e.g. 'watch 2 docker ps'");
+    println!("'history'     -> list past cmds, '!N' reruns one and '!!' the last");
+    println!("'clear'       -> clears the terminal");
+    println!("'exit'        -> exit cmd");
+}
+
+pub mod connect {
+    //! This module provides an API for ssh login to an EC2.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::{
+        env, fmt, fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        process::{self, Command, Stdio},
+        sync::atomic::{AtomicU64, Ordering},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use colored::Colorize;
+
+    use crate::{
+        cmd::{helpers, run_cmd},
+        config::{self, Config},
+    };
+
+    /// Counts the ControlMaster connections opened, so a keepalive thread stops once the one it
+    /// was started for is closed.
+    ///
+    static MASTER: AtomicU64 = AtomicU64::new(0);
+
+    /// Reasons a connection to the EC2 can fail, each holding the ssh stderr, or the reason the
+    /// PEM file was rejected.
+    ///
+    #[derive(Debug)]
+    pub enum ConnectError {
+        SshFailed(String),
+        DnsFailed(String),
+        Timeout(String),
+        AuthFailed(String),
+        HostKey(String),
+        BadPem(String),
+    }
+
+    impl ConnectError {
+        /// Classifies a failed ssh attempt from its stderr.
+        ///
+        /// A name that doesn't resolve is told apart from a timeout, since `Temporary failure
+        /// in name resolution` would otherwise read as a network problem.
+        ///
+        fn from_stderr(stderr: &str) -> ConnectError {
+            let stderr: String = stderr.trim().to_string();
+            let lower: String = stderr.to_lowercase();
+
+            if lower.contains("could not resolve hostname") {
+                ConnectError::DnsFailed(stderr)
+            } else if lower.contains("permission denied") {
+                ConnectError::AuthFailed(stderr)
+            } else if lower.contains("host key verification failed") {
+                ConnectError::HostKey(stderr)
+            } else if lower.contains("timed out") {
+                ConnectError::Timeout(stderr)
+            } else {
+                ConnectError::SshFailed(stderr)
+            }
+        }
+
+        /// Returns a remediation hint for the error.
+        ///
+        pub fn hint(&self) -> &'static str {
+            match self {
+                ConnectError::SshFailed(_) => "check that ssh is installed and EC2 is correct",
+                ConnectError::DnsFailed(_) => {
+                    "the instance may have restarted and gotten a new DNS name, update EC2 in your \
+                     config"
+                }
+                ConnectError::Timeout(_) => {
+                    "check that the instance is running and its security group allows ssh"
+                }
+                ConnectError::AuthFailed(_) => "check that PEM points to the key for this instance",
+                ConnectError::HostKey(_) => {
+                    "check the host key in known_hosts, or STRICT_HOST_KEY_CHECKING"
+                }
+                ConnectError::BadPem(_) => "check the PEM path in .env or ~/.wcli/config.toml",
+            }
+        }
+    }
+
+    impl fmt::Display for ConnectError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let (msg, stderr) = match self {
+                ConnectError::SshFailed(stderr) => ("unable to connect to EC2", stderr),
+                ConnectError::DnsFailed(stderr) => ("EC2 address could not be resolved", stderr),
+                ConnectError::Timeout(stderr) => ("connection to EC2 timed out", stderr),
+                ConnectError::AuthFailed(stderr) => ("authentication to EC2 failed", stderr),
+                ConnectError::HostKey(stderr) => ("EC2 host key was rejected", stderr),
+                ConnectError::BadPem(reason) => ("invalid PEM file", reason),
+            };
+
+            if stderr.is_empty() {
+                write!(f, "{msg}")
+            } else {
+                write!(f, "{msg}\n{stderr}")
+            }
+        }
+    }
+
+    /// Returns an ssh connection string.
+    ///
+    /// When a ControlMaster socket is configured the connection is shared with every other
+    /// command, so only the first one pays for the ssh handshake.
+    ///
+    pub fn ssh(config: &Config, bash_cmd: &str) -> String {
+        format!("ssh {} '{bash_cmd}'", destination(config))
+    }
+
+    /// Returns an ssh connection string that allocates a terminal for the command, so
+    /// full-screen tools like `top` can draw and read keys.
+    ///
+    pub fn ssh_tty(config: &Config, bash_cmd: &str) -> String {
+        format!("ssh -t {} '{bash_cmd}'", destination(config))
+    }
+
+    /// Returns an ssh connection string for an interactive login shell, with a terminal
+    /// allocated so tools like `vim` and `top` work.
+    ///
+    pub fn interactive(config: &Config) -> String {
+        format!("ssh -t {}", destination(config))
+    }
+
+    /// Returns the key, port, options and `user@host` shared by every ssh connection string.
+    ///
+    fn destination(config: &Config) -> String {
+        format!(
+            "-i {} -p {} {}{}{}{}",
+            config.pem,
+            config.ssh_port,
+            host_key_options(config),
+            keepalive_options(config),
+            control_options(config),
+            target(&config.ssh_user, &config.ec2)
+        )
+    }
+
+    /// Returns the `user@host` to connect to.
+    ///
+    /// `EC2` values that already include a user, like `ec2-user@ec2-xxxxxxxx...`, are used as
+    /// is so older configs keep working.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let host: &str = "ec2-xxxxxxxx.amazonaws.com";
+    /// assert_eq!(target("ubuntu", host), "ubuntu@ec2-xxxxxxxx.amazonaws.com");
+    /// ```
+    pub fn target(ssh_user: &str, ec2: &str) -> String {
+        if ec2.contains('@') {
+            ec2.to_string()
+        } else {
+            format!("{ssh_user}@{ec2}")
+        }
+    }
+
+    /// Returns a ControlMaster socket path unique to this process.
+    ///
+    pub fn control_path() -> PathBuf {
+        env::temp_dir().join(format!("wcli-{}.sock", process::id()))
+    }
+
+    /// Returns the ssh options that decide whether the EC2's host key is trusted.
+    ///
+    /// Setting `StrictHostKeyChecking` explicitly stops ssh asking the yes/no question on the
+    /// first connection, which would hang since its output is captured.
+    ///
+    pub fn host_key_options(config: &Config) -> String {
+        let mut options: String = format!("-o StrictHostKeyChecking={} ", config.host_key_checking);
+
+        if let Some(path) = &config.known_hosts {
+            options.push_str(&format!("-o UserKnownHostsFile={path} "));
+        }
+
+        options
+    }
+
+    /// Returns the ssh options that send a keepalive when the connection has been quiet for
+    /// `keepalive_interval_secs`, so an idle connection isn't dropped by a firewall or NAT, and
+    /// give up after `keepalive_count_max` go unanswered. Returns nothing if disabled.
+    ///
+    pub fn keepalive_options(config: &Config) -> String {
+        match config.keepalive_interval_secs {
+            0 => String::new(),
+            secs => format!(
+                "-o ServerAliveInterval={secs} -o ServerAliveCountMax={} ",
+                config.keepalive_count_max
+            ),
+        }
+    }
+
+    /// Returns the ssh options to share a ControlMaster connection, or nothing if disabled.
+    ///
+    pub fn control_options(config: &Config) -> String {
+        match &config.control_path {
+            Some(path) => format!(
+                "-o ControlMaster=auto -o ControlPath={} -o ControlPersist=60 ",
+                path.display()
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Closes the ControlMaster connection and removes its socket.
+    ///
+    pub fn close_master(config: &Config) {
+        let Some(path) = &config.control_path else {
+            return;
+        };
+        MASTER.fetch_add(1, Ordering::SeqCst);
+
+        Command::new("ssh")
+            .arg("-O")
+            .arg("exit")
+            .arg("-o")
+            .arg(format!("ControlPath={}", path.display()))
+            .arg(target(&config.ssh_user, &config.ec2))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .ok();
+
+        fs::remove_file(path).ok();
+    }
+
+    /// Runs a no-op over the ControlMaster connection every `keepalive_interval_secs` from a
+    /// background thread, until [`close_master`] is called.
+    ///
+    /// The master exits once it has had no commands for its `ControlPersist` time, so without
+    /// this the first command after a long pause pays for a new handshake, or fails if the
+    /// connection dropped. Nothing is started without a master, under `--dry-run` or with
+    /// keepalives turned off.
+    ///
+    fn keep_alive(config: &Config) {
+        let Some(path) = config.control_path.clone() else {
+            return;
+        };
+        if config.dry_run || config.keepalive_interval_secs == 0 {
+            return;
+        }
+
+        let master: u64 = MASTER.fetch_add(1, Ordering::SeqCst) + 1;
+        let interval: Duration = Duration::from_secs(config.keepalive_interval_secs);
+        let target: String = target(&config.ssh_user, &config.ec2);
+
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                if MASTER.load(Ordering::SeqCst) != master {
+                    return;
+                }
+                if !path.exists() {
+                    continue;
+                }
+
+                Command::new("ssh")
+                    .arg("-o")
+                    .arg(format!("ControlPath={}", path.display()))
+                    .args(["-o", "ControlMaster=no", "-o", "BatchMode=yes"])
+                    .arg(&target)
+                    .arg("true")
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .ok();
+            }
+        });
+    }
+
+    /// Adds a passphrase protected PEM to the running ssh-agent, asking for the passphrase once,
+    /// so every following ssh command authenticates through the agent instead of prompting.
+    ///
+    /// Nothing is done for an unencrypted key, which ssh reads with `-i` as before, or for a key
+    /// the agent already holds. Without an agent a warning explains how to start one.
+    ///
+    pub fn add_to_agent(config: &Config) {
+        if config.dry_run || !is_encrypted(&config.pem) {
+            return;
+        }
+
+        if env::var_os("SSH_AUTH_SOCK").is_none() {
+            eprintln!(
+                "{} PEM is passphrase protected and no ssh-agent is running",
+                "warning:".yellow()
+            );
+            eprintln!(
+                "{} start one with 'eval $(ssh-agent)' so the passphrase is only asked once\n",
+                "hint:".yellow()
+            );
+            return;
+        }
+
+        if agent_has_key(&config.pem) {
+            return;
+        }
+
+        let added: bool = Command::new("ssh-add")
+            .arg(&config.pem)
+            .status()
+            .is_ok_and(|status: process::ExitStatus| status.success());
+
+        if !added {
+            eprintln!("{} unable to add PEM to ssh-agent\n", "warning:".yellow());
+        }
+    }
+
+    /// Returns whether the key file needs a passphrase, which `ssh-keygen -y` complains about
+    /// when reading it with an empty one.
+    ///
+    fn is_encrypted(pem: &str) -> bool {
+        if !Path::new(pem).is_file() {
+            return false;
+        }
+
+        Command::new("ssh-keygen")
+            .args(["-y", "-P", "", "-f", pem])
+            .stdin(Stdio::null())
+            .output()
+            .is_ok_and(|output: process::Output| {
+                !output.status.success()
+                    && String::from_utf8_lossy(&output.stderr).contains("passphrase")
+            })
+    }
+
+    /// Returns whether the ssh-agent holds the key, by comparing its fingerprint with the ones
+    /// `ssh-add -l` lists.
+    ///
+    fn agent_has_key(pem: &str) -> bool {
+        let Ok(key) = Command::new("ssh-keygen").args(["-l", "-f", pem]).output() else {
+            return false;
+        };
+        let Ok(agent) = Command::new("ssh-add").arg("-l").output() else {
+            return false;
+        };
+
+        let key: String = String::from_utf8_lossy(&key.stdout).to_string();
+        let agent: String = String::from_utf8_lossy(&agent.stdout).to_string();
+
+        key.split_whitespace()
+            .nth(1)
+            .is_some_and(|fingerprint: &str| agent.contains(fingerprint))
+    }
+
+    /// Checks that the PEM file exists, is a regular file and is only readable by its owner,
+    /// which ssh insists on before it will use the key.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) with the fix if the file is missing, isn't a file or has
+    ///   permissions broader than 0600.
+    ///
+    /// # Examples
+    /// ```rust
+    /// validate_pem("/home/user/key.pem")?;
+    /// ```
+    pub fn validate_pem(path: &str) -> Result<(), String> {
+        let metadata: fs::Metadata = match fs::metadata(Path::new(path)) {
+            Ok(metadata) => metadata,
+            Err(_) => return Err(format!("PEM file {path} doesn't exist")),
+        };
+
+        if !metadata.is_file() {
+            return Err(format!("PEM path {path} is not a file"));
+        }
+
+        if metadata.permissions().mode() & 0o077 != 0 {
+            return Err(format!(
+                "PEM file is group/world readable; run chmod 600 {path}"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the config for `--config-check` without connecting, so it works offline, and
+    /// returns the exit code, [`config::EXIT_CONFIG`] if any check failed.
+    ///
+    /// Each check is printed as a ✓ or ✗ line: the PEM file and its permissions, the `EC2`
+    /// address, the known hosts file if one is set, and that `ssh` and `scp` are installed.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// ✓ 'staging' loaded from /home/user/.wcli/config.toml
+    /// ✓ PEM file /home/user/staging.pem
+    /// ✗ EC2 'ec2-user@' has no host name
+    /// ✓ ssh found at /usr/bin/ssh
+    /// ✓ scp found at /usr/bin/scp
+    /// ```
+    pub fn check_config(config: &Config) -> i32 {
+        let source: String = match &config.source {
+            Some(path) => path.display().to_string(),
+            None => "environment variables".to_string(),
+        };
+
+        let mut checks: Vec<Result<String, String>> = vec![
+            Ok(format!("'{}' loaded from {source}", config.host)),
+            validate_pem(&config.pem).map(|_| format!("PEM file {}", config.pem)),
+            check_ec2(&config.ec2)
+                .map(|_| format!("EC2 {}", target(&config.ssh_user, &config.ec2))),
+        ];
+
+        if let Some(path) = &config.known_hosts {
+            checks.push(if Path::new(path).is_file() {
+                Ok(format!("Known hosts file {path}"))
+            } else {
+                Err(format!("Known hosts file {path} doesn't exist"))
+            });
+        }
+
+        for program in ["ssh", "scp"] {
+            checks.push(match find_program(program) {
+                Some(path) => Ok(format!("{program} found at {}", path.display())),
+                None => Err(format!("{program} isn't installed or isn't on PATH")),
+            });
+        }
+
+        let mut failed: bool = false;
+        for check in &checks {
+            match check {
+                Ok(passed) => println!("{} {passed}", "✓".green()),
+                Err(e) => {
+                    failed = true;
+                    println!("{} {e}", "✗".red());
+                }
+            }
+        }
+
+        if failed { config::EXIT_CONFIG } else { 0 }
+    }
+
+    /// Checks that `EC2` looks like a host name or `user@host`.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if it is empty, has no host name, or has characters a host name
+    ///   can't contain, like spaces or quotes.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert!(check_ec2("ec2-user@ec2-xxxxxxxx.compute.amazonaws.com").is_ok());
+    /// ```
+    fn check_ec2(ec2: &str) -> Result<(), String> {
+        let (user, host) = ec2.rsplit_once('@').unwrap_or(("", ec2));
+
+        if ec2.is_empty() {
+            return Err("EC2 is empty".to_string());
+        }
+
+        if ec2.contains('@') && user.is_empty() {
+            return Err(format!("EC2 '{ec2}' has no user before the @"));
+        }
+
+        if host.is_empty() {
+            return Err(format!("EC2 '{ec2}' has no host name"));
+        }
+
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':' | '_');
+        if !host.chars().all(valid) || user.contains(char::is_whitespace) {
+            return Err(format!(
+                "EC2 '{ec2}' has characters a host name can't contain"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns where a program is installed, searching `PATH` like the shell does.
+    ///
+    fn find_program(program: &str) -> Option<PathBuf> {
+        let path = env::var_os("PATH")?;
+
+        env::split_paths(&path)
+            .map(|dir: PathBuf| dir.join(program))
+            .find(|candidate: &PathBuf| {
+                fs::metadata(candidate).is_ok_and(|metadata| {
+                    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+                })
+            })
+    }
+
+    /// Tests connection to EC2 using ssh.
+    ///
+    /// Failed attempts are retried up to `connect_attempts` times, waiting 1s, 2s, 4s and so on
+    /// in between, so an instance that is still booting has time to come up. Authentication
+    /// and host key failures aren't retried since they won't fix themselves.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+    /// - Returns [`except`](std::process::Output) if current time is unreachable.
+    /// - Returns [`ConnectError::BadPem`] if the PEM file fails [`validate_pem`].
+    /// - Returns [`ConnectError`] from the last attempt if every attempt is unsuccessful.
+    ///
+    pub fn test_connection(config: &Config) -> Result<(), ConnectError> {
+        validate_pem(&config.pem).map_err(ConnectError::BadPem)?;
+
+        let mut attempt: u32 = 1;
+        let first: Duration = loop {
+            match try_connection(config) {
+                Ok(elapsed) => break elapsed,
+                Err(e @ (ConnectError::AuthFailed(_) | ConnectError::HostKey(_))) => return Err(e),
+                Err(e) if attempt >= config.connect_attempts => return Err(e),
+                Err(_) => {
+                    let delay: Duration = Duration::from_secs(1 << (attempt - 1).min(5));
+                    attempt += 1;
+
+                    println!(
+                        "{} ({attempt}/{})...",
+                        "retrying".yellow(),
+                        config.connect_attempts
+                    );
+                    thread::sleep(delay);
+                }
+            }
+        };
+
+        println!(
+            "{} to {} on {} in {}\n",
+            "Connected".green(),
+            config.host,
+            chrono::offset::Local::now().format("%a %b %e at %T"),
+            helpers::format_duration(first)
+        );
+
+        if config.control_path.is_some() {
+            let start: Instant = Instant::now();
+            run_cmd(config, "echo test");
+
+            println!(
+                "Reusing ssh session, round trip {} down from {}\n",
+                helpers::format_duration(start.elapsed()),
+                helpers::format_duration(first)
+            );
+            keep_alive(config);
+        }
+
+        Ok(())
+    }
+
+    /// How long a ping is shown in the main prompt before it is refreshed.
+    ///
+    const PING_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Result of the last round trip to the EC2, shown in the main prompt.
+    ///
+    /// `latency` is [`None`] if the round trip failed.
+    ///
+    pub struct Ping {
+        latency: Option<Duration>,
+        at: Instant,
+    }
+
+    impl Ping {
+        /// Runs a lightweight `echo` over ssh and times the round trip.
+        ///
+        /// # Examples
+        /// ```rust
+        /// let ping: Ping = Ping::now(&config);
+        /// ```
+        pub fn now(config: &Config) -> Ping {
+            Ping {
+                latency: try_connection(config).ok(),
+                at: Instant::now(),
+            }
+        }
+
+        /// Returns true once the ping is old enough to be refreshed.
+        ///
+        pub fn is_stale(&self) -> bool {
+            self.at.elapsed() >= PING_INTERVAL
+        }
+
+        /// Returns the status for the prompt, a green dot and the latency when connected or a
+        /// red dot when not.
+        ///
+        /// # Examples
+        /// ```plaintext
+        /// [●12ms]
+        /// ```
+        pub fn indicator(&self) -> String {
+            match self.latency {
+                Some(latency) => format!("[{}{}ms]", "●".green(), latency.as_millis()),
+                None => format!("[{}]", "●".red()),
+            }
+        }
+    }
+
+    impl fmt::Display for Ping {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.latency {
+                Some(latency) => write!(f, "{} in {}ms", "Connected".green(), latency.as_millis()),
+                None => write!(f, "{}, run 'reconnect' for details", "Disconnected".red()),
+            }
+        }
+    }
+
+    /// Closes any shared ssh session and tests the connection again, which opens a new one.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`ConnectError`] if the connection test fails, see [`test_connection`].
+    ///
+    pub fn reconnect(config: &Config) -> Result<(), ConnectError> {
+        close_master(config);
+
+        test_connection(config)
+    }
+
+    /// Makes a single connection attempt and returns how long the round trip took.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`ConnectError`] classified from the ssh stderr if the attempt fails.
+    ///
+    fn try_connection(config: &Config) -> Result<Duration, ConnectError> {
+        let start: Instant = Instant::now();
+        let output: std::process::Output = run_cmd(config, "echo test");
+
+        if output.status.success() {
+            Ok(start.elapsed())
+        } else {
+            let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+
+            Err(ConnectError::from_stderr(&helpers::redact(
+                &stderr,
+                &config.pass,
+            )))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{ConnectError, check_ec2, keepalive_options, target};
+        use crate::config::Config;
+
+        #[test]
+        fn check_ec2_rejects_malformed_addresses() {
+            assert!(check_ec2("ec2-user@ec2-xxxxxxxx.compute.amazonaws.com").is_ok());
+            assert!(check_ec2("10.0.0.12").is_ok());
+            assert!(check_ec2("").is_err());
+            assert!(check_ec2("ec2-user@").is_err());
+            assert!(check_ec2("@10.0.0.12").is_err());
+            assert!(check_ec2("ec2-user@my host").is_err());
+        }
+
+        #[test]
+        fn target_adds_the_ssh_user() {
+            assert_eq!(
+                target("ubuntu", "ec2-xxxxxxxx.compute.amazonaws.com"),
+                "ubuntu@ec2-xxxxxxxx.compute.amazonaws.com"
+            );
+        }
+
+        #[test]
+        fn target_keeps_a_user_in_ec2() {
+            assert_eq!(
+                target("ubuntu", "ec2-user@ec2-xxxxxxxx.compute.amazonaws.com"),
+                "ec2-user@ec2-xxxxxxxx.compute.amazonaws.com"
+            );
+        }
+
+        #[test]
+        fn connect_errors_are_classified_from_stderr() {
+            let dns: &str = "ssh: Could not resolve hostname ec2-3-8-1-2.compute.amazonaws.com: \
+                             Name or service not known";
+            assert!(matches!(
+                ConnectError::from_stderr(dns),
+                ConnectError::DnsFailed(_)
+            ));
+
+            let dns: &str = "ssh: Could not resolve hostname ec2.test: Temporary failure in name \
+                             resolution";
+            assert!(matches!(
+                ConnectError::from_stderr(dns),
+                ConnectError::DnsFailed(_)
+            ));
+
+            let auth: &str = "ec2-user@10.0.0.12: Permission denied (publickey).";
+            assert!(matches!(
+                ConnectError::from_stderr(auth),
+                ConnectError::AuthFailed(_)
+            ));
+
+            let timeout: &str = "ssh: connect to host 10.0.0.12 port 22: Connection timed out";
+            assert!(matches!(
+                ConnectError::from_stderr(timeout),
+                ConnectError::Timeout(_)
+            ));
+        }
+
+        #[test]
+        fn keepalives_can_be_turned_off() {
+            let mut config: Config = Config::test();
+            assert_eq!(
+                keepalive_options(&config),
+                "-o ServerAliveInterval=30 -o ServerAliveCountMax=3 "
+            );
+
+            config.keepalive_interval_secs = 0;
+            assert_eq!(keepalive_options(&config), "");
+        }
+    }
+}
+
+pub mod scp {
+    //! This module provides an API for copying files to and from an EC2 with scp.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::{
+        fs,
+        process::{Child, Command, Output, Stdio},
+        time::Instant,
+    };
+
+    use colored::Colorize;
+
+    use crate::{
+        cmd::{Running, connect, dry_run, helpers, msg_input, or_exit},
+        config::Config,
+        logging,
+    };
+
+    /// Prompts for a local and remote path then uploads the file.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  push
+    /// Local path: scripts/setup.sh
+    /// Remote path: setup.sh
+    /// Uploaded 1204 bytes to setup.sh
+    /// ```
+    pub fn push(config: &Config) {
+        let Some(local) = or_exit(msg_input("Local path")) else {
+            return;
+        };
+        let Some(remote) = or_exit(msg_input("Remote path")) else {
+            return;
+        };
+
+        let output: Output = upload(config, local.trim(), remote.trim());
+        report(&output, "Uploaded", local.trim(), remote.trim());
+    }
+
+    /// Prompts for a remote and local path then downloads the file.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  pull
+    /// Remote path: /var/log/app.log
+    /// Local path: app.log
+    /// Downloaded 52311 bytes to app.log
+    /// ```
+    pub fn pull(config: &Config) {
+        let Some(remote) = or_exit(msg_input("Remote path")) else {
+            return;
+        };
+        let Some(local) = or_exit(msg_input("Local path")) else {
+            return;
+        };
+
+        let output: Output = download(config, remote.trim(), local.trim());
+        report(&output, "Downloaded", local.trim(), local.trim());
+    }
+
+    /// Uploads a local file to the EC2.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if scp can't be executed.
+    /// - Returns [`stderr`](std::process::Output) if the transfer fails.
+    ///
+    pub fn upload(config: &Config, local: &str, remote: &str) -> Output {
+        let target: String = format!(
+            "{}:'{remote}'",
+            connect::target(&config.ssh_user, &config.ec2)
+        );
+
+        transfer(config, &format!("'{local}'"), &target)
+    }
+
+    /// Downloads a file from the EC2.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if scp can't be executed.
+    /// - Returns [`stderr`](std::process::Output) if the transfer fails.
+    ///
+    pub fn download(config: &Config, remote: &str, local: &str) -> Output {
+        let source: String = format!(
+            "{}:'{remote}'",
+            connect::target(&config.ssh_user, &config.ec2)
+        );
+
+        transfer(config, &source, &format!("'{local}'"))
+    }
+
+    /// Runs scp from source to target while showing the spinner.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if scp can't be executed.
+    ///
+    fn transfer(config: &Config, source: &str, target: &str) -> Output {
+        let scp_cmd: String = format!(
+            "scp -i {} -P {} {}{}{source} {target}",
+            config.pem,
+            config.ssh_port,
+            connect::host_key_options(config),
+            connect::control_options(config)
+        );
+
+        if config.dry_run {
+            return dry_run(config, &scp_cmd, None);
+        }
+
+        let output: Output = helpers::with_spinner(|| {
+            let start: Instant = helpers::trace_command(config, &scp_cmd);
+            let child: Child = Command::new("bash")
+                .arg("-c")
+                .arg(&scp_cmd)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .expect("failed to execute scp");
+            helpers::trace_spawn(config, &child, start);
+
+            let _running: Running = Running::track(&child);
+            let output: Output = child.wait_with_output().expect("failed to execute scp");
+            helpers::trace_exit(config, start, &output.status, Some(&output));
+
+            output
+        });
+
+        logging::record(
+            config,
+            &format!("scp {source} {target}"),
+            None,
+            output.status.code(),
+        );
+
+        output
+    }
+
+    /// Prints the number of bytes transferred, or the scp stderr if the transfer failed.
+    ///
+    /// The size is read from the local copy of the file.
+    ///
+    fn report(output: &Output, action: &str, local: &str, target: &str) {
+        if !output.status.success() {
+            helpers::print_cmd(output);
+            return;
+        }
+
+        match fs::metadata(local) {
+            Ok(metadata) => println!("{} {} bytes to {target}\n", action.green(), metadata.len()),
+            Err(_) => println!("{} to {target}\n", action.green()),
+        }
+    }
+}
+
+pub mod git {
+    //! This module provides an API for running git commands.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::process::{ExitStatus, Output};
+
+    use colored::Colorize;
+
+    use crate::{
+        cmd::{
+            CommandRunner, LineEditor, PromptVars, Ssh, confirm_dangerous,
+            helpers::{self, clear},
+            is_blank_or_comment, msg_input, or_exit, run_cmd, shell_prompt,
+        },
+        config::Config,
+        logging,
+        session::Session,
+    };
+
+    /// Provides an API for running git commands.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+    ///
+    /// # Examples
+    /// ```rust
+    /// run_git(&session);
+    /// ```
+    /// Output
+    /// ```plaintext
+    /// [user@wcli ~]$ git   
+    /// Repo path: directory/repository
+    /// (dev) >>>  status
+    /// On branch dev
+    /// ...
+    /// ```
+    pub fn run_git(session: &Session) {
+        logging::set_shell("git");
+        let config: &Config = &session.config;
+        let Some(mut directory) = repo_input(config) else {
+            return;
+        };
+        let mut editor: LineEditor = LineEditor::new("git");
+        println!("Run 'help' for commands\n");
+
+        let mut branch: Option<String> = current_branch(config, directory.trim());
+        let mut failures: u32 = 0;
+
+        loop {
+            let branch_name: &str = branch.as_deref().unwrap_or("not a repo");
+            let prompt_vars: PromptVars = PromptVars {
+                user: &session.user,
+                cwd: directory.trim(),
+                branch: branch_name,
+                shell: "git",
+                ..PromptVars::default()
+            };
+            let prompt: String =
+                shell_prompt(config, &format!("({branch_name}) >>> "), &prompt_vars);
+            let Some(user_cmd) = or_exit(editor.prompt_input(&prompt)) else {
+                break;
+            };
+            if is_blank_or_comment(&user_cmd) {
+                continue;
+            }
+
+            match user_cmd.trim() {
+                "exit" => break,
+                "change" => run_git(session),
+                "clone" => {
+                    if let Some(cloned) = clone(config) {
+                        directory = cloned;
+                        branch = current_branch(config, &directory);
+                    }
+                }
+                "history" => editor.print_history(),
+                "branches" => branches(config, directory.trim()),
+                "timestamps on" => helpers::set_timestamps(true),
+                "timestamps off" => helpers::set_timestamps(false),
+                "clear" => clear(),
+                "help" => git_help(),
+                _ => {
+                    if is_branch_checkout(&user_cmd)
+                        && !branch_exists(config, directory.trim(), &user_cmd)
+                    {
+                        continue;
+                    }
+
+                    if let Some(status) =
+                        git_cmd(&Ssh, config, directory.as_str().trim(), &user_cmd)
+                        && !status.success()
+                    {
+                        failures += 1;
+                        print_failure(&status, failures);
+                    }
+
+                    if changes_branch(&user_cmd) {
+                        branch = current_branch(config, directory.trim());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Prints a red line saying a git command failed, with its exit code and how many have
+    /// failed in this git session, so a failure stands out from normal output.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// (dev) >>>  pull
+    /// error: Your local changes to the following files would be overwritten by merge
+    /// git command failed (exit 1), 1 failure this session
+    /// ```
+    fn print_failure(status: &ExitStatus, failures: u32) {
+        let code: String = match status.code() {
+            Some(code) => format!("exit {code}"),
+            None => "killed by a signal".to_string(),
+        };
+        let plural: &str = if failures == 1 { "" } else { "s" };
+
+        println!(
+            "{}\n",
+            format!("git command failed ({code}), {failures} failure{plural} this session")
+                .red()
+                .bold()
+        );
+    }
+
+    /// Prompts for a repository path until it points at a git repository, offering to clone one
+    /// when it doesn't.
+    ///
+    /// Returns [`None`] at the end of input.
+    ///
+    fn repo_input(config: &Config) -> Option<String> {
+        loop {
+            let directory: String = normalise_path(&or_exit(msg_input("Repo path"))?);
+
+            if directory.is_empty() {
+                println!("{} repo path can't be empty", "error:".red());
+                continue;
+            }
+
+            let work_tree: String = format!("git -C {directory} rev-parse --is-inside-work-tree");
+            if run_cmd(config, &work_tree).status.success() {
+                return Some(directory);
+            }
+
+            let exists: String = format!("test -d {directory}");
+            if run_cmd(config, &exists).status.success() {
+                println!("{} {directory} is not a git repository", "error:".red());
+            } else {
+                println!("{} {directory} doesn't exist", "error:".red());
+            }
+
+            let answer: String = or_exit(msg_input("Clone a repository instead? [y/N]"))?;
+            if answer.trim().eq_ignore_ascii_case("y")
+                && let Some(cloned) = clone(config)
+            {
+                return Some(cloned);
+            }
+        }
+    }
+
+    /// Returns the path trimmed and without a trailing `/`.
+    ///
+    fn normalise_path(path: &str) -> String {
+        let path: &str = path.trim();
+
+        match path.trim_end_matches('/') {
+            "" if path.starts_with('/') => "/".to_string(),
+            trimmed => trimmed.to_string(),
+        }
+    }
+
+    /// Returns the branch checked out in the directory, or `detached at <commit>` when HEAD
+    /// isn't on a branch, warning if it isn't a git repository.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`None`] if the directory isn't a git repository.
+    ///
+    fn current_branch(config: &Config, directory: &str) -> Option<String> {
+        let branch_cmd: String = format!(
+            "cd {directory} && git rev-parse --abbrev-ref HEAD && git rev-parse --short HEAD"
+        );
+        let output: Output = run_cmd(config, &branch_cmd);
+
+        if output.status.success() {
+            Some(branch_name(&String::from_utf8_lossy(&output.stdout)))
+        } else {
+            println!(
+                "{} {directory} is not a git repository\n",
+                "warning:".yellow()
+            );
+            None
+        }
+    }
+
+    /// Returns the branch from the output of `git rev-parse --abbrev-ref HEAD` followed by
+    /// `git rev-parse --short HEAD`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(branch_name("HEAD\n3f51a4b\n"), "detached at 3f51a4b");
+    /// ```
+    fn branch_name(stdout: &str) -> String {
+        let mut lines = stdout.lines().map(str::trim);
+
+        match (lines.next(), lines.next()) {
+            (Some("HEAD"), Some(commit)) => format!("detached at {commit}"),
+            (Some(branch), _) => branch.to_string(),
+            (None, _) => String::new(),
+        }
+    }
+
+    /// Lists the local and remote branches with the checked out one in green, or a detached
+    /// HEAD in yellow.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// (dev) >>>  branches
+    /// * dev
+    ///   main
+    ///   remotes/origin/HEAD -> origin/main
+    ///   remotes/origin/dev
+    /// ```
+    fn branches(config: &Config, directory: &str) {
+        let list_cmd: String = format!("cd {directory} && git branch -a --no-color");
+        let output: Output = run_cmd(config, &list_cmd);
+        if !output.status.success() {
+            helpers::print_cmd(&output);
+            return;
+        }
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            match line.strip_prefix("* ") {
+                Some(current) if current.starts_with('(') => {
+                    println!("{} {}", "*".yellow(), current.yellow().bold())
+                }
+                Some(current) => println!("{} {}", "*".green(), current.green().bold()),
+                None => println!("{line}"),
+            }
+        }
+
+        println!();
+    }
+
+    /// Returns true if the command is a plain `checkout <branch>`, with or without `git`, whose
+    /// branch can be checked before switching. Checkouts with options or paths go to git as is.
+    ///
+    fn is_branch_checkout(user_cmd: &str) -> bool {
+        let git_cmd: String = with_git(user_cmd);
+        let words: Vec<&str> = git_cmd.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["git", "checkout" | "switch", branch] => !branch.starts_with('-'),
+            _ => false,
+        }
+    }
+
+    /// Returns true if a `checkout <branch>` names a local branch or one on a remote, printing
+    /// an error if it doesn't so the checkout isn't sent.
+    ///
+    fn branch_exists(config: &Config, directory: &str, user_cmd: &str) -> bool {
+        let branch: &str = user_cmd.split_whitespace().last().unwrap_or("");
+
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/');
+        if !branch.chars().all(valid) {
+            println!("{} '{branch}' isn't a valid branch name\n", "error:".red());
+            return false;
+        }
+
+        let refs: String = format!(
+            "cd {directory} && git for-each-ref refs/heads/{branch} refs/remotes/*/{branch}"
+        );
+        let output: Output = run_cmd(config, &refs);
+
+        if output.status.success() && !output.stdout.is_empty() {
+            return true;
+        }
+
+        println!(
+            "{} no branch named '{branch}', run 'branches' to list them\n",
+            "error:".red()
+        );
+        false
+    }
+
+    /// Returns true if the command could move HEAD to another branch.
+    ///
+    fn changes_branch(user_cmd: &str) -> bool {
+        user_cmd
+            .split_whitespace()
+            .any(|word: &str| matches!(word, "checkout" | "switch" | "pull" | "merge"))
+    }
+
+    /// Git subcommands that are sent without asking, anything else is confirmed first in case
+    /// it is a typo.
+    ///
+    const GIT_COMMANDS: &[&str] = &[
+        "add",
+        "am",
+        "apply",
+        "archive",
+        "bisect",
+        "blame",
+        "branch",
+        "bundle",
+        "checkout",
+        "cherry-pick",
+        "clean",
+        "clone",
+        "commit",
+        "config",
+        "describe",
+        "diff",
+        "fetch",
+        "format-patch",
+        "gc",
+        "grep",
+        "help",
+        "init",
+        "lfs",
+        "log",
+        "ls-files",
+        "ls-remote",
+        "maintenance",
+        "merge",
+        "mv",
+        "notes",
+        "pull",
+        "push",
+        "rebase",
+        "reflog",
+        "remote",
+        "reset",
+        "restore",
+        "revert",
+        "rev-parse",
+        "rm",
+        "shortlog",
+        "show",
+        "sparse-checkout",
+        "stash",
+        "status",
+        "submodule",
+        "switch",
+        "tag",
+        "version",
+        "worktree",
+    ];
+
+    /// Runs a git command on the EC2 instance and returns its exit status, or [`None`] if it
+    /// wasn't sent.
+    ///
+    /// `git` is prepended if it was left off, and a subcommand that isn't in [`GIT_COMMANDS`]
+    /// is only sent once the user confirms it, saving a round trip on typos like `git stauts`.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    ///
+    /// # Examples
+    /// ```rust
+    /// git_cmd(&Ssh, &config, "Documents/repository", "git status");
+    /// ```
+    /// Output
+    /// ```plaintext
+    /// [user@wcli ~]$ git   
+    /// Repo path: directory/repository
+    /// (dev) >>>  git status
+    /// On branch dev
+    /// ...
+    /// ```
+    fn git_cmd(
+        runner: &dyn CommandRunner,
+        config: &Config,
+        directory: &str,
+        user_cmd: &str,
+    ) -> Option<ExitStatus> {
+        let user_cmd: String = with_git(user_cmd);
+
+        if let Some(subcommand) = unknown_subcommand(&user_cmd) {
+            let hint: String = suggest(subcommand)
+                .map(|known: &str| format!(", did you mean '{known}'?"))
+                .unwrap_or_default();
+            println!(
+                "{} '{subcommand}' isn't a known git command{hint}",
+                "warning:".yellow()
+            );
+
+            let answer: String = or_exit(msg_input("Send it anyway? [y/N]")).unwrap_or_default();
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                println!();
+                return None;
+            }
+        }
+
+        if !confirm_dangerous(config, &user_cmd) {
+            return None;
+        }
+
+        let git_cmd: String = format!("cd {directory} && {user_cmd}");
+
+        let output = runner.run(config, &git_cmd);
+
+        helpers::print_cmd(&output);
+        Some(output.status)
+    }
+
+    /// Returns the command with `git` in front, unless it already starts with it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(with_git("log --oneline"), "git log --oneline");
+    /// ```
+    fn with_git(user_cmd: &str) -> String {
+        let user_cmd: &str = user_cmd.trim();
+
+        match user_cmd.split_whitespace().next() {
+            Some("git") => user_cmd.to_string(),
+            _ => format!("git {user_cmd}"),
+        }
+    }
+
+    /// Returns the git subcommand if it isn't one of [`GIT_COMMANDS`].
+    ///
+    /// Global options before the subcommand, like `-C <path>` or `--no-pager`, are skipped.
+    ///
+    fn unknown_subcommand(git_cmd: &str) -> Option<&str> {
+        let mut words = git_cmd.split_whitespace().skip(1);
+
+        while let Some(word) = words.next() {
+            match word {
+                "-C" | "-c" => {
+                    words.next();
+                }
+                _ if word.starts_with('-') => {}
+                _ if GIT_COMMANDS.contains(&word) => return None,
+                _ => return Some(word),
+            }
+        }
+
+        None
+    }
+
+    /// Returns the known subcommand closest to a mistyped one, if it is within two edits.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(suggest("stauts"), Some("status"));
+    /// ```
+    fn suggest(subcommand: &str) -> Option<&'static str> {
+        GIT_COMMANDS
+            .iter()
+            .map(|known: &&str| (distance(subcommand, known), *known))
+            .filter(|(distance, _)| *distance <= 2)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, known)| known)
+    }
+
+    /// Returns the number of single character edits between two words.
+    ///
+    fn distance(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+
+        for (i, a) in a.chars().enumerate() {
+            let mut previous: usize = row[0];
+            row[0] = i + 1;
+
+            for j in 0..b.len() {
+                let substitute: usize = previous + usize::from(a != b[j]);
+                previous = row[j + 1];
+                row[j + 1] = substitute.min(row[j] + 1).min(previous + 1);
+            }
+        }
+
+        row[b.len()]
+    }
+
+    /// Clones a repository on the EC2 and returns the directory it was cloned into.
+    ///
+    /// Prompts for the URL and target directory, asking before cloning into a directory that
+    /// already exists.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`None`] if the clone is cancelled or fails.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  clone
+    /// Git URL: https://github.com/Taghunter98/wcli.git
+    /// Target directory: Documents/wcli
+    /// Cloned into Documents/wcli
+    /// ```
+    fn clone(config: &Config) -> Option<String> {
+        let url: String = or_exit(msg_input("Git URL"))?.trim().to_string();
+        let directory: String = or_exit(msg_input("Target directory"))?.trim().to_string();
+
+        if url.is_empty() || directory.is_empty() {
+            println!("clone requires a URL and a target directory\n");
+            return None;
+        }
+
+        let exists: String = format!("test -e {directory}");
+
+        if run_cmd(config, &exists).status.success() {
+            let question: String = format!("{directory} already exists, clone? [y/N]");
+            let answer: String = or_exit(msg_input(&question))?;
+
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                println!();
+                return None;
+            }
+        }
+
+        let output: Output = run_cmd(config, &format!("git clone {url} {directory}"));
+
+        if output.status.success() {
+            println!("{} into {directory}\n", "Cloned".green());
+            Some(directory)
+        } else {
+            helpers::print_cmd(&output);
+            None
+        }
+    }
+
+    /// Prints out a help message.
+    ///
+    fn git_help() {
+        println!("\nCOMMANDS");
+        println!("'any'        -> run a git command, 'git' can be left off");
+        println!("'change'     -> change git directory");
+        println!("'clone'      -> clone a repository and switch to it");
+        println!("'branches'   -> list the local and remote branches");
+        println!("'checkout'   -> switch branch, checking the branch exists first");
+        println!("'timestamps' -> head each output with the time, 'timestamps on' or 'off'");
+        println!("'history'    -> list past commands, '!N' reruns one and '!!' the last");
+        println!("'clear'      -> clears the terminal");
+        println!("'exit'       -> exit git");
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{
+            branch_name, git_cmd, is_branch_checkout, suggest, unknown_subcommand, with_git,
+        };
+        use crate::{cmd::tests::MockRunner, config::Config};
+
+        #[test]
+        fn git_cmd_runs_in_the_repo() {
+            let runner: MockRunner = MockRunner::new("");
+            git_cmd(&runner, &Config::test(), "/var/www/app", "status\n");
+            git_cmd(&runner, &Config::test(), "app", "git log --oneline");
+
+            assert_eq!(
+                runner.calls.into_inner(),
+                vec![
+                    ("cd /var/www/app && git status".to_string(), None),
+                    ("cd app && git log --oneline".to_string(), None),
+                ]
+            );
+        }
+
+        #[test]
+        fn branch_name_reports_a_detached_head() {
+            assert_eq!(branch_name("dev\n3f51a4b\n"), "dev");
+            assert_eq!(branch_name("HEAD\n3f51a4b\n"), "detached at 3f51a4b");
+        }
+
+        #[test]
+        fn only_plain_checkouts_are_checked() {
+            assert!(is_branch_checkout("checkout dev"));
+            assert!(is_branch_checkout("git switch feature/login"));
+            assert!(!is_branch_checkout("checkout -b dev"));
+            assert!(!is_branch_checkout("checkout dev -- src/main.rs"));
+        }
+
+        #[test]
+        fn git_is_prepended_once() {
+            assert_eq!(with_git("status\n"), "git status");
+            assert_eq!(with_git("git log --oneline"), "git log --oneline");
+        }
+
+        #[test]
+        fn unknown_subcommands_are_caught() {
+            assert_eq!(unknown_subcommand("git stauts"), Some("stauts"));
+            assert_eq!(unknown_subcommand("git -C app --no-pager log"), None);
+            assert_eq!(unknown_subcommand("git --version"), None);
+            assert_eq!(suggest("stauts"), Some("status"));
+            assert_eq!(suggest("frobnicate"), None);
+        }
+    }
+}
+
+pub mod sql {
+    //! This module provides an API for running sql commands.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::{
+        fs,
+        process::Output,
+        time::{Duration, Instant},
+    };
+
+    use colored::Colorize;
+
+    use crate::{
+        cmd::{
+            CommandRunner, LineEditor, PromptVars, Ssh, confirm_dangerous, confirm_host,
+            fail_script,
+            helpers::{self, clear},
+            is_blank_or_comment, msg_input, or_exit, run_cmd_input, shell_prompt,
+        },
+        config::Config,
+        logging,
+        session::Session,
+    };
+
+    /// Provides an API for running sql commands.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+    /// - Panics [`panic`](std::macros) if mariadb connection is unsuccessful.
+    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    ///
+    /// # Examples
+    /// ```rust
+    /// run_sql(&session);
+    /// ```
+    /// Select emails from table
+    /// ```plaintext
+    /// >>> SELECT email FROM <Table>;
+    /// ```
+    /// Describe a table
+    /// ```plaintext
+    /// >>> DESCRIBE <Table>;
+    /// ```
+    /// Delete a table
+    /// ```plaintext
+    /// >>> DROP Table <Table>;
+    /// ```
+    pub fn run_sql(session: &Session) {
+        logging::set_shell("sql");
+        let config: &Config = &session.config;
+        test_sql_connection(config);
+
+        let Some(database) = database_input() else {
+            return;
+        };
+        let mut editor: LineEditor = LineEditor::new("sql");
+        let mut raw: bool = false;
+        let mut last_query: Option<String> = None;
+        let mut transaction: Option<Vec<String>> = None;
+        println!("Run 'help' for commands\n");
+
+        let prompt_vars: PromptVars = PromptVars {
+            user: &session.user,
+            cwd: &database,
+            shell: "sql",
+            ..PromptVars::default()
+        };
+
+        loop {
+            let pending: String = match &transaction {
+                Some(statements) => format!("(tx {}) ", statements.len()),
+                None => String::new(),
+            };
+            let prompt: String = pending + &shell_prompt(config, ">>> ", &prompt_vars);
+            let Some(query) = or_exit(editor.prompt_input(&prompt)) else {
+                break;
+            };
+            if is_blank_or_comment(&query) {
+                continue;
+            }
+
+            match query.trim() {
+                "exit" => break,
+                "database" => {
+                    println!("In database: {}", &database);
+                }
+                "change" => {
+                    discard(transaction.take());
+                    run_sql(session)
+                }
+                "begin" if transaction.is_some() => println!(
+                    "{} a transaction is already open, 'commit' or 'rollback' it first\n",
+                    "error:".red()
+                ),
+                "begin" => {
+                    transaction = Some(Vec::new());
+                    println!("Transaction started, queries run together on 'commit'\n");
+                }
+                "commit" => match transaction.take() {
+                    Some(statements) if statements.is_empty() => println!("Nothing to commit\n"),
+                    Some(statements) => {
+                        let sql: String = transaction_sql(&statements);
+                        commit(config, database.trim(), &sql, statements.len(), raw);
+                    }
+                    None => println!("{} no open transaction, run 'begin'\n", "error:".red()),
+                },
+                "rollback" => match transaction.take() {
+                    Some(statements) => discard(Some(statements)),
+                    None => println!("{} no open transaction, run 'begin'\n", "error:".red()),
+                },
+                "timestamps on" => helpers::set_timestamps(true),
+                "timestamps off" => helpers::set_timestamps(false),
+                "raw" | "--raw" => {
+                    raw = !raw;
+                    println!("Raw output {}", if raw { "on" } else { "off" });
+                }
+                "tables" => tables(config, &database, raw),
+                cmd if cmd.starts_with("describe ") => {
+                    describe(config, &database, &cmd["describe ".len()..], raw)
+                }
+                "rerun" => match &last_query {
+                    Some(last) => sql_query(&Ssh, config, database.trim(), last, raw),
+                    None => println!("{} no previous query to rerun\n", "error:".red()),
+                },
+                "edit" => match &last_query {
+                    Some(last) => println!("{last}\n"),
+                    None => println!("{} no previous query to edit\n", "error:".red()),
+                },
+                cmd if cmd == "export" || cmd.starts_with("export ") => export(
+                    &Ssh,
+                    config,
+                    &database,
+                    last_query.as_deref(),
+                    cmd["export".len()..].trim(),
+                ),
+                cmd if cmd == "source" || cmd.starts_with("source ") => {
+                    let path: &str = cmd["source".len()..].trim();
+                    match (read_script(path), &mut transaction) {
+                        (Err(e), _) => println!("{} {e}\n", "error:".red()),
+                        (Ok(script), _) if !confirm_script(config, path, &script) => {}
+                        (Ok(script), Some(statements)) => statements.push(script),
+                        (Ok(script), None) => source(config, database.trim(), path, &script, raw),
+                    }
+                }
+                "history" => editor.print_history(),
+                "clear" => clear(),
+                "help" => sql_help(),
+                _ => {
+                    let (query, forced) = match query.trim().strip_prefix("force ") {
+                        Some(query) => (query.trim(), true),
+                        None => (query.trim(), false),
+                    };
+
+                    if !forced
+                        && config.sql_safe_mode
+                        && let Some(keyword) = missing_where(query)
+                    {
+                        println!(
+                            "{} refused {keyword} without a WHERE, it would change every row",
+                            "warning:".yellow().bold()
+                        );
+                        println!("Run 'force {query}' if that's what you want\n");
+                        fail_script();
+                        continue;
+                    }
+
+                    if !confirm_dangerous(config, query) {
+                        continue;
+                    }
+
+                    match &mut transaction {
+                        Some(statements) => statements.push(query.to_string()),
+                        None => {
+                            sql_query(&Ssh, config, database.trim(), query, raw);
+                            last_query = Some(query.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        discard(transaction);
+    }
+
+    /// Returns the statements of a transaction wrapped in `START TRANSACTION;` and `COMMIT;`,
+    /// adding the `;` a statement was typed without.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let statements = vec!["DELETE FROM Users WHERE id = 4".to_string()];
+    /// assert_eq!(
+    ///     transaction_sql(&statements),
+    ///     "START TRANSACTION;\nDELETE FROM Users WHERE id = 4;\nCOMMIT;"
+    /// );
+    /// ```
+    fn transaction_sql(statements: &[String]) -> String {
+        let mut sql: String = "START TRANSACTION;\n".to_string();
+
+        for statement in statements {
+            sql.push_str(statement);
+            if !statement.ends_with(';') {
+                sql.push(';');
+            }
+            sql.push('\n');
+        }
+
+        sql + "COMMIT;"
+    }
+
+    /// Runs a transaction as a single mariadb invocation, so its statements are committed
+    /// together or not at all.
+    ///
+    /// mariadb stops at the first statement that fails, before `COMMIT;`, and the transaction
+    /// is rolled back when the connection closes.
+    ///
+    fn commit(config: &Config, database: &str, sql: &str, statements: usize, raw: bool) {
+        let output: Output = run_query(&Ssh, config, database, sql);
+        print_result(&output, raw);
+
+        if output.status.success() {
+            let plural: &str = if statements == 1 { "" } else { "s" };
+            println!("{} {statements} statement{plural}\n", "Committed".green());
+        } else {
+            println!("{} nothing was committed\n", "Rolled back,".red());
+        }
+    }
+
+    /// Returns the contents of a local sql script.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if no path was given, or the file can't be read or is empty.
+    ///
+    fn read_script(path: &str) -> Result<String, String> {
+        if path.is_empty() {
+            return Err("source requires a file, e.g. 'source migrate.sql'".to_string());
+        }
+
+        let script: String =
+            fs::read_to_string(path).map_err(|e| format!("unable to read {path}: {e}"))?;
+
+        if script.trim().is_empty() {
+            Err(format!("{path} is empty"))
+        } else {
+            Ok(script.trim_end().to_string())
+        }
+    }
+
+    /// Warns about a script that drops or truncates anything and asks before it runs,
+    /// returning whether to go ahead. Every script goes ahead under `--dry-run`.
+    ///
+    fn confirm_script(config: &Config, path: &str, script: &str) -> bool {
+        let Some(keyword) = destructive_keyword(script) else {
+            return true;
+        };
+        if config.dry_run {
+            return true;
+        }
+
+        println!(
+            "{} {path} contains {keyword} and may be destructive",
+            "warning:".yellow().bold()
+        );
+        confirm_host(config)
+    }
+
+    /// Returns `DELETE` or `UPDATE` if a statement in the sql is one without a `WHERE`, which
+    /// would change every row of the table. Case, spacing and line breaks don't matter.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(missing_where("delete  from Users;"), Some("DELETE"));
+    /// assert_eq!(missing_where("UPDATE Users SET age = 32\nWHERE id = 4;"), None);
+    /// ```
+    fn missing_where(sql: &str) -> Option<&'static str> {
+        sql.split(';').find_map(|statement: &str| {
+            let mut words = statement
+                .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .filter(|word: &&str| !word.is_empty());
+
+            let keyword: &'static str = match words.next()?.to_uppercase().as_str() {
+                "DELETE" => "DELETE",
+                "UPDATE" => "UPDATE",
+                _ => return None,
+            };
+
+            (!words.any(|word: &str| word.eq_ignore_ascii_case("WHERE"))).then_some(keyword)
+        })
+    }
+
+    /// Returns `DROP` or `TRUNCATE` if the sql uses either as a word, ignoring case.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(destructive_keyword("drop table Users;"), Some("DROP"));
+    /// assert_eq!(destructive_keyword("SELECT dropped FROM Users;"), None);
+    /// ```
+    fn destructive_keyword(sql: &str) -> Option<&'static str> {
+        sql.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .find_map(|word: &str| {
+                ["DROP", "TRUNCATE"]
+                    .into_iter()
+                    .find(|keyword: &&str| word.eq_ignore_ascii_case(keyword))
+            })
+    }
+
+    /// Runs a local sql script on the EC2 by writing it to mariadb's stdin, so statements
+    /// spanning several lines and quotes in the script reach mariadb untouched.
+    ///
+    /// mariadb stops at the first statement that fails, so the error is printed along with a
+    /// reminder that the statements before it have already run.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  source migrations/0004_add_orders.sql
+    /// Sourced migrations/0004_add_orders.sql
+    /// ```
+    fn source(config: &Config, database: &str, path: &str, script: &str, raw: bool) {
+        let output: Output = run_query(&Ssh, config, database, script);
+
+        if output.status.success() {
+            if !output.stdout.is_empty() {
+                print_result(&output, raw);
+            }
+            println!("{} {path}\n", "Sourced".green());
+        } else {
+            helpers::print_cmd(&output);
+            println!(
+                "{} {path} stopped at the error, the statements before it have run\n",
+                "error:".red()
+            );
+        }
+    }
+
+    /// Drops an open transaction without running it, saying how many statements were lost.
+    ///
+    fn discard(transaction: Option<Vec<String>>) {
+        if let Some(statements) = transaction {
+            let count: usize = statements.len();
+            let plural: &str = if count == 1 { "" } else { "s" };
+            println!("Discarded {count} uncommitted statement{plural}\n");
+        }
+    }
+
+    /// Prompts for a database name until a valid one is given, or [`None`] at the end of input.
+    ///
+    fn database_input() -> Option<String> {
+        loop {
+            let database: String = or_exit(msg_input("Database"))?.trim().to_string();
+
+            match validate_name(&database) {
+                Ok(()) => return Some(database),
+                Err(e) => println!("{} {e}", "error:".red()),
+            }
+        }
+    }
+
+    /// Checks that a database or table name only contains letters, digits and underscores.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if the name is empty or has any other characters.
+    ///
+    fn validate_name(name: &str) -> Result<(), String> {
+        let valid: bool = name
+            .chars()
+            .all(|c: char| c.is_ascii_alphanumeric() || c == '_');
+
+        if name.is_empty() || !valid {
+            Err(format!(
+                "invalid name '{name}', only letters, digits and '_' are allowed"
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the remote command that runs mariadb as `DB_USER`, reading sql from stdin.
+    ///
+    /// The first line of stdin is the password from [`login_password`], so it never reaches
+    /// mariadb as sql. `root` without `DB_PASS` logs in through sudo, which is only handed the
+    /// password when it actually needs it. Any other login gets `DB_PASS` in `MYSQL_PWD`
+    /// rather than on the command line, where `ps` would show it.
+    ///
+    fn mariadb_cmd(config: &Config) -> String {
+        let mut login: String = format!("-u {}", config.db_user);
+        if let Some(db_host) = &config.db_host {
+            login.push_str(&format!(" -h {db_host}"));
+        }
+        if let Some(db_port) = config.db_port {
+            login.push_str(&format!(" -P {db_port}"));
+        }
+
+        match &config.db_pass {
+            None if config.db_user == "root" => format!(
+                "IFS= read -r pass; if sudo -n true 2>/dev/null; then sudo mariadb {login}; \
+                 else {{ printf \"%s\\n\" \"$pass\"; cat; }} | sudo -S -p \"\" mariadb {login}; fi"
+            ),
+            Some(_) => format!("IFS= read -r pass; MYSQL_PWD=\"$pass\" mariadb {login}"),
+            None => format!("IFS= read -r pass; mariadb {login}"),
+        }
+    }
+
+    /// Returns where the database server is, `DB_HOST` and `DB_PORT` when set and the EC2
+    /// otherwise.
+    ///
+    fn db_address(config: &Config) -> String {
+        let db_host: &str = config.db_host.as_deref().unwrap_or("localhost");
+
+        match config.db_port {
+            Some(db_port) => format!("{db_host}:{db_port}"),
+            None => db_host.to_string(),
+        }
+    }
+
+    /// Returns the password written ahead of the sql for [`mariadb_cmd`], `DB_PASS` if it is
+    /// set, the sudo password for `root` and nothing otherwise.
+    ///
+    fn login_password(config: &Config) -> &str {
+        match &config.db_pass {
+            Some(db_pass) => db_pass,
+            None if config.db_user == "root" => &config.pass,
+            None => "",
+        }
+    }
+
+    /// Runs a sql query on the EC2.
+    ///
+    /// The query is written to mariadb's stdin rather than interpolated into the command, so
+    /// quotes and semicolons in the query are passed through untouched.
+    ///
+    /// Result sets are printed as an aligned table unless `raw` is set, in which case mariadb's
+    /// tab separated output is printed as is. A dim footer follows, like the mysql client's
+    /// `3 rows in set` or `Query OK, 1 row affected`.
+    ///
+    /// mariadb doesn't report affected rows in batch mode, so `SELECT ROW_COUNT();` is run
+    /// after an `INSERT`, `UPDATE`, `DELETE` or `REPLACE` and taken back off the output.
+    ///
+    /// ## Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    fn sql_query(
+        runner: &dyn CommandRunner,
+        config: &Config,
+        database: &str,
+        query: &str,
+        raw: bool,
+    ) {
+        let keyword: String = query.split_whitespace().next().unwrap_or("").to_uppercase();
+        let dml: bool = matches!(keyword.as_str(), "INSERT" | "UPDATE" | "DELETE" | "REPLACE");
+
+        let sql: String = if dml {
+            format!("{query}\nSELECT ROW_COUNT();")
+        } else {
+            query.to_string()
+        };
+        let mut output: Output = run_query(runner, config, database, &sql);
+        if !output.status.success() || config.dry_run {
+            print_result(&output, raw);
+            return;
+        }
+
+        let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+        let summary: String = match split_row_count(&stdout) {
+            (rest, Some(affected)) if dml => {
+                output.stdout = rest.as_bytes().to_vec();
+                format!("Query OK, {} affected", rows(affected))
+            }
+            _ => result_summary(&keyword, &stdout),
+        };
+
+        if !output.stdout.trim_ascii().is_empty() {
+            print_result(&output, raw);
+        }
+        println!("{}\n", summary.dimmed());
+    }
+
+    /// Splits the `ROW_COUNT()` result set added after a statement off the end of the output.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(split_row_count("ROW_COUNT()\n3\n"), ("", Some(3)));
+    /// ```
+    fn split_row_count(stdout: &str) -> (&str, Option<u64>) {
+        let trimmed: &str = stdout.trim_end();
+
+        if let Some((rest, count)) = trimmed.rsplit_once('\n')
+            && let Some(rest) = rest.strip_suffix("ROW_COUNT()")
+            && let Ok(count) = count.trim().parse::<u64>()
+        {
+            return (rest, Some(count));
+        }
+
+        (stdout, None)
+    }
+
+    /// Returns the footer for a statement that isn't a DML, `N rows in set` for anything that
+    /// returns rows and `Query OK` otherwise.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(result_summary("SELECT", "name\nBeth\nJosh\n"), "2 rows in set");
+    /// ```
+    fn result_summary(keyword: &str, stdout: &str) -> String {
+        let result_set: bool = matches!(
+            keyword,
+            "SELECT" | "SHOW" | "DESCRIBE" | "DESC" | "EXPLAIN" | "WITH" | "TABLE" | "VALUES"
+        );
+
+        match parse_rows(stdout).len() {
+            0 if !result_set => "Query OK".to_string(),
+            lines => format!("{} in set", rows(lines.saturating_sub(1) as u64)),
+        }
+    }
+
+    /// Returns `1 row` or `N rows`.
+    ///
+    fn rows(count: u64) -> String {
+        let plural: &str = if count == 1 { "" } else { "s" };
+
+        format!("{count} row{plural}")
+    }
+
+    /// Lists the tables in the current database.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    ///
+    fn tables(config: &Config, database: &str, raw: bool) {
+        let output: Output = run_query(&Ssh, config, database, "SHOW TABLES;");
+
+        if output.status.success() && output.stdout.trim_ascii().is_empty() {
+            println!("no tables\n");
+        } else {
+            print_result(&output, raw);
+        }
+    }
+
+    /// Describes the columns of a table in the current database.
+    ///
+    /// # Errors
+    ///
+    /// - Prints an error if the table name is invalid.
+    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    ///
+    fn describe(config: &Config, database: &str, table: &str, raw: bool) {
+        let table: &str = table.trim().trim_end_matches(';').trim_end();
+
+        match validate_name(table) {
+            Ok(()) => sql_query(&Ssh, config, database, &format!("DESCRIBE {table};"), raw),
+            Err(e) => println!("{} {e}", "error:".red()),
+        }
+    }
+
+    /// Runs the last query again and writes its result set to a local CSV file.
+    ///
+    /// Only a query that returns rows is run again, so exporting after an `INSERT`, `UPDATE`
+    /// or `DELETE` doesn't repeat the change.
+    ///
+    /// # Errors
+    ///
+    /// - Prints an error if there is no previous query, it doesn't return rows, it returned no
+    ///   result set or the file can't be written.
+    /// - Returns [`stderr`](std::process::Output) if the query fails.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  SELECT name, age FROM Users;
+    /// >>>  export users.csv
+    /// Exported 2 rows to users.csv
+    /// ```
+    fn export(
+        runner: &dyn CommandRunner,
+        config: &Config,
+        database: &str,
+        query: Option<&str>,
+        path: &str,
+    ) {
+        let Some(query) = query else {
+            println!("{} no query to export, run one first\n", "error:".red());
+            return;
+        };
+
+        if !returns_rows(query) {
+            println!("{} the last query didn't return rows\n", "error:".red());
+            return;
+        }
+
+        if path.is_empty() {
+            println!("{} export requires a file name\n", "error:".red());
+            return;
+        }
+
+        let output: Output = run_query(runner, config, database, query);
+        if !output.status.success() {
+            helpers::print_cmd(&output);
+            return;
+        }
+
+        let rows: Vec<Vec<String>> = parse_rows(&String::from_utf8_lossy(&output.stdout));
+        if rows.is_empty() {
+            println!("{} the last query returned no result set\n", "error:".red());
+            return;
+        }
+
+        match fs::write(path, to_csv(&rows)) {
+            Ok(()) => println!("{} {} rows to {path}\n", "Exported".green(), rows.len() - 1),
+            Err(e) => println!("{} unable to write {path}: {e}\n", "error:".red()),
+        }
+    }
+
+    /// Returns true if every statement in the sql only reads rows, a `SELECT`, `SHOW`,
+    /// `DESCRIBE` or `EXPLAIN`, so it is safe to run again.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert!(returns_rows("SELECT name FROM Users;"));
+    /// assert!(!returns_rows("SELECT 1; DELETE FROM Users WHERE id = 4;"));
+    /// ```
+    fn returns_rows(sql: &str) -> bool {
+        let mut statements = sql
+            .split(';')
+            .map(str::trim)
+            .filter(|statement: &&str| !statement.is_empty())
+            .peekable();
+
+        statements.peek().is_some()
+            && statements.all(|statement: &str| {
+                let keyword: String = statement
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .next()
+                    .unwrap_or("")
+                    .to_uppercase();
+
+                matches!(
+                    keyword.as_str(),
+                    "SELECT" | "SHOW" | "DESCRIBE" | "DESC" | "EXPLAIN"
+                )
+            })
+    }
+
+    /// Returns the rows as RFC 4180 CSV.
+    ///
+    /// Fields containing commas, quotes or line breaks are quoted, with quotes doubled, and
+    /// records end with CRLF.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let rows = vec![vec!["name".to_string()], vec!["Beth, \"B\"".to_string()]];
+    /// assert_eq!(to_csv(&rows), "name\r\n\"Beth, \"\"B\"\"\"\r\n");
+    /// ```
+    fn to_csv(rows: &[Vec<String>]) -> String {
+        let mut csv: String = String::new();
+
+        for row in rows {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|field: &String| {
+                    if field.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", field.replace('"', "\"\""))
+                    } else {
+                        field.clone()
+                    }
+                })
+                .collect();
+
+            csv.push_str(&fields.join(","));
+            csv.push_str("\r\n");
+        }
+
+        csv
+    }
+
+    /// Returns Output of a sql query run against the database on the EC2.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
+    ///
+    fn run_query(
+        runner: &dyn CommandRunner,
+        config: &Config,
+        database: &str,
+        query: &str,
+    ) -> Output {
+        let sql: String = format!("{}\nUSE {database};\n{query}\n", login_password(config));
+
+        runner.run_input(config, &mariadb_cmd(config), &sql)
+    }
+
+    /// Prints a query result as an aligned table, or as is when `raw` is set or on error.
+    ///
+    fn print_result(output: &Output, raw: bool) {
+        match format_table(&String::from_utf8_lossy(&output.stdout)) {
+            Some(table) if output.status.success() && !raw => {
+                helpers::print_timestamp();
+                println!("{table}")
+            }
+            _ => helpers::print_cmd(output),
+        }
+    }
+
+    /// Returns mariadb's tab separated output rendered as an aligned table.
+    ///
+    /// Cells containing newlines span several lines of the table. Returns `None` if there is
+    /// no result set or the rows don't all have the same number of columns.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// +------+------+
+    /// | name | age  |
+    /// +------+------+
+    /// | Beth | 31   |
+    /// | Josh | NULL |
+    /// +------+------+
+    /// ```
+    fn format_table(stdout: &str) -> Option<String> {
+        let rows: Vec<Vec<String>> = parse_rows(stdout);
+
+        let columns: usize = rows.first()?.len();
+        if rows.iter().any(|row: &Vec<String>| row.len() != columns) {
+            return None;
+        }
+
+        let mut widths: Vec<usize> = vec![0; columns];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                let longest: usize = cell
+                    .lines()
+                    .map(|l: &str| l.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                *width = (*width).max(longest);
+            }
+        }
+
+        let separator: String = widths
+            .iter()
+            .map(|width: &usize| "-".repeat(width + 2))
+            .collect::<Vec<String>>()
+            .join("+");
+        let separator: String = format!("+{separator}+");
+
+        let mut table: Vec<String> = vec![separator.clone()];
+        for (i, row) in rows.iter().enumerate() {
+            let height: usize = row
+                .iter()
+                .map(|c: &String| c.lines().count())
+                .max()
+                .unwrap_or(1);
+
+            for n in 0..height.max(1) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| {
+                        let line: &str = cell.lines().nth(n).unwrap_or("");
+                        format!(" {line:<width$} ")
+                    })
+                    .collect();
+
+                table.push(format!("|{}|", cells.join("|")));
+            }
+
+            if i == 0 {
+                table.push(separator.clone());
+            }
+        }
+        table.push(separator);
+
+        Some(table.join("\n"))
+    }
+
+    /// Returns the header and rows of mariadb's tab separated output, with cells unescaped.
+    ///
+    fn parse_rows(stdout: &str) -> Vec<Vec<String>> {
+        stdout
+            .lines()
+            .map(|line: &str| line.split('\t').map(unescape).collect())
+            .collect()
+    }
+
+    /// Reverses the escaping mariadb applies to tabs, newlines and backslashes in batch mode.
+    ///
+    fn unescape(cell: &str) -> String {
+        let mut unescaped: String = String::new();
+        let mut chars: std::str::Chars<'_> = cell.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some('0') => unescaped.push_str("\\0"),
+                Some(other) => unescaped.push(other),
+                None => unescaped.push('\\'),
+            }
+        }
+
+        unescaped
+    }
+
+    /// Tests the connection to mariadb.
+    ///
+    /// # Errors
+    ///
+    /// - Panics [`panic`](std::macros) if mariadb connection is unsuccessful.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// test_sql_connection(&config);
+    /// ```
+    ///
+    fn test_sql_connection(config: &Config) {
+        let start: Instant = Instant::now();
+        let input: String = format!("{}\n", login_password(config));
+        let output: Output = run_cmd_input(config, &mariadb_cmd(config), &input);
+        let elapsed: Duration = start.elapsed();
+
+        if output.status.success() {
+            println!(
+                "{} to mariadb on {} in {}\n",
+                "Connected".green(),
+                db_address(config),
+                helpers::format_duration(elapsed)
+            )
+        } else {
+            panic!("unable to connect to mariadb on {}", db_address(config))
+        }
+    }
+
+    /// Prints out a help message.
+    ///
+    fn sql_help() {
+        println!("\nCOMMANDS");
+        println!("'any'         -> run a sql query, ensure syntax is correct");
+        println!("'change db'   -> show current database");
+        println!("'tables'      -> list the tables in the database");
+        println!("'describe'    -> describe a table, e.g. 'describe Users'");
+        println!("'raw'         -> toggle raw tab separated output");
+        println!("'rerun'       -> run the last query again");
+        println!("'edit'        -> print the last query to copy and tweak");
+        println!("'export'      -> save the last result as CSV, e.g. 'export users.csv'");
+        println!("'source'      -> run a local sql file, e.g. 'source migrate.sql'");
+        println!("'force'       -> run a DELETE or UPDATE without a WHERE anyway");
+        println!("'begin'       -> hold the following queries as one transaction");
+        println!("'commit'      -> run the held queries together, all or nothing");
+        println!("'rollback'    -> discard the held queries");
+        println!("'timestamps'  -> head each output with the time, 'timestamps on' or 'off'");
+        println!("'history'     -> list past queries, '!N' reruns one and '!!' the last");
+        println!("'clear'       -> clears the terminal");
+        println!("'exit'        -> exit sql");
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{
+            destructive_keyword, export, missing_where, parse_rows, result_summary, returns_rows,
+            split_row_count, sql_query, to_csv, transaction_sql, validate_name,
+        };
+        use crate::{cmd::tests::MockRunner, config::Config};
+
+        #[test]
+        fn sql_query_sends_the_query_on_stdin() {
+            let runner: MockRunner = MockRunner::new("ROW_COUNT()\n1\n");
+            let mut config: Config = Config::test();
+            sql_query(&runner, &config, "shop", "SELECT * FROM Users;", false);
+
+            config.db_user = "app".to_string();
+            config.db_pass = Some("s3cret".to_string());
+            config.db_host = Some("db.test".to_string());
+            let delete: &str = "DELETE FROM Users WHERE id = 4;";
+            sql_query(&runner, &config, "shop", delete, false);
+
+            let calls: Vec<(String, Option<String>)> = runner.calls.into_inner();
+            assert!(calls[0].0.contains("sudo mariadb -u root;"));
+            assert_eq!(
+                calls[0].1.as_deref(),
+                Some("hunter2\nUSE shop;\nSELECT * FROM Users;\n")
+            );
+            assert_eq!(
+                calls[1].0,
+                "IFS= read -r pass; MYSQL_PWD=\"$pass\" mariadb -u app -h db.test"
+            );
+            assert_eq!(
+                calls[1].1.as_deref(),
+                Some("s3cret\nUSE shop;\nDELETE FROM Users WHERE id = 4;\nSELECT ROW_COUNT();\n")
+            );
+        }
+
+        #[test]
+        fn quotes_and_semicolons_reach_mariadb_unchanged() {
+            let runner: MockRunner = MockRunner::new("");
+            let query: &str = "SELECT \"a;b\", 'it''s', `x;y` FROM t;";
+            sql_query(&runner, &Config::test(), "shop", query, false);
+
+            let calls: Vec<(String, Option<String>)> = runner.calls.into_inner();
+            assert!(!calls[0].0.contains("a;b"));
+            assert_eq!(
+                calls[0].1.as_deref(),
+                Some("hunter2\nUSE shop;\nSELECT \"a;b\", 'it''s', `x;y` FROM t;\n")
+            );
+        }
+
+        #[test]
+        fn export_only_reruns_queries_that_return_rows() {
+            let runner: MockRunner = MockRunner::new("");
+            let delete: &str = "DELETE FROM Users WHERE id = 4;";
+            export(&runner, &Config::test(), "shop", Some(delete), "users.csv");
+            assert!(runner.calls.borrow().is_empty());
+
+            assert!(returns_rows("select name FROM Users;"));
+            assert!(returns_rows("SHOW TABLES"));
+            assert!(!returns_rows("START TRANSACTION;\nSELECT 1;\nCOMMIT;"));
+            assert!(!returns_rows("SELECT 1; DELETE FROM Users WHERE id = 4;"));
+            assert!(!returns_rows(""));
+        }
+
+        #[test]
+        fn database_names_are_validated() {
+            assert!(validate_name("shop_2").is_ok());
+            assert!(validate_name("").is_err());
+            assert!(validate_name("foo; rm -rf /").is_err());
+            assert!(validate_name("shop'").is_err());
+        }
+
+        #[test]
+        fn missing_where_finds_whole_table_changes() {
+            assert_eq!(missing_where("delete  from Users;"), Some("DELETE"));
+            assert_eq!(missing_where("Update Users\nSET age = 32;"), Some("UPDATE"));
+            assert_eq!(missing_where("DELETE FROM Users where id = 4;"), None);
+            assert_eq!(missing_where("SELECT 1; DELETE FROM a;"), Some("DELETE"));
+            assert_eq!(missing_where("SELECT * FROM Users;"), None);
+        }
+
+        #[test]
+        fn summary_counts_rows_in_set_and_affected() {
+            assert_eq!(result_summary("SELECT", "name\nBeth\n"), "1 row in set");
+            assert_eq!(result_summary("SELECT", ""), "0 rows in set");
+            assert_eq!(result_summary("CREATE", ""), "Query OK");
+            assert_eq!(split_row_count("ROW_COUNT()\n1\n"), ("", Some(1)));
+            assert_eq!(split_row_count("id\n4\n"), ("id\n4\n", None));
+        }
+
+        #[test]
+        fn destructive_keyword_matches_whole_words() {
+            assert_eq!(destructive_keyword("drop table Users;"), Some("DROP"));
+            assert_eq!(destructive_keyword("Truncate Orders;"), Some("TRUNCATE"));
+            assert_eq!(destructive_keyword("SELECT dropped FROM backdrop;"), None);
+        }
+
+        #[test]
+        fn transaction_sql_terminates_each_statement() {
+            let statements: Vec<String> = vec!["DELETE FROM Users".to_string(), "SELECT 1;".into()];
+
+            assert_eq!(
+                transaction_sql(&statements),
+                "START TRANSACTION;\nDELETE FROM Users;\nSELECT 1;\nCOMMIT;"
+            );
+        }
+
+        #[test]
+        fn to_csv_quotes_special_fields() {
+            let rows: Vec<Vec<String>> =
+                parse_rows("name\tnote\nBeth\ta, \"b\"\nJosh\tline\\nbreak");
+
+            assert_eq!(
+                to_csv(&rows),
+                "name,note\r\nBeth,\"a, \"\"b\"\"\"\r\nJosh,\"line\nbreak\"\r\n"
+            );
+        }
+    }
+}
+
+pub mod test {
+    //! This module provides an API for running Python unittests.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+    
+    use std::{fmt, process::Output, time::Instant};
+
+    use colored::Colorize;
+
+    use crate::{
+        cmd::{helpers, msg_input, or_exit, run_cmd},
+        config::Config,
+        logging,
+    };
+
+    /// Provides an API for running Python unittests.
+    /// 
+    /// Function requires user to input the directory, venv, test directory and runner, which
+    /// is either `unittest` (the default) or `pytest`.
+    /// 
+    /// The venv is created if it doesn't exist yet, see [`prepare_venv`].
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`expect`](Result<Error>) if any input is unreadable.
+    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// run_unittests(&config);
+    /// ```
+    /// Output
+    /// ```plaintext
+    /// [user@wcli ~]$ test
+    /// Repo path: Directory/repository
+    /// venv name: .venv
+    /// Tests path: app/tests
+    /// Runner [unittest/pytest]:
+    /// 
+    /// 12 passed, 0 failed in 6.2s
+    /// ```
+    /// 
+    pub fn run_unittests(config: &Config) {
+        logging::set_shell("test");
+        let Some(directory) = or_exit(msg_input("Repo path")) else {
+            return;
+        };
+        let Some(venv) = or_exit(msg_input("venv name")) else {
+            return;
+        };
+        let Some(tests) = or_exit(msg_input("Tests path")) else {
+            return;
+        };
+        let Some(runner) = runner_input() else {
+            return;
+        };
+
+        prepare_venv(config, directory.trim(), venv.trim());
+
+        let bash_cmd = format!(
+            "cd {} && source {}/bin/activate && {} {}",
+            directory.as_str().trim(),
+            venv.as_str().trim(),
+            runner.command(),
+            tests.as_str().trim()
+        );
+
+        test_cmd(config, &bash_cmd, &runner);
+    }
+
+    /// Test runners that can be used to run the tests.
+    ///
+    enum Runner {
+        Unittest,
+        Pytest,
+    }
+
+    impl Runner {
+        /// Returns the command that runs the tests in a directory.
+        ///
+        fn command(&self) -> &'static str {
+            match self {
+                Runner::Unittest => "python3 -m unittest discover",
+                Runner::Pytest => "pytest",
+            }
+        }
+
+        /// Returns the counts parsed from the runner's summary line.
+        ///
+        fn summary(&self, output: &str) -> Option<Summary> {
+            match self {
+                Runner::Unittest => Summary::from_unittest(output),
+                Runner::Pytest => Summary::from_pytest(output),
+            }
+        }
+    }
+
+    /// Prompts for the test runner until a valid one is given, defaulting to unittest.
+    ///
+    /// Returns [`None`] at the end of input.
+    ///
+    fn runner_input() -> Option<Runner> {
+        loop {
+            match or_exit(msg_input("Runner [unittest/pytest]"))?.trim() {
+                "" | "unittest" => return Some(Runner::Unittest),
+                "pytest" => return Some(Runner::Pytest),
+                other => println!("{} unknown runner '{other}'", "error:".red()),
+            }
+        }
+    }
+
+    /// Creates the venv with `python3 -m venv` if it doesn't exist and installs the repository's
+    /// `requirements.txt` into it.
+    ///
+    /// When the venv already exists the user is asked whether to reinstall the dependencies,
+    /// defaulting to skip.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`stderr`](std::process::Output) if the venv can't be created.
+    ///
+    fn prepare_venv(config: &Config, directory: &str, venv: &str) {
+        let activate: String = format!("cd {directory} && test -f {venv}/bin/activate");
+
+        if run_cmd(config, &activate).status.success() {
+            let answer: String =
+                or_exit(msg_input("venv found, install dependencies? [y/N]")).unwrap_or_default();
+
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                return;
+            }
+        } else {
+            println!("{} {venv}", "Creating".green());
+
+            let venv_cmd: String = format!("cd {directory} && python3 -m venv {venv}");
+            let output: Output = run_cmd(config, &venv_cmd);
+
+            if !output.status.success() {
+                helpers::print_cmd(&output);
+                return;
+            }
+        }
+
+        install_requirements(config, directory, venv);
+    }
+
+    /// Installs `requirements.txt` into the venv, if the repository has one.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    ///
+    fn install_requirements(config: &Config, directory: &str, venv: &str) {
+        let requirements: String = format!("cd {directory} && test -f requirements.txt");
+
+        if !run_cmd(config, &requirements).status.success() {
+            println!("No requirements.txt found, skipping dependencies");
+            return;
+        }
+
+        println!("{} requirements.txt", "Installing".green());
+
+        let pip_cmd: String =
+            format!("cd {directory} && {venv}/bin/pip install -r requirements.txt");
+        let output: Output = run_cmd(config, &pip_cmd);
+
+        if !output.status.success() {
+            helpers::print_cmd(&output);
+        }
+    }
+
+    /// Runs a Linux command to execute Python unittests in a module.
+    /// 
+    /// Function times the runtime of the tests to get a very acurate runtime measurement off by
+    /// 1ns. Failed test results will appear in the terminal.
+    ///
+    /// # Errors
+    /// 
+    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    /// 
+    fn test_cmd(config: &Config, bash_cmd: &str, runner: &Runner) {
+        let now: Instant = Instant::now();
+
+        let output: std::process::Output = run_cmd(config, bash_cmd);
+
+        let elapsed: String = helpers::format_duration(now.elapsed());
+
+        let stdout: std::borrow::Cow<'_, str> = String::from_utf8_lossy(&output.stdout);
+        let stderr: std::borrow::Cow<'_, str> = String::from_utf8_lossy(&output.stderr);
+
+        match runner.summary(&format!("{stdout}{stderr}")) {
+            Some(summary) => println!("\n{summary} in {elapsed}"),
+            None if output.status.success() => println!("\nAll tests passed in {elapsed}"),
+            None => println!("\nTests failed after {elapsed}"),
+        }
+
+        match runner {
+            _ if output.status.success() => println!("{stdout}"),
+            Runner::Unittest => println!("{stderr}"),
+            Runner::Pytest => println!("{stdout}{stderr}"),
+        }
+    }
+
+    /// Counts of test results parsed from a test runner's output.
+    ///
+    #[derive(Debug, Default, PartialEq)]
+    struct Summary {
+        passed: usize,
+        failed: usize,
+        errors: usize,
+        skipped: usize,
+    }
+
+    impl Summary {
+        /// Parses the trailing `Ran N tests` and `OK`/`FAILED (...)` lines printed by unittest.
+        ///
+        /// Returns `None` if the output doesn't contain a unittest summary.
+        ///
+        /// # Examples
+        /// ```plaintext
+        /// Ran 15 tests in 6.012s
+        ///
+        /// FAILED (failures=2, errors=1)
+        /// ```
+        fn from_unittest(output: &str) -> Option<Summary> {
+            let ran: usize = output
+                .lines()
+                .rev()
+                .find_map(|line: &str| line.strip_prefix("Ran "))?
+                .split_whitespace()
+                .next()?
+                .parse()
+                .ok()?;
+
+            let result: &str = output
+                .lines()
+                .rev()
+                .map(str::trim)
+                .find(|line: &&str| line.starts_with("OK") || line.starts_with("FAILED"))?;
+
+            let mut summary: Summary = Summary::default();
+            let counts: &str = result
+                .split_once('(')
+                .map_or("", |(_, rest)| rest.trim_end_matches(')'));
+
+            for pair in counts.split(", ") {
+                let Some((key, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value: usize = value.parse().ok()?;
+
+                match key {
+                    "failures" => summary.failed = value,
+                    "errors" => summary.errors = value,
+                    "skipped" => summary.skipped = value,
+                    _ => {}
+                }
+            }
+
+            summary.passed = ran.saturating_sub(summary.failed + summary.errors + summary.skipped);
+
+            Some(summary)
+        }
+
+        /// Parses the final `== 12 passed, 2 failed in 6.01s ==` line printed by pytest.
+        ///
+        /// Returns `None` if the output doesn't contain a pytest summary.
+        ///
+        fn from_pytest(output: &str) -> Option<Summary> {
+            let line: &str = output.lines().rev().find_map(|line: &str| {
+                let line: &str = line.trim().trim_matches('=').trim();
+                let (counts, _) = line.rsplit_once(" in ")?;
+                let known: bool = ["passed", "failed", "error", "skipped", "no tests ran"]
+                    .iter()
+                    .any(|word: &&str| counts.contains(word));
+
+                known.then_some(counts)
+            })?;
+
+            let mut summary: Summary = Summary::default();
+
+            for pair in line.split(", ") {
+                let Some((count, key)) = pair.trim().split_once(' ') else {
+                    continue;
+                };
+                let Ok(count) = count.parse::<usize>() else {
+                    continue;
+                };
+
+                match key {
+                    "passed" => summary.passed = count,
+                    "failed" => summary.failed = count,
+                    "error" | "errors" => summary.errors = count,
+                    "skipped" => summary.skipped = count,
+                    _ => {}
+                }
+            }
+
+            Some(summary)
+        }
+    }
+
+    impl fmt::Display for Summary {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let failed: String = format!("{} failed", self.failed);
+
+            write!(f, "{}, ", format!("{} passed", self.passed).green())?;
+            if self.failed > 0 {
+                write!(f, "{}", failed.red())?;
+            } else {
+                write!(f, "{failed}")?;
+            }
+
+            if self.errors > 0 {
+                let noun: &str = if self.errors == 1 { "error" } else { "errors" };
+                write!(f, ", {}", format!("{} {noun}", self.errors).red())?;
+            }
+
+            if self.skipped > 0 {
+                write!(f, ", {}", format!("{} skipped", self.skipped).yellow())?;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::Summary;
+
+        #[test]
+        fn unittest_summaries_are_counted() {
+            let failed: &str = "..F.E.F\n\
+                ----------------------------------------------------------------------\n\
+                Ran 15 tests in 6.012s\n\
+                \n\
+                FAILED (failures=2, errors=1)\n";
+            let summary = Summary {
+                passed: 12,
+                failed: 2,
+                errors: 1,
+                skipped: 0,
+            };
+            assert_eq!(Summary::from_unittest(failed), Some(summary));
+
+            let skipped: &str = "Ran 8 tests in 0.101s\n\nOK (skipped=3)\n";
+            let summary = Summary {
+                passed: 5,
+                skipped: 3,
+                ..Summary::default()
+            };
+            assert_eq!(Summary::from_unittest(skipped), Some(summary));
+        }
+
+        #[test]
+        fn unittest_output_without_a_summary_is_ignored() {
+            let crashed: &str = "ModuleNotFoundError: No module named 'app'\n";
+            assert!(Summary::from_unittest(crashed).is_none());
+
+            let bad_count: &str = "Ran 3 tests in 0.1s\n\nFAILED (failures=x)\n";
+            assert!(Summary::from_unittest(bad_count).is_none());
+        }
+
+        #[test]
+        fn pytest_summaries_are_counted() {
+            let failed: &str = "FAILED tests/test_api.py::test_login - AssertionError\n\
+                ============ 12 passed, 2 failed, 1 error in 6.01s ============\n";
+            let summary = Summary {
+                passed: 12,
+                failed: 2,
+                errors: 1,
+                skipped: 0,
+            };
+            assert_eq!(Summary::from_pytest(failed), Some(summary));
+
+            let skipped: &str = "===== 5 passed, 3 skipped in 0.10s =====\n";
+            let summary = Summary {
+                passed: 5,
+                skipped: 3,
+                ..Summary::default()
+            };
+            assert_eq!(Summary::from_pytest(skipped), Some(summary));
+        }
+
+        #[test]
+        fn pytest_ignores_what_it_cant_read() {
+            let crashed: &str = "ERROR: file or directory not found: tests\n";
+            assert!(Summary::from_pytest(crashed).is_none());
+
+            let bad_count: &str = "===== x passed, 2 failed in 1.00s =====\n";
+            let summary = Summary {
+                failed: 2,
+                ..Summary::default()
+            };
+            assert_eq!(Summary::from_pytest(bad_count), Some(summary));
+        }
+    }
+}
+
+pub mod alias {
+    //! This module provides shortcuts for long commands in the cmd shell, kept in
+    //! `~/.wcli/aliases` between sessions.
+    //!
+    //! ```toml
+    //! deploy = "cd /app && ./deploy.sh"
+    //! ```
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::{collections::BTreeMap, fs, path::PathBuf};
+
+    use colored::Colorize;
+
+    use crate::config;
+
+    /// Returns the path to `~/.wcli/aliases`.
+    ///
+    fn aliases_path() -> Option<PathBuf> {
+        config::wcli_dir().map(|dir: PathBuf| dir.join("aliases"))
+    }
+
+    /// Returns the `[aliases]` from the config file, `defaults`, with the saved aliases added
+    /// and replacing any of the same name.
+    ///
+    /// A file that can't be read or parsed is reported as a warning rather than stopping the
+    /// shell.
+    ///
+    pub fn load(defaults: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        let mut aliases: BTreeMap<String, String> = defaults.clone();
+        let Some(path) = aliases_path().filter(|path: &PathBuf| path.exists()) else {
+            return aliases;
+        };
+
+        let saved: Result<BTreeMap<String, String>, String> = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents: String| toml::from_str(&contents).map_err(|e| e.to_string()));
+
+        match saved {
+            Ok(saved) => aliases.extend(saved),
+            Err(e) => eprintln!(
+                "{} unable to read {}: {e}",
+                "warning:".yellow(),
+                path.display()
+            ),
+        }
+
+        aliases
+    }
+
+    /// Writes the aliases to `~/.wcli/aliases`, leaving out those that are the same as in the
+    /// config file so a later change there isn't hidden.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if the home directory is unknown or the file can't be written.
+    ///
+    fn save(
+        aliases: &BTreeMap<String, String>,
+        defaults: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        let path: PathBuf = aliases_path().ok_or("HOME is not set")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+
+        let saved: BTreeMap<&String, &String> = aliases
+            .iter()
+            .filter(|(name, command)| defaults.get(*name) != Some(command))
+            .collect();
+        let contents: String = toml::to_string(&saved).map_err(|e| e.to_string())?;
+        fs::write(&path, contents).map_err(|e| e.to_string())
+    }
+
+    /// Defines an alias, or lists every alias when there are no arguments, and saves the
+    /// change.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  alias deploy = "cd /app && ./deploy.sh"
+    /// >>>  alias
+    /// deploy = "cd /app && ./deploy.sh"
+    /// ```
+    pub fn alias(
+        aliases: &mut BTreeMap<String, String>,
+        defaults: &BTreeMap<String, String>,
+        args: &str,
+    ) {
+        if args.trim().is_empty() {
+            if aliases.is_empty() {
+                println!("no aliases set");
+            }
+
+            for (name, command) in aliases.iter() {
+                println!("{name} = \"{command}\"");
+            }
+
+            return println!();
+        }
+
+        match parse_alias(args) {
+            Ok((name, command)) => {
+                aliases.insert(name, command);
+                persist(aliases, defaults);
+            }
+            Err(e) => println!("{} {e}\n", "error:".red()),
+        }
+    }
+
+    /// Removes an alias and saves the change.
+    ///
+    /// An alias from the config file can't be removed here, since it would come back in the
+    /// next session, but one replaced with `alias` goes back to its value in the config file.
+    ///
+    pub fn unalias(
+        aliases: &mut BTreeMap<String, String>,
+        defaults: &BTreeMap<String, String>,
+        name: &str,
+    ) {
+        let name: &str = name.trim();
+        if name.is_empty() {
+            return println!("{} usage: unalias <name>\n", "error:".red());
+        }
+
+        if let Some(command) = defaults.get(name) {
+            if aliases.get(name) == Some(command) {
+                return println!(
+                    "{} alias '{name}' is set in ~/.wcli/config.toml, remove it there\n",
+                    "error:".red()
+                );
+            }
+
+            aliases.insert(name.to_string(), command.clone());
+            println!("Alias '{name}' is back to \"{command}\" from ~/.wcli/config.toml");
+            return persist(aliases, defaults);
+        }
+
+        if aliases.remove(name).is_none() {
+            return println!("{} no alias named '{name}'\n", "error:".red());
+        }
+
+        persist(aliases, defaults);
+    }
+
+    /// Saves the aliases, warning if they can't be written.
+    ///
+    fn persist(aliases: &BTreeMap<String, String>, defaults: &BTreeMap<String, String>) {
+        if let Err(e) = save(aliases, defaults) {
+            eprintln!("{} unable to save aliases: {e}", "warning:".yellow());
+        }
+
+        println!();
+    }
+
+    /// Returns the name and command of a `name = "command"` definition, with any quotes around
+    /// the command removed.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if there's no `=`, the name is empty or contains spaces, the
+    ///   command is empty, or the name is `alias` or `unalias`.
+    ///
+    pub fn parse_alias(definition: &str) -> Result<(String, String), String> {
+        let (name, command) = definition
+            .split_once('=')
+            .ok_or("usage: alias <name> = \"<command>\"")?;
+        let name: &str = name.trim();
+        let command: &str = command.trim();
+
+        let unquoted: &str = ['"', '\'']
+            .iter()
+            .find_map(|quote: &char| {
+                command
+                    .strip_prefix(*quote)
+                    .and_then(|command: &str| command.strip_suffix(*quote))
+            })
+            .unwrap_or(command);
+        check(name, unquoted)?;
+
+        Ok((name.to_string(), unquoted.to_string()))
+    }
+
+    /// Checks that an alias can be typed and expanded, for both `alias` and the `[aliases]`
+    /// section of the config file.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if the name is empty or contains spaces, the command is
+    ///   empty, or the name is `alias` or `unalias`.
+    ///
+    pub fn check(name: &str, command: &str) -> Result<(), String> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("invalid alias name '{name}'"));
+        }
+
+        if matches!(name, "alias" | "unalias") {
+            return Err(format!("'{name}' can't be used as an alias"));
+        }
+
+        if command.trim().is_empty() {
+            return Err(format!("alias '{name}' needs a command"));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the line with its first word replaced by the alias of that name, if there is
+    /// one, so arguments typed after an alias are kept.
+    ///
+    /// Only the first word is expanded, and only once, so an alias can't loop.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let aliases = BTreeMap::from([("logs".to_string(), "tail -n 50".to_string())]);
+    /// assert_eq!(expand(&aliases, "logs app.log"), "tail -n 50 app.log");
+    /// ```
+    pub fn expand(aliases: &BTreeMap<String, String>, line: &str) -> String {
+        let line: &str = line.trim_start();
+        let (first, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+        match aliases.get(first) {
+            Some(command) if rest.is_empty() => command.clone(),
+            Some(command) => format!("{command} {rest}"),
+            None => line.to_string(),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::collections::BTreeMap;
+
+        use super::{expand, parse_alias};
+
+        #[test]
+        fn parse_alias_strips_quotes() {
+            assert_eq!(
+                parse_alias("deploy = \"cd /app && ./deploy.sh\""),
+                Ok(("deploy".to_string(), "cd /app && ./deploy.sh".to_string()))
+            );
+            assert_eq!(
+                parse_alias("ll=ls -l"),
+                Ok(("ll".to_string(), "ls -l".to_string()))
+            );
+            assert!(parse_alias("deploy").is_err());
+            assert!(parse_alias("my deploy = ls").is_err());
+            assert!(parse_alias("unalias = ls").is_err());
+            assert!(parse_alias("deploy = \"\"").is_err());
+        }
+
+        #[test]
+        fn expand_replaces_the_first_word_only() {
+            let aliases: BTreeMap<String, String> = BTreeMap::from([
+                ("logs".to_string(), "tail -n 50".to_string()),
+                ("ll".to_string(), "ls -l".to_string()),
+            ]);
+
+            assert_eq!(expand(&aliases, "logs app.log"), "tail -n 50 app.log");
+            assert_eq!(expand(&aliases, "ll"), "ls -l");
+            assert_eq!(expand(&aliases, "echo ll"), "echo ll");
+        }
+    }
+}
+
+pub mod package {
+    //! This module provides the package manager of the EC2, so packages can be installed on
+    //! both Amazon Linux and Debian based images.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::{process::Output, sync::Mutex};
+
+    use crate::{cmd::CommandRunner, config::Config};
+
+    /// The package manager found on the EC2, detected once per session.
+    ///
+    static DETECTED: Mutex<Option<PackageManager>> = Mutex::new(None);
+
+    /// Filesystems an install writes to, the root for the packages and `/var` for the package
+    /// manager's cache.
+    ///
+    const DISK_CHECK: &str = "df -P / /var";
+
+    /// How full a filesystem can be, in percent, before an install asks to go ahead.
+    ///
+    pub const DISK_FULL_PERCENT: u8 = 90;
+
+    /// Package managers WCLI knows how to drive.
+    ///
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum PackageManager {
+        Apt,
+        Dnf,
+        Yum,
+    }
+
+    impl PackageManager {
+        /// Returns the package manager with the given name, as used by `PACKAGE_MANAGER`.
+        ///
+        /// # Errors
+        ///
+        /// - Returns [`Err`](Result) if the name isn't `apt`, `dnf` or `yum`.
+        ///
+        pub fn parse(name: &str) -> Result<PackageManager, String> {
+            match name.trim() {
+                "apt" | "apt-get" => Ok(PackageManager::Apt),
+                "dnf" => Ok(PackageManager::Dnf),
+                "yum" => Ok(PackageManager::Yum),
+                _ => Err(format!(
+                    "PACKAGE_MANAGER must be apt, dnf or yum, got '{name}'"
+                )),
+            }
+        }
+
+        /// Returns the sudo command that installs a package without asking for confirmation.
+        ///
+        /// # Examples
+        /// ```rust
+        /// assert_eq!(PackageManager::Yum.install("git"), "sudo yum install -y git");
+        /// ```
+        pub fn install(&self, package: &str) -> String {
+            self.command("install", package)
+        }
+
+        /// Returns the sudo command that removes a package without asking for confirmation.
+        ///
+        pub fn remove(&self, package: &str) -> String {
+            self.command("remove", package)
+        }
+
+        /// Returns the sudo command that refreshes the package metadata.
+        ///
+        pub fn update(&self) -> String {
+            match self {
+                PackageManager::Apt => self.command("update", ""),
+                PackageManager::Dnf | PackageManager::Yum => self.command("makecache", ""),
+            }
+        }
+
+        /// Returns the sudo command that applies every available update.
+        ///
+        pub fn upgrade(&self) -> String {
+            match self {
+                PackageManager::Apt | PackageManager::Dnf => self.command("upgrade", ""),
+                PackageManager::Yum => self.command("update", ""),
+            }
+        }
+
+        /// Returns a non-interactive sudo command for the package manager.
+        ///
+        /// apt is run with `DEBIAN_FRONTEND=noninteractive` so package scripts don't stop to
+        /// ask questions either.
+        ///
+        fn command(&self, action: &str, args: &str) -> String {
+            let args: String = format!("{action} -y {}", args.trim());
+            let args: &str = args.trim_end();
+
+            match self {
+                PackageManager::Apt => {
+                    format!("sudo env DEBIAN_FRONTEND=noninteractive apt-get {args}")
+                }
+                PackageManager::Dnf => format!("sudo dnf {args}"),
+                PackageManager::Yum => format!("sudo yum {args}"),
+            }
+        }
+    }
+
+    /// Returns the package manager of the EC2, from `PACKAGE_MANAGER` if set, otherwise
+    /// detected with `command -v` the first time it is needed. Nothing is run under `--dry-run`,
+    /// so yum is assumed.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if `PACKAGE_MANAGER` isn't a known package manager.
+    /// - Returns [`Err`](Result) if none of `apt-get`, `dnf` or `yum` is installed.
+    ///
+    pub fn manager(runner: &dyn CommandRunner, config: &Config) -> Result<PackageManager, String> {
+        if let Some(name) = &config.package_manager {
+            return PackageManager::parse(name);
+        }
+
+        let mut detected = DETECTED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(manager) = *detected {
+            return Ok(manager);
+        }
+
+        let detect: &str =
+            "for pm in apt-get dnf yum; do command -v $pm >/dev/null && echo $pm && break; done";
+        let output: Output = runner.run(config, detect);
+        let found: String = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        if config.dry_run {
+            return Ok(PackageManager::parse(&found).unwrap_or(PackageManager::Yum));
+        }
+
+        let manager: PackageManager = PackageManager::parse(&found).map_err(|_| {
+            "no apt-get, dnf or yum found on the EC2, set PACKAGE_MANAGER to choose one".to_string()
+        })?;
+        *detected = Some(manager);
+
+        Ok(manager)
+    }
+
+    /// Returns the mount point and percentage used of the fullest filesystem an install writes
+    /// to, or [`None`] if `df` couldn't be read.
+    ///
+    pub fn fullest_disk(runner: &dyn CommandRunner, config: &Config) -> Option<(String, u8)> {
+        let output: Output = runner.run(config, DISK_CHECK);
+
+        disk_usage(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Returns the mount point and percentage used of the fullest filesystem in `df -P`
+    /// output, the first one listed if several are as full.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let df: &str = "Filesystem 1024-blocks Used Available Capacity Mounted on\n\
+    ///                 /dev/xvda1 8376300 7957485 418815 95% /";
+    /// assert_eq!(disk_usage(df), Some(("/".to_string(), 95)));
+    /// ```
+    fn disk_usage(df: &str) -> Option<(String, u8)> {
+        df.lines()
+            .skip(1)
+            .filter_map(|line: &str| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let percent: u8 = fields.get(4)?.strip_suffix('%')?.parse().ok()?;
+
+                Some((fields.get(5..)?.join(" "), percent))
+            })
+            .reduce(|fullest, disk| if disk.1 > fullest.1 { disk } else { fullest })
+    }
+
+    /// Returns how many packages an upgrade changed, read from apt's `N upgraded` line or the
+    /// `Upgrade N Packages` line of the dnf and yum transaction summary.
+    ///
+    /// Returns [`None`] if the output doesn't say.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let summary: &str = "3 upgraded, 0 newly installed, 0 to remove and 0 not upgraded.";
+    /// assert_eq!(upgraded(summary), Some(3));
+    /// ```
+    pub fn upgraded(output: &str) -> Option<usize> {
+        for line in output.lines() {
+            let line: &str = line.trim();
+
+            if line == "Nothing to do." || line.starts_with("No packages marked for update") {
+                return Some(0);
+            }
+
+            if let Some((count, _)) = line.split_once(" upgraded, ") {
+                return count.trim().parse().ok();
+            }
+
+            let mut words = line.split_whitespace();
+            if let (Some("Upgrade" | "Update"), Some(count), Some(unit)) =
+                (words.next(), words.next(), words.next())
+                && unit.starts_with("Package")
+            {
+                return count.parse().ok();
+            }
+        }
+
+        None
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{PackageManager, disk_usage, fullest_disk, upgraded};
+        use crate::{cmd::tests::MockRunner, config::Config};
+
+        #[test]
+        fn finds_the_fullest_disk() {
+            let df: &str = "Filesystem 1024-blocks Used Available Capacity Mounted on\n\
+                            /dev/xvda1 8376300 7957485 418815 95% /\n\
+                            /dev/xvda1 8376300 7957485 418815 95% /\n\
+                            /dev/xvdb1 20466256 2046625 18419631 10% /var";
+            assert_eq!(disk_usage(df), Some(("/".to_string(), 95)));
+            assert_eq!(disk_usage("df: /var: No such file or directory"), None);
+
+            let runner: MockRunner = MockRunner::new(df);
+            assert_eq!(
+                fullest_disk(&runner, &Config::test()),
+                Some(("/".to_string(), 95))
+            );
+            assert_eq!(runner.calls.into_inner()[0].0, "df -P / /var");
+        }
+
+        #[test]
+        fn builds_commands_for_each_manager() {
+            assert_eq!(
+                PackageManager::Apt.install("git"),
+                "sudo env DEBIAN_FRONTEND=noninteractive apt-get install -y git"
+            );
+            assert_eq!(
+                PackageManager::Apt.remove("git"),
+                "sudo env DEBIAN_FRONTEND=noninteractive apt-get remove -y git"
+            );
+            assert_eq!(
+                PackageManager::Dnf.install("git"),
+                "sudo dnf install -y git"
+            );
+            assert_eq!(PackageManager::Dnf.remove("git"), "sudo dnf remove -y git");
+            assert_eq!(
+                PackageManager::Yum.install("git "),
+                "sudo yum install -y git"
+            );
+            assert_eq!(PackageManager::Yum.remove("git"), "sudo yum remove -y git");
+        }
+
+        #[test]
+        fn parses_manager_names() {
+            assert_eq!(PackageManager::parse("apt-get"), Ok(PackageManager::Apt));
+            assert_eq!(PackageManager::parse("dnf"), Ok(PackageManager::Dnf));
+            assert!(PackageManager::parse("pacman").is_err());
+        }
+
+        #[test]
+        fn builds_update_and_upgrade_commands() {
+            assert_eq!(
+                PackageManager::Apt.upgrade(),
+                "sudo env DEBIAN_FRONTEND=noninteractive apt-get upgrade -y"
+            );
+            assert_eq!(PackageManager::Dnf.update(), "sudo dnf makecache -y");
+            assert_eq!(PackageManager::Yum.upgrade(), "sudo yum update -y");
+        }
+
+        #[test]
+        fn counts_upgraded_packages() {
+            let apt: &str = "Reading package lists...\n\
+                2 upgraded, 0 newly installed, 0 to remove and 1 not upgraded.\n";
+            let yum: &str = "Transaction Summary\n\
+                ================\n\
+                Upgrade  5 Packages\n\n\
+                Total download size: 12 M\n";
+
+            let dnf: &str = "Dependencies resolved.\nNothing to do.\nComplete!\n";
+
+            assert_eq!(upgraded(apt), Some(2));
+            assert_eq!(upgraded(yum), Some(5));
+            assert_eq!(upgraded(dnf), Some(0));
+            assert_eq!(upgraded("Complete!\n"), None);
+        }
+    }
+}
+
+pub mod listing {
+    //! This module provides colored, aligned `ls -l` output in the cmd shell, since the remote
+    //! `ls` doesn't color its output when it's captured.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::process::Output;
+
+    use colored::Colorize;
+
+    /// A line of `ls -l` output, with `name` holding `link -> target` for a symlink.
+    ///
+    struct Entry<'a> {
+        mode: &'a str,
+        links: &'a str,
+        owner: &'a str,
+        group: &'a str,
+        size: &'a str,
+        month: &'a str,
+        day: &'a str,
+        time: &'a str,
+        name: &'a str,
+    }
+
+    /// Returns the output of a long listing like `ls -l` or `ll` with its columns aligned,
+    /// directories in blue, executables in green and symlinks in cyan.
+    ///
+    /// Returns [`None`] for any other command, when colors are off, or if a line isn't in the
+    /// usual `ls -l` format, so the output is shown as it came.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let output: Output = run_cmd(config, "ls -la");
+    /// helpers::print_cmd(&listing::render("ls -la", &output).unwrap_or(output));
+    /// ```
+    pub fn render(command: &str, output: &Output) -> Option<Output> {
+        if !is_listing(command) || !colored::control::SHOULD_COLORIZE.should_colorize() {
+            return None;
+        }
+
+        let stdout: String = format_listing(&String::from_utf8_lossy(&output.stdout))?;
+
+        Some(Output {
+            stdout: stdout.into_bytes(),
+            ..output.clone()
+        })
+    }
+
+    /// Returns true if the command is a long listing whose output is only the listing, so
+    /// nothing piped, chained or redirected.
+    ///
+    fn is_listing(command: &str) -> bool {
+        if command.contains(['|', ';', '&', '>', '<', '`', '$']) {
+            return false;
+        }
+
+        let mut words = command.split_whitespace();
+        match words.next() {
+            Some("ll") => true,
+            Some("ls") => words.any(|word: &str| {
+                word.starts_with('-') && !word.starts_with("--") && word.contains('l')
+            }),
+            _ => false,
+        }
+    }
+
+    /// Returns the listing with its columns aligned and each name colored by type.
+    ///
+    /// The `total` lines, and the headers and blank lines between directories, are kept as
+    /// they are. Returns [`None`] if any other line isn't an entry, or there are no entries.
+    ///
+    fn format_listing(stdout: &str) -> Option<String> {
+        let kept =
+            |line: &str| line.is_empty() || line.starts_with("total ") || line.ends_with(':');
+
+        let mut entries: Vec<Entry> = Vec::new();
+        for line in stdout.lines().filter(|line: &&str| !kept(line)) {
+            entries.push(parse_entry(line)?);
+        }
+        if entries.is_empty() {
+            return None;
+        }
+
+        let mut widths: [usize; 5] = [0; 5];
+        for e in &entries {
+            let fields: [&str; 5] = [e.links, e.owner, e.group, e.size, e.month];
+            for (width, field) in widths.iter_mut().zip(fields) {
+                *width = (*width).max(field.len());
+            }
+        }
+        let [links, owner, group, size, month] = widths;
+
+        let mut entries = entries.into_iter();
+        let mut listing: String = String::new();
+        for line in stdout.lines() {
+            if kept(line) {
+                listing.push_str(line);
+            } else if let Some(e) = entries.next() {
+                listing.push_str(&format!(
+                    "{} {:>links$} {:<owner$} {:<group$} {:>size$} {:<month$} {:>2} {:>5} {}",
+                    e.mode,
+                    e.links,
+                    e.owner,
+                    e.group,
+                    e.size,
+                    e.month,
+                    e.day,
+                    e.time,
+                    paint(e.mode, e.name)
+                ));
+            }
+            listing.push('\n');
+        }
+
+        Some(listing)
+    }
+
+    /// Returns the entry on a line of `ls -l` output.
+    ///
+    /// Returns [`None`] for anything else, like the output of `ls -li` or a device's
+    /// `major, minor` size, so an unexpected line is never misread.
+    ///
+    fn parse_entry(line: &str) -> Option<Entry<'_>> {
+        let mut fields: [&str; 8] = [""; 8];
+        let mut rest: &str = line;
+        for field in &mut fields {
+            let trimmed: &str = rest.trim_start_matches(' ');
+            let (value, after) = trimmed.split_once(' ')?;
+            *field = value;
+            rest = after;
+        }
+
+        let [mode, links, owner, group, size, month, day, time] = fields;
+        let numeric = |value: &str| !value.is_empty() && value.chars().all(|c| c.is_ascii_digit());
+
+        let valid: bool = mode.starts_with(['-', 'd', 'l', 'b', 'c', 'p', 's'])
+            && mode
+                .get(1..10)
+                .is_some_and(|perms: &str| perms.chars().all(|c: char| "rwxsStT-".contains(c)))
+            && numeric(links)
+            && size.starts_with(|c: char| c.is_ascii_digit())
+            && !month.is_empty()
+            && month.chars().all(char::is_alphabetic)
+            && numeric(day)
+            && (time.contains(':') || numeric(time))
+            && !rest.is_empty();
+
+        valid.then_some(Entry {
+            mode,
+            links,
+            owner,
+            group,
+            size,
+            month,
+            day,
+            time,
+            name: rest,
+        })
+    }
+
+    /// Returns the name colored by the type of file in the mode, directories blue, symlinks
+    /// cyan and executables green.
+    ///
+    fn paint(mode: &str, name: &str) -> String {
+        let executable: bool = mode
+            .get(1..10)
+            .is_some_and(|perms: &str| perms.contains(['x', 's', 't']));
+        match mode.chars().next() {
+            Some('d') => name.blue().bold().to_string(),
+            Some('l') => match name.split_once(" -> ") {
+                Some((link, target)) => format!("{} -> {target}", link.cyan()),
+                None => name.cyan().to_string(),
+            },
+            Some('-') if executable => name.green().bold().to_string(),
+            _ => name.to_string(),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{format_listing, is_listing, parse_entry};
+
+        #[test]
+        fn only_long_listings_are_rendered() {
+            assert!(is_listing("ls -la /var/www"));
+            assert!(is_listing("ll"));
+            assert!(!is_listing("ls /var/www"));
+            assert!(!is_listing("ls --color=never"));
+            assert!(!is_listing("ls -l | grep app"));
+        }
+
+        #[test]
+        fn listings_are_aligned() {
+            let ls: &str = "total 12\n\
+                drwxr-xr-x 2 root root 4096 Jan  1 12:00 app\n\
+                -rwxr-xr-x 1 ec2-user ec2-user 120 Mar 14  2024 deploy.sh\n\
+                lrwxrwxrwx 1 root root 9 Jan  1 12:00 current -> app/v2\n";
+
+            let listing: String = format_listing(ls).unwrap_or_default();
+            let lines: Vec<&str> = listing.lines().collect();
+            assert_eq!(lines[0], "total 12");
+            assert!(lines[1].starts_with("drwxr-xr-x 2 root     root     4096 Jan  1 12:00 "));
+            assert!(lines[2].starts_with("-rwxr-xr-x 1 ec2-user ec2-user  120 Mar 14  2024 "));
+            assert!(lines[3].contains("current"));
+
+            let link: &str = "lrwxrwxrwx 1 root root 9 Jan  1 12:00 current -> app/v2";
+            assert_eq!(parse_entry(link).map(|e| e.name), Some("current -> app/v2"));
+        }
+
+        #[test]
+        fn unexpected_lines_are_left_alone() {
+            assert!(format_listing("total 0\n").is_none());
+            assert!(format_listing("ls: cannot access 'x': No such file or directory\n").is_none());
+            assert!(format_listing("1234 -rw-r--r-- 1 root root 0 Jan  1 12:00 inode\n").is_none());
+            assert!(format_listing("crw-rw-rw- 1 root root 1, 3 Jan  1 12:00 null\n").is_none());
+            assert!(format_listing("-rwxr-xr-é 1 root root 0 Jan  1 12:00 accent\n").is_none());
+        }
+    }
+}
+
+pub mod process {
+    //! This module provides the `ps` and `kill` commands of the cmd shell, which list the
+    //! processes on the EC2 as a table and stop one by its PID or name.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::process::Output;
+
+    use colored::Colorize;
+
+    use crate::{
+        cmd::{CommandRunner, helpers, msg_input, or_exit},
+        config::Config,
+    };
+
+    /// Lists every process with its owner, CPU, memory and full command line.
+    ///
+    const PS: &str = "ps aux";
+
+    /// A line of `ps aux` output, with `line` kept whole so a filter can match any of it.
+    ///
+    struct Process<'a> {
+        user: &'a str,
+        pid: &'a str,
+        cpu: &'a str,
+        mem: &'a str,
+        command: &'a str,
+        line: &'a str,
+    }
+
+    /// Returns true if `ps` with these arguments is a filter for the shell's table, rather
+    /// than options like `-ef` or `aux` for the remote `ps`, or a pipeline.
+    ///
+    pub fn is_filter(args: &str) -> bool {
+        let options = |word: &str| word.chars().all(|c: char| "auxwf".contains(c));
+
+        args.is_empty()
+            || (!args.starts_with('-')
+                && !args.contains(['|', ';', '&', '>', '<', '`', '$'])
+                && !args.split_whitespace().next().is_some_and(options))
+    }
+
+    /// Returns true if `kill` with these arguments is a single PID or process name for the
+    /// shell to resolve, rather than signals, job specs or several PIDs for the remote `kill`.
+    ///
+    pub fn is_target(args: &str) -> bool {
+        !args.is_empty()
+            && !args.starts_with(['-', '%'])
+            && !args.contains(char::is_whitespace)
+            && !args.contains(['|', ';', '&', '>', '<', '`', '$'])
+    }
+
+    /// Prints the processes on the EC2 as an aligned table, only those whose `ps aux` line
+    /// contains `filter` if one is given, like piping it through `grep`.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// ps
+    /// ps nginx
+    /// ```
+    pub fn ps(runner: &dyn CommandRunner, config: &Config, filter: &str) {
+        let output: Output = runner.run(config, PS);
+        let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+        let Some(processes) = parse_ps(&stdout) else {
+            helpers::print_cmd(&output);
+            return;
+        };
+
+        let matching: Vec<&Process> = processes
+            .iter()
+            .filter(|p: &&Process| p.line.contains(filter))
+            .collect();
+        if matching.is_empty() {
+            println!("No processes match '{filter}'\n");
+            return;
+        }
+
+        helpers::print_cmd(&Output {
+            stdout: format_table(&matching).into_bytes(),
+            ..output
+        });
+    }
+
+    /// Stops the process with the PID, or every process running the program with the name,
+    /// after showing them and asking. A plain `kill` is tried first and, if the processes
+    /// aren't the ssh user's, it's retried with sudo.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// kill 4312
+    /// kill node
+    /// ```
+    pub fn kill(runner: &dyn CommandRunner, config: &Config, target: &str) {
+        let output: Output = runner.run(config, PS);
+        let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+        let Some(processes) = parse_ps(&stdout) else {
+            helpers::print_cmd(&output);
+            return;
+        };
+
+        let targets: Vec<&Process> = find(&processes, target);
+        if targets.is_empty() {
+            let process: String = match target.parse::<u32>() {
+                Ok(_) => format!("with PID {target}"),
+                Err(_) => format!("named '{target}', search with 'ps {target}'"),
+            };
+            println!("{} no process {process}\n", "error:".red());
+            return;
+        }
+
+        print!("{}", format_table(&targets));
+        let question: String = match targets.len() {
+            1 => "Kill this process? [y/N]".to_string(),
+            count => format!("Kill these {count} processes? [y/N]"),
+        };
+        let answer: String = or_exit(msg_input(&question)).unwrap_or_default();
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!();
+            return;
+        }
+
+        let pids: Vec<&str> = targets.iter().map(|p: &&Process| p.pid).collect();
+        let output: Output = stop(runner, config, &pids.join(" "));
+        if output.status.success() {
+            println!("{} {}\n", "Stopped".green(), pids.join(", "));
+        } else {
+            helpers::print_cmd(&output);
+        }
+    }
+
+    /// Sends SIGTERM to the PIDs, again with sudo if the ssh user isn't allowed to.
+    ///
+    fn stop(runner: &dyn CommandRunner, config: &Config, pids: &str) -> Output {
+        let output: Output = runner.run(config, &format!("kill {pids}"));
+        let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+
+        if output.status.success() || !stderr.contains("Operation not permitted") {
+            return output;
+        }
+        runner.run_sudo(config, &format!("sudo kill {pids}"))
+    }
+
+    /// Returns the processes in `ps aux` output, or [`None`] if it doesn't start with the
+    /// usual header or a line can't be read, so unexpected output is shown as it came.
+    ///
+    fn parse_ps(stdout: &str) -> Option<Vec<Process<'_>>> {
+        let mut lines = stdout.lines();
+        if !lines.next()?.trim_start().starts_with("USER") {
+            return None;
+        }
+
+        lines
+            .filter(|line: &&str| !line.trim().is_empty())
+            .map(parse_process)
+            .collect()
+    }
+
+    /// Returns the process on a line of `ps aux` output, whose eleventh column onwards is the
+    /// command line.
+    ///
+    fn parse_process(line: &str) -> Option<Process<'_>> {
+        let mut fields: [&str; 10] = [""; 10];
+        let mut rest: &str = line;
+        for field in &mut fields {
+            let trimmed: &str = rest.trim_start_matches(' ');
+            let (value, after) = trimmed.split_once(' ')?;
+            *field = value;
+            rest = after;
+        }
+
+        let [user, pid, cpu, mem, ..] = fields;
+        let command: &str = rest.trim_start_matches(' ');
+        let valid: bool = pid.parse::<u32>().is_ok() && !command.is_empty();
+
+        valid.then_some(Process {
+            user,
+            pid,
+            cpu,
+            mem,
+            command,
+            line,
+        })
+    }
+
+    /// Returns the process with the PID if `target` is a number, otherwise every process
+    /// whose program is named `target`, so `nginx` matches `/usr/sbin/nginx` and
+    /// `nginx: worker process` but not `tail nginx.log`.
+    ///
+    fn find<'a>(processes: &'a [Process<'a>], target: &str) -> Vec<&'a Process<'a>> {
+        let program = |p: &Process| -> bool {
+            let first: &str = p.command.split_whitespace().next().unwrap_or("");
+            let first: &str = first.trim_end_matches(':');
+            first.rsplit('/').next() == Some(target)
+        };
+
+        match target.parse::<u32>() {
+            Ok(_) => processes.iter().filter(|p| p.pid == target).collect(),
+            Err(_) => processes.iter().filter(|p| program(p)).collect(),
+        }
+    }
+
+    /// Returns the processes as a table with the PID, user, CPU and memory columns aligned.
+    ///
+    fn format_table(processes: &[&Process]) -> String {
+        let mut widths: [usize; 4] = ["PID".len(), "USER".len(), "%CPU".len(), "%MEM".len()];
+        for p in processes {
+            let fields: [&str; 4] = [p.pid, p.user, p.cpu, p.mem];
+            for (width, field) in widths.iter_mut().zip(fields) {
+                *width = (*width).max(field.len());
+            }
+        }
+        let [pid, user, cpu, mem] = widths;
+
+        let header: String = format!(
+            "{:>pid$} {:<user$} {:>cpu$} {:>mem$} COMMAND",
+            "PID", "USER", "%CPU", "%MEM"
+        );
+        let mut table: String = format!("{}\n", header.bold());
+        for p in processes {
+            table.push_str(&format!(
+                "{:>pid$} {:<user$} {:>cpu$} {:>mem$} {}\n",
+                p.pid, p.user, p.cpu, p.mem, p.command
+            ));
+        }
+
+        table
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{find, format_table, is_filter, is_target, parse_ps, ps};
+        use crate::{cmd::tests::MockRunner, config::Config};
+
+        const PS_AUX: &str = "\
+USER         PID %CPU %MEM    VSZ   RSS TTY      STAT START   TIME COMMAND
+root           1  0.0  0.4 172316 12800 ?        Ss   Jan01   0:09 /usr/lib/systemd/systemd
+root         811  0.0  0.1  55220  1712 ?        Ss   Jan01   0:00 nginx: master process
+nginx        812  0.3  0.2  55880  5624 ?        S    Jan01   4:01 nginx: worker process
+ec2-user    4312 12.5  3.1 921016 98512 pts/0    Sl+  10:02   1:13 node server.js --port 80
+ec2-user    4400  0.0  0.0 222404  1280 pts/1    S+   10:05   0:00 tail -f nginx.log
+";
+
+        #[test]
+        fn ps_output_is_parsed_and_aligned() {
+            let processes = parse_ps(PS_AUX).unwrap_or_default();
+            assert_eq!(processes.len(), 5);
+            assert_eq!(processes[3].command, "node server.js --port 80");
+
+            let table: String = format_table(&processes.iter().collect::<Vec<_>>());
+            let lines: Vec<&str> = table.lines().collect();
+            assert_eq!(lines[1], "   1 root      0.0  0.4 /usr/lib/systemd/systemd");
+            assert_eq!(lines[4], "4312 ec2-user 12.5  3.1 node server.js --port 80");
+
+            assert!(parse_ps("ps: command not found\n").is_none());
+        }
+
+        #[test]
+        fn kill_targets_are_found_by_pid_or_program() {
+            let processes = parse_ps(PS_AUX).unwrap_or_default();
+            let pids = |target: &str| -> Vec<&str> {
+                find(&processes, target).iter().map(|p| p.pid).collect()
+            };
+
+            assert_eq!(pids("4312"), ["4312"]);
+            assert_eq!(pids("nginx"), ["811", "812"]);
+            assert_eq!(pids("systemd"), ["1"]);
+            assert!(pids("43").is_empty());
+            assert!(pids("server.js").is_empty());
+        }
+
+        #[test]
+        fn only_plain_arguments_are_handled_by_the_shell() {
+            assert!(is_filter(""));
+            assert!(is_filter("nginx"));
+            assert!(!is_filter("aux"));
+            assert!(!is_filter("-ef"));
+            assert!(!is_filter("aux | grep nginx"));
+
+            assert!(is_target("4312"));
+            assert!(is_target("node"));
+            assert!(!is_target("-9 4312"));
+            assert!(!is_target("4312 4400"));
+            assert!(!is_target("%1"));
+        }
+
+        #[test]
+        fn ps_runs_ps_aux() {
+            let runner: MockRunner = MockRunner::new(PS_AUX);
+            ps(&runner, &Config::test(), "nginx");
+
+            assert_eq!(runner.calls.borrow()[0], ("ps aux".to_string(), None));
+        }
+    }
+}
+
+pub mod view {
+    //! This module provides the `view` command, which prints a file from the EC2 with syntax
+    //! highlighting.
+    //!
+    //! ## License
+    //!
+    //! Copyright (C) Josh Bassett. All rights reserved.
+    //!
+    //! Apache 2.0
+    //!
+
+    use std::{
+        io::{self, IsTerminal},
+        path::Path,
+        process::Output,
+    };
+
+    use colored::Colorize;
+    use syntect::{
+        easy::HighlightLines,
+        highlighting::{Theme, ThemeSet},
+        parsing::{SyntaxReference, SyntaxSet},
+        util::{LinesWithEndings, as_24_bit_terminal_escaped},
+    };
+
+    use crate::{
+        cmd::{helpers, in_cwd, run_cmd},
+        config::Config,
+    };
+
+    /// Most lines printed without `--all`, so a large log doesn't flood the terminal.
+    ///
+    const VIEW_LINES: usize = 200;
+
+    /// Prints a file from the EC2, highlighted if its extension is a known language.
+    ///
+    /// Only the first [`VIEW_LINES`] lines are printed unless `--all` is given.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>> view app/main.py
+    /// >>> view --all /var/log/messages
+    /// ```
+    pub fn view(config: &Config, cwd: &Option<String>, args: &str) {
+        let (all, path) = parse_view(args);
+        if path.is_empty() {
+            return println!("{} view needs a path\n", "error:".red());
+        }
+
+        let bash_cmd: String = if all {
+            format!("cat {path}")
+        } else {
+            format!("head -n {} {path}", VIEW_LINES + 1)
+        };
+
+        let output: Output = run_cmd(config, &in_cwd(cwd, &bash_cmd));
+        if !output.status.success() {
+            return helpers::print_cmd(&output);
+        }
+
+        let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+        let truncated: bool = !all && stdout.lines().count() > VIEW_LINES;
+        let text: String = if truncated {
+            stdout
+                .lines()
+                .take(VIEW_LINES)
+                .map(|line: &str| format!("{line}\n"))
+                .collect()
+        } else {
+            stdout
+        };
+
+        helpers::page(&highlight(path, &text).unwrap_or(text));
+
+        if truncated {
+            let rest: String = format!("run 'view --all {path}' for the rest");
+            println!(
+                "{} only the first {VIEW_LINES} lines are shown, {rest}\n",
+                "note:".yellow()
+            );
+        }
+    }
+
+    /// Splits the `view` arguments into whether `--all` was given and the path.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(parse_view("--all app.log"), (true, "app.log"));
+    /// ```
+    pub fn parse_view(args: &str) -> (bool, &str) {
+        match args.trim().strip_prefix("--all") {
+            Some(path) if path.is_empty() || path.starts_with(' ') => (true, path.trim()),
+            _ => (false, args.trim()),
+        }
+    }
+
+    /// Returns the text with terminal colors for the language of the path's extension.
+    ///
+    /// Returns [`None`] if stdout isn't a terminal, colors are off or the language is unknown.
+    ///
+    fn highlight(path: &str, text: &str) -> Option<String> {
+        if !io::stdout().is_terminal() || !colored::control::SHOULD_COLORIZE.should_colorize() {
+            return None;
+        }
+
+        let extension: &str = Path::new(path).extension()?.to_str()?;
+        let syntaxes: SyntaxSet = SyntaxSet::load_defaults_newlines();
+        let syntax: &SyntaxReference = syntaxes.find_syntax_by_extension(extension)?;
+        let themes: ThemeSet = ThemeSet::load_defaults();
+        let theme: &Theme = themes.themes.get("base16-ocean.dark")?;
+
+        let mut lines: HighlightLines = HighlightLines::new(syntax, theme);
+        let mut highlighted: String = String::new();
+
+        for line in LinesWithEndings::from(text) {
+            let ranges = lines.highlight_line(line, &syntaxes).ok()?;
+            highlighted.push_str(&as_24_bit_terminal_escaped(&ranges, false));
+        }
+        highlighted.push_str("\x1b[0m");
+
+        Some(highlighted)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::parse_view;
+
+        #[test]
+        fn parse_view_reads_all_flag() {
+            assert_eq!(parse_view("app/main.py"), (false, "app/main.py"));
+            assert_eq!(parse_view(" --all app.log "), (true, "app.log"));
+            assert_eq!(parse_view("--all"), (true, ""));
+            assert_eq!(parse_view("--allowed.txt"), (false, "--allowed.txt"));
+        }
+    }
+}
+
+pub mod status {
+    //! This module provides a health summary of the EC2.
+    //!
+    //! ## License
     //!
     //! Copyright (C) Josh Bassett. All rights reserved.
     //!
     //! Apache 2.0
     //!
 
-    use std::time::SystemTime;
+    use std::process::Output;
 
     use colored::Colorize;
 
-    use crate::cmd::{
-        helpers::{self, clear},
-        input, msg_input, run_cmd,
+    use crate::{
+        cmd::{helpers, run_cmd},
+        config::Config,
     };
 
-    /// Provides an API for running sql commands.
+    /// Separates the output of each command in the bundle.
     ///
-    /// # Errors
+    const SEPARATOR: &str = "--wcli-status--";
+
+    /// Commands in the bundle, each one's stderr is dropped so a missing command shows as n/a.
     ///
-    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
-    /// - Panics [`panic`](std::macros) if mariadb connection is unsuccessful.
-    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    const COMMANDS: [&str; 4] = ["uptime", "df -h /", "free -m", "uname -a"];
+
+    /// Prints the host, uptime, load, disk and memory of the EC2 from a single round trip.
     ///
     /// # Examples
-    /// ```rust
-    /// run_sql(&password);
-    /// ```
-    /// Select emails from table
-    /// ```plaintext
-    /// >>> SELECT email FROM <Table>;
-    /// ```
-    /// Describe a table
     /// ```plaintext
-    /// >>> DESCRIBE <Table>;
-    /// ```
-    /// Delete a table
-    /// ```plaintext
-    /// >>> DROP Table <Table>;
+    /// [user@wcli ~]$ status
+    /// Host      ip-172-31-0-1
+    /// System    Linux 6.1.0-1.amzn2023 x86_64
+    /// Uptime    3 days, 4:05
+    /// Load      0.00, 0.01, 0.05
+    /// Disk      4.1G of 8.0G used (52%), 3.9G free
+    /// Memory    812M of 1949M used, 1137M available
     /// ```
-    pub fn run_sql(password: &str) {
-        test_sql_connection(password);
+    pub fn status(config: &Config) {
+        let bash_cmd: String = COMMANDS
+            .iter()
+            .map(|command: &&str| format!("{command} 2>/dev/null"))
+            .collect::<Vec<String>>()
+            .join(&format!("; echo {SEPARATOR}; "));
 
-        let database: String = msg_input("Database");
-        println!("Run 'help' for commands\n");
+        let output: Output = run_cmd(config, &bash_cmd);
+        if output.status.code() == Some(helpers::SSH_ERROR) {
+            helpers::print_cmd(&output);
+            return;
+        }
 
-        loop {
-            let query: String = input();
+        let stdout: String = String::from_utf8_lossy(&output.stdout).to_string();
+        let mut sections = stdout.split(SEPARATOR).map(str::trim);
+        let uptime: &str = sections.next().unwrap_or("");
+        let df: &str = sections.next().unwrap_or("");
+        let free: &str = sections.next().unwrap_or("");
+        let uname: &str = sections.next().unwrap_or("");
 
-            match query.trim() {
-                "exit" => break,
-                "database" => {
-                    println!("In database: {}", &database);
-                }
-                "change" => run_sql(password),
-                "clear" => clear(),
-                "help" => sql_help(),
-                _ => sql_query(password, database.as_str().trim(), query.as_str().trim()),
-            }
-        }
+        let (host, system) = parse_uname(uname).unzip();
+        let (up, load) = parse_uptime(uptime).unzip();
+
+        field("Host", host);
+        field("System", system);
+        field("Uptime", up);
+        field("Load", load);
+        field("Disk", parse_df(df));
+        field("Memory", parse_free(free));
+        println!();
     }
 
-    /// Runs a sql query on the EC2.
-    ///
-    /// ## Errors
+    /// Prints a labelled field, or `n/a` if it couldn't be found.
     ///
-    /// - Returns [`expect`](Result<Error>) if bash_cmd has bad inputs or an incorrect password.
-    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
-    fn sql_query(password: &str, database: &str, query: &str) {
-        let sql_cmd: String =
-            format!("echo {password} | sudo -S mariadb -u root -p -e \"USE {database}; {query}\"");
-
-        let output = run_cmd(&sql_cmd);
-        helpers::print_cmd(&output);
+    fn field(label: &str, value: Option<String>) {
+        let value: String = value.unwrap_or_else(|| "n/a".dimmed().to_string());
+        println!("{} {value}", format!("{label:<9}").bold());
     }
 
-    /// Tests the connection to mariadb.
-    ///
-    /// # Errors
-    ///
-    /// - Panics [`panic`](std::macros) if mariadb connection is unsuccessful.
+    /// Returns the time since boot and the load averages from `uptime`.
     ///
     /// # Examples
-    ///
     /// ```rust
-    /// let password: &str = "password";
-    /// let res: bool = test_sql_connection(&password);
+    /// let line = " 12:00:01 up 3 days,  4:05,  2 users,  load average: 0.00, 0.01, 0.05";
+    /// assert_eq!(
+    ///     parse_uptime(line),
+    ///     Some(("3 days, 4:05".to_string(), "0.00, 0.01, 0.05".to_string()))
+    /// );
     /// ```
+    fn parse_uptime(output: &str) -> Option<(String, String)> {
+        let (_, rest) = output.split_once(" up ")?;
+        let (up, load) = rest.split_once("load average")?;
+
+        let up: Vec<&str> = up
+            .split(',')
+            .map(str::trim)
+            .filter(|part: &&str| !part.is_empty() && !part.contains("user"))
+            .collect();
+        let load: &str = load.trim_start_matches(['s', ':']).trim();
+
+        Some((up.join(", "), load.to_string()))
+    }
+
+    /// Returns the used, total and free space of the root filesystem from `df -h /`.
     ///
-    fn test_sql_connection(password: &str) {
-        let sql_cmd: String = format!("echo {password} | sudo -S mariadb -u root -p");
+    fn parse_df(output: &str) -> Option<String> {
+        let fields: Vec<&str> = output.lines().nth(1)?.split_whitespace().collect();
+        let [_, size, used, avail, percent, ..] = fields[..] else {
+            return None;
+        };
 
-        let output: std::process::Output = run_cmd(&sql_cmd);
-        let now = SystemTime::now().elapsed().expect("unable to get time");
+        Some(format!("{used} of {size} used ({percent}), {avail} free"))
+    }
 
-        if output.status.success() {
-            println!("{} to mariadb in {:?}\n", "Connected".green(), now)
-        } else {
-            panic!("unable to connect to mariadb")
-        }
+    /// Returns the used, total and available memory from `free -m`.
+    ///
+    fn parse_free(output: &str) -> Option<String> {
+        let line: &str = output
+            .lines()
+            .find(|line: &&str| line.starts_with("Mem:"))?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [_, total, used, ..] = fields[..] else {
+            return None;
+        };
+
+        let available: String = fields
+            .get(6)
+            .map(|available: &&str| format!(", {available}M available"))
+            .unwrap_or_default();
+
+        Some(format!("{used}M of {total}M used{available}"))
     }
 
-    /// Prints out a help message.
+    /// Returns the hostname and the kernel name, release and architecture from `uname -a`.
     ///
-    fn sql_help() {
-        println!("\nCOMMANDS");
-        println!("'any'         -> run a sql query, ensure syntax is correct");
-        println!("'change db'   -> show current database");
-        println!("'clear'       -> clears the terminal");
-        println!("'exit'        -> exit sql");
+    /// The architecture is the field before the operating system at the end of the line.
+    ///
+    fn parse_uname(output: &str) -> Option<(String, String)> {
+        let fields: Vec<&str> = output.split_whitespace().collect();
+        let [kernel, host, release, .., arch, _] = fields[..] else {
+            return None;
+        };
+
+        Some((host.to_string(), format!("{kernel} {release} {arch}")))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{parse_df, parse_free, parse_uname, parse_uptime};
+
+        #[test]
+        fn parses_each_command() {
+            let uptime: &str =
+                " 12:00:01 up 3 days,  4:05,  2 users,  load average: 0.00, 0.01, 0.05";
+            assert_eq!(
+                parse_uptime(uptime),
+                Some(("3 days, 4:05".to_string(), "0.00, 0.01, 0.05".to_string()))
+            );
+
+            let df: &str =
+                "Filesystem Size Used Avail Use% Mounted on\n/dev/xvda1 8.0G 4.1G 3.9G 52% /";
+            assert_eq!(
+                parse_df(df).as_deref(),
+                Some("4.1G of 8.0G used (52%), 3.9G free")
+            );
+
+            let free: &str = "       total used free shared buff/cache available\n\
+                              Mem:    1949  812  200      1         936      1137";
+            assert_eq!(
+                parse_free(free).as_deref(),
+                Some("812M of 1949M used, 1137M available")
+            );
+
+            let uname: &str =
+                "Linux ip-172-31-0-1 6.1.0 #1 SMP Mon Jan 1 x86_64 x86_64 x86_64 GNU/Linux";
+            assert_eq!(
+                parse_uname(uname),
+                Some((
+                    "ip-172-31-0-1".to_string(),
+                    "Linux 6.1.0 x86_64".to_string()
+                ))
+            );
+        }
+
+        #[test]
+        fn missing_commands_are_none() {
+            assert_eq!(parse_uptime(""), None);
+            assert_eq!(parse_df(""), None);
+            assert_eq!(parse_free(""), None);
+            assert_eq!(parse_uname(""), None);
+        }
     }
 }
 
-pub mod test {
-    //! This module provides an API for running Python unittests.
+pub mod helpers {
+    //! This module provides helper functions.
     //!
     //! ## License
     //!
@@ -431,103 +6851,417 @@ pub mod test {
     //!
     //! Apache 2.0
     //!
-    
-    use std::time::Instant;
 
-    use crate::cmd::{msg_input, run_cmd};
+    use std::{
+        borrow::Cow,
+        env, fs,
+        io::{self, IsTerminal, Write},
+        process::{Child, Command, ExitStatus, Output, Stdio},
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            mpsc::{self, RecvTimeoutError},
+        },
+        thread,
+        time::{Duration, Instant},
+    };
 
-    /// Provides an API for running Python unittests.
-    /// 
-    /// Function requires user to input the directory, venv and test directory.
-    /// 
-    /// TODO - automatic venv creation.
+    use colored::Colorize;
+    use indicatif::{ProgressBar, ProgressStyle};
+    use similar::{TextDiff, udiff::UnifiedDiff};
+
+    use crate::config::Config;
+
+    /// Whether output taller than the terminal is paged, turned off by `--no-pager`.
     ///
-    /// # Errors
+    static PAGING: AtomicBool = AtomicBool::new(true);
+
+    /// Bytes of output printed before the rest is cut off, or `0` to print everything. Set
+    /// from `output_limit_kb` in the config file.
     ///
-    /// - Returns [`expect`](Result<Error>) if any input is unreadable.
+    static OUTPUT_LIMIT: AtomicUsize = AtomicUsize::new(1024 * 1024);
+
+    /// Whether output is headed with the local time, toggled by `timestamps on` and
+    /// `timestamps off` in any shell.
+    ///
+    static TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+
+    /// Exit code ssh uses when it fails to connect, rather than the remote command failing.
+    ///
+    pub const SSH_ERROR: i32 = 255;
+
+    /// Exit code WCLI uses when the ssh connection itself failed.
+    ///
+    pub const EXIT_SSH_FAILED: i32 = 69;
+
+    /// Exit code of a command stopped by the timeout, the same as coreutils' `timeout`.
+    ///
+    pub const EXIT_TIMEOUT: i32 = 124;
+
+    /// Prints `stdout` or `stderr` to the terminal.
+    /// 
+    /// When the command failed any `stdout` is printed first, then `stderr` in red after an
+    /// `error:` marker, and a script being run by `load` is stopped.
+    ///
+    /// /// # Errors
+    /// 
     /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
+    /// 
+    pub fn print_cmd(output: &Output) {
+        print_timestamp();
+
+        let stdout: Cow<'_, str> = String::from_utf8_lossy(&output.stdout);
+        let truncated: Option<&str> = truncate(&stdout, OUTPUT_LIMIT.load(Ordering::Relaxed));
+        let shown: &str = truncated.unwrap_or(&stdout);
+
+        if output.status.success() {
+            page(shown);
+            if truncated.is_some() {
+                offer_save(&output.stdout);
+            }
+            return;
+        }
+
+        let stderr: Cow<'_, str> = String::from_utf8_lossy(&output.stderr);
+
+        if !shown.trim().is_empty() {
+            println!("{}", shown.trim_end());
+        }
+
+        if truncated.is_some() {
+            offer_save(&output.stdout);
+        }
+
+        if stderr.trim().is_empty() {
+            println!();
+        } else {
+            println!("{} {}\n", "error:".red().bold(), stderr.trim_end().red());
+        }
+
+        super::fail_script();
+    }
+
+    /// Turns the timestamp above each block of output on or off.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// >>>  timestamps on
+    /// Timestamps on
+    /// ```
+    pub fn set_timestamps(on: bool) {
+        TIMESTAMPS.store(on, Ordering::Relaxed);
+        println!("Timestamps {}\n", if on { "on" } else { "off" });
+    }
+
+    /// Prints the local time as a dim line, if timestamps are on. Every shell's output goes
+    /// through [`print_cmd`], or this for sql tables, so a transcript can be read like a log.
     ///
     /// # Examples
+    /// ```plaintext
+    /// >>>  uptime
+    /// [2025-06-19 11:48:02]
+    ///  11:48:02 up 12 days,  3:04,  1 user,  load average: 0.00, 0.01, 0.05
+    /// ```
+    pub fn print_timestamp() {
+        if TIMESTAMPS.load(Ordering::Relaxed) {
+            let now: String = chrono::offset::Local::now()
+                .format("[%Y-%m-%d %H:%M:%S]")
+                .to_string();
+            println!("{}", now.dimmed());
+        }
+    }
+
+    /// Sets how many bytes of output [`print_cmd`] prints, `0` prints everything.
+    ///
+    pub fn set_output_limit(bytes: usize) {
+        OUTPUT_LIMIT.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the start of the text that fits in `limit` bytes, or [`None`] if all of it fits
+    /// or there is no limit.
     ///
+    /// # Examples
     /// ```rust
-    /// run_unittests();
+    /// assert_eq!(truncate("hello world", 5), Some("hello"));
     /// ```
-    /// Output
+    fn truncate(text: &str, limit: usize) -> Option<&str> {
+        if limit == 0 || text.len() <= limit {
+            return None;
+        }
+
+        let end: usize = (0..=limit)
+            .rev()
+            .find(|end: &usize| text.is_char_boundary(*end))
+            .unwrap_or(0);
+
+        Some(&text[..end])
+    }
+
+    /// Says the output was cut short and offers to write all of it to a local file.
+    ///
+    /// # Examples
     /// ```plaintext
-    /// [user@wcli ~]$ test
-    /// Repo path: Directory/repository
-    /// venv name: .venv
-    /// Tests path: app/tests
-    /// 
-    /// All tests passed in 6s
+    /// … output truncated (48.2 MB total)
+    /// Save the full output to a file? Enter a path or leave blank to skip: dump.bin
+    /// Saved 50541234 bytes to dump.bin
     /// ```
-    /// 
-    pub fn run_unittests() {
-        let directory: String = msg_input("Repo path");
-        let venv: String = msg_input("venv name");
-        let tests: String = msg_input("Tests path");
+    fn offer_save(stdout: &[u8]) {
+        let kb: f64 = stdout.len() as f64 / 1024.0;
+        let total: String = if kb < 1024.0 {
+            format!("{kb:.1} KB")
+        } else {
+            format!("{:.1} MB", kb / 1024.0)
+        };
+        println!("{}", format!("… output truncated ({total} total)").yellow());
 
-        let bash_cmd = format!(
-            "cd {} && source {}/bin/activate && python3 -m unittest discover {}",
-            directory.as_str().trim(),
-            venv.as_str().trim(),
-            tests.as_str().trim()
-        );
+        if !io::stdin().is_terminal() {
+            println!();
+            return;
+        }
+
+        let question: &str = "Save the full output to a file? Enter a path or leave blank to skip";
+        let path: String = super::or_exit(super::msg_input(question)).unwrap_or_default();
+        let path: &str = path.trim();
+        if path.is_empty() {
+            println!();
+            return;
+        }
+
+        match fs::write(path, stdout) {
+            Ok(()) => println!("Saved {} bytes to {path}\n", stdout.len()),
+            Err(e) => println!("{} unable to save to {path}: {e}\n", "error:".red()),
+        }
+    }
+
+    /// Turns paging of long output on or off.
+    ///
+    pub fn set_paging(enabled: bool) {
+        PAGING.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Prints text followed by a blank line, paging it if it is taller than the terminal.
+    ///
+    /// The `PAGER` command is used if set, otherwise a screenful is printed at a time. Nothing
+    /// is paged when paging is off or when stdin or stdout isn't a terminal.
+    ///
+    pub fn page(text: &str) {
+        let interactive: bool = io::stdin().is_terminal() && io::stdout().is_terminal();
+        let height: Option<usize> = terminal_height();
+
+        let fits: bool = height.is_none_or(|height: usize| text.lines().count() < height);
+        if !PAGING.load(Ordering::Relaxed) || !interactive || fits {
+            println!("{text}");
+            return;
+        }
+
+        if let Ok(pager) = env::var("PAGER")
+            && !pager.trim().is_empty()
+            && run_pager(&pager, text).is_ok()
+        {
+            println!();
+            return;
+        }
+
+        more(text, height.unwrap_or(24));
+    }
+
+    /// Writes text to the stdin of the user's `PAGER` and waits for it to exit.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](io::Result) if the pager can't be started.
+    ///
+    fn run_pager(pager: &str, text: &str) -> io::Result<()> {
+        let mut child: std::process::Child = Command::new("sh")
+            .arg("-c")
+            .arg(pager)
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // The pager closing early, e.g. after `q` in less, is not an error.
+            stdin.write_all(text.as_bytes()).ok();
+        }
+
+        child.wait().map(|_| ())
+    }
+
+    /// Prints a screenful of text at a time, waiting for space to show the next page, enter to
+    /// show the next line or `q` to stop.
+    ///
+    fn more(text: &str, height: usize) {
+        let lines: Vec<&str> = text.lines().collect();
+        let page: usize = height.saturating_sub(1).max(1);
+        let mut shown: usize = 0;
+        let mut end: usize = page.min(lines.len());
+
+        loop {
+            for line in &lines[shown..end] {
+                println!("{line}");
+            }
+            shown = end;
+
+            if shown >= lines.len() {
+                break;
+            }
+
+            let remaining: String = format!(
+                "-- more ({} lines left) -- space: page, enter: line, q: quit",
+                lines.len() - shown
+            );
+            print!("{}", remaining.reversed());
+            io::stdout().flush().ok();
+
+            let key: Option<u8> = read_key();
+            print!("\r\x1b[2K");
+
+            match key {
+                Some(b' ') => end = (shown + page).min(lines.len()),
+                Some(b'\n' | b'\r') => end = shown + 1,
+                _ => break,
+            }
+        }
+
+        println!();
+    }
+
+    /// Returns the number of rows in the terminal, if stdout is one.
+    ///
+    fn terminal_height() -> Option<usize> {
+        // SAFETY: winsize is plain data and ioctl only writes into it.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let result: i32 = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+        (result == 0 && size.ws_row > 0).then_some(usize::from(size.ws_row))
+    }
+
+    /// Reads a single key press without waiting for enter or echoing it.
+    ///
+    /// Returns [`None`] if the terminal can't be read.
+    ///
+    fn read_key() -> Option<u8> {
+        with_raw_mode(|| {
+            let mut key: u8 = 0;
+            // SAFETY: reads at most one byte into `key`.
+            let read: isize = unsafe { libc::read(libc::STDIN_FILENO, (&raw mut key).cast(), 1) };
+
+            (read == 1).then_some(key)
+        })
+        .flatten()
+    }
+
+    /// Waits up to `timeout` for a key press or Ctrl-C, returning true if either happened.
+    ///
+    /// The key is consumed so it doesn't show up at the next prompt. If the terminal can't be
+    /// read only Ctrl-C can end the wait early.
+    ///
+    pub fn wait_for_key(timeout: Duration) -> bool {
+        let deadline: Instant = Instant::now() + timeout;
+
+        let pressed: Option<bool> = with_raw_mode(|| {
+            let mut poll: libc::pollfd = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            while Instant::now() < deadline && !super::interrupted() {
+                // SAFETY: polls a single file descriptor for up to 100ms.
+                if unsafe { libc::poll(&mut poll, 1, 100) } > 0 {
+                    let mut key: u8 = 0;
+                    // SAFETY: reads at most one byte into `key`.
+                    unsafe { libc::read(libc::STDIN_FILENO, (&raw mut key).cast(), 1) };
+                    return true;
+                }
+            }
+
+            super::interrupted()
+        });
 
-        test_cmd(&bash_cmd);
+        pressed.unwrap_or_else(|| {
+            while Instant::now() < deadline && !super::interrupted() {
+                thread::sleep(Duration::from_millis(100));
+            }
+            super::interrupted()
+        })
     }
 
-    /// Runs a Linux command to execute Python unittests in a module.
-    /// 
-    /// Function times the runtime of the tests to get a very acurate runtime measurement off by
-    /// 1ns. Failed test results will appear in the terminal.
+    /// Waits up to `timeout` for input on stdin without reading it, returning false if none
+    /// arrived.
     ///
-    /// # Errors
-    /// 
-    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
-    /// 
-    fn test_cmd(bash_cmd: &str) {
-        let now: Instant = Instant::now();
+    /// The key is left for the line editor to read. Ctrl-C counts as input and starts the wait
+    /// again, and input that isn't a terminal is always ready.
+    ///
+    pub fn wait_for_input(timeout: Duration) -> bool {
+        let ready: Option<bool> = with_raw_mode(|| {
+            let mut poll: libc::pollfd = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            let mut deadline: Instant = Instant::now() + timeout;
+            while Instant::now() < deadline {
+                if super::interrupted() {
+                    super::clear_interrupt();
+                    deadline = Instant::now() + timeout;
+                }
 
-        let output: std::process::Output = run_cmd(bash_cmd);
+                // SAFETY: polls a single file descriptor for up to 100ms.
+                if unsafe { libc::poll(&mut poll, 1, 100) } > 0 {
+                    return true;
+                }
+            }
 
-        let elapsed: u64 = now.elapsed().as_secs();
+            false
+        });
 
-        if output.status.success() {
-            println!("\nAll tests passed in {}s", elapsed);
-            println!("{}", String::from_utf8_lossy(&output.stdout));
-        } else {
-            println!("\nTests failed after {}s", elapsed);
-            println!("{}", String::from_utf8_lossy(&output.stderr));
-        }
+        ready.unwrap_or(true)
     }
-}
 
-pub mod helpers {
-    //! This module provides helper functions.
-    //!
-    //! ## License
-    //!
-    //! Copyright (C) Josh Bassett. All rights reserved.
-    //!
-    //! Apache 2.0
-    //!
+    /// Runs `f` with the terminal reading key presses one at a time without echoing them,
+    /// restoring the settings afterwards.
+    ///
+    /// Returns [`None`] if stdin isn't a terminal.
+    ///
+    fn with_raw_mode<T>(f: impl FnOnce() -> T) -> Option<T> {
+        let fd: i32 = libc::STDIN_FILENO;
 
-    use std::process::{Command, Output};
+        // SAFETY: termios is plain data filled in by tcgetattr, and the original settings are
+        // restored before returning.
+        unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut original) != 0 {
+                return None;
+            }
 
-    use indicatif::{ProgressBar, ProgressStyle};
+            let mut raw: libc::termios = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            libc::tcsetattr(fd, libc::TCSANOW, &raw);
 
-    /// Prints `stdout` or `stderr` to the terminal.
-    /// 
-    /// /// # Errors
-    /// 
-    /// - Returns [`stderr`](std::process::Output) if the returned command is an error.
-    /// 
-    pub fn print_cmd(output: &Output) {
-        if output.status.success() {
-            println!("{}", String::from_utf8_lossy(&output.stdout));
-        } else {
-            println!("{}", String::from_utf8_lossy(&output.stderr));
+            let result: T = f();
+            libc::tcsetattr(fd, libc::TCSANOW, &original);
+
+            Some(result)
+        }
+    }
+
+    /// Returns the exit code WCLI should exit with for a remote command.
+    ///
+    /// The remote exit code is passed through, except ssh's own 255 which is mapped to
+    /// [`EXIT_SSH_FAILED`] so callers can tell a connection failure from a failed command. A
+    /// command killed by a signal exits with 1.
+    ///
+    /// # Examples
+    /// ```rust
+    /// process::exit(exit_with(&output));
+    /// ```
+    pub fn exit_with(output: &Output) -> i32 {
+        match output.status.code() {
+            Some(SSH_ERROR) => EXIT_SSH_FAILED,
+            Some(code) => code,
+            None => 1,
         }
     }
 
@@ -546,7 +7280,14 @@ pub mod helpers {
 
     /// Creates a new loading spinner with incrementing bullet points.
     /// 
+    /// The spinner is drawn on stderr, so a hidden bar is returned when stderr isn't a terminal
+    /// to keep piped output clean.
+    ///
     pub fn new_bar() -> ProgressBar {
+        if !io::stderr().is_terminal() {
+            return ProgressBar::hidden();
+        }
+
         let bar: ProgressBar = ProgressBar::new_spinner();
 
         let binding: Vec<String> = (1..10)
@@ -560,16 +7301,98 @@ pub mod helpers {
         bar
     }
 
+    /// Runs `f` while showing the loading spinner.
+    ///
+    /// The spinner is cleared and stdout flushed before returning, so a command that fails
+    /// straight away can't leave a partial spinner line in front of its error.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let output: Output = with_spinner(|| Command::new("ls").output().expect("failed"));
+    /// ```
+    pub fn with_spinner<T>(f: impl FnOnce() -> T) -> T {
+        spin(None, f)
+    }
+
+    /// Runs `f` while showing the loading spinner, adding `hint` to it if `f` is still running
+    /// after `after`.
+    ///
+    /// When stderr isn't a terminal the hint is printed as a warning instead.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let output: Output = with_hang_hint(Duration::from_secs(30), "still running", || run());
+    /// ```
+    pub fn with_hang_hint<T>(after: Duration, hint: &str, f: impl FnOnce() -> T) -> T {
+        spin(Some((after, hint)), f)
+    }
+
+    /// Shows the spinner around `f`, see [`with_spinner`] and [`with_hang_hint`].
+    ///
+    fn spin<T>(hint: Option<(Duration, &str)>, f: impl FnOnce() -> T) -> T {
+        let bar: ProgressBar = new_bar();
+        if !bar.is_hidden() {
+            bar.enable_steady_tick(Duration::from_millis(80));
+        }
+
+        let (done, finished) = mpsc::channel::<()>();
+        let result: T = thread::scope(|scope| {
+            if let Some((after, hint)) = hint {
+                let bar: ProgressBar = bar.clone();
+                scope.spawn(move || {
+                    if finished.recv_timeout(after) != Err(RecvTimeoutError::Timeout) {
+                        return;
+                    }
+
+                    if bar.is_hidden() {
+                        eprintln!("{} {hint}", "warning:".yellow());
+                    } else {
+                        bar.set_message(hint.yellow().to_string());
+                    }
+                });
+            }
+
+            let result: T = f();
+            done.send(()).ok();
+            result
+        });
+
+        bar.finish_and_clear();
+        io::stdout().flush().ok();
+        io::stderr().flush().ok();
+
+        result
+    }
+
     /// Prints help message.
     /// 
     pub fn help() {
         println!("\nCOMMANDS");
-        println!("'cmd'     -> run a Linux command");
-        println!("'test'    -> run Python unit tests");
-        println!("'git'     -> run a git command in a repository");
-        println!("'sql'     -> run a sql query, run 'help' for assistance");
-        println!("'clear'   -> clear the terminal");
-        println!("'exit'    -> exit wcli");
+        println!("'cmd'       -> run a Linux command");
+        println!("'test'      -> run Python unit tests");
+        println!("'git'       -> run a git command in a repository");
+        println!("'sql'       -> run a sql query, run 'help' for assistance");
+        println!("'status'    -> show the uptime, load, disk and memory of the EC2");
+        println!("'ping'      -> time a round trip to the EC2");
+        println!("'reconnect' -> test the connection again, reopening the shared ssh session");
+        println!("'load'      -> run a local file of commands, '--continue' keeps going on errors");
+        println!("'local'     -> run a command on this machine instead of the EC2");
+        println!("'<name>'    -> run a command from [commands] in ~/.wcli/config.toml");
+        println!("'shell'     -> open a full ssh shell on the EC2, 'exit' comes back to wcli");
+        println!("'profile'   -> list the hosts, 'profile <name>' switches to another one");
+        println!("'history'   -> list past commands, '!N' reruns one and '!!' the last");
+        println!("'cheatsheet' -> show example commands for files, services, git and sql");
+        println!("'clear'     -> clear the terminal");
+        println!("'exit'      -> exit wcli");
+        println!("\nPROMPT");
+        println!("Set 'prompt' in ~/.wcli/config.toml or the PROMPT environment variable, e.g.");
+        println!("prompt = \"{{user}}@{{host}}:{{cwd}} ({{shell}})$\"");
+        println!("'{{user}}'    -> your local user name");
+        println!("'{{host}}'    -> the host from ~/.wcli/config.toml");
+        println!("'{{cwd}}'     -> the cmd directory, git repository or sql database");
+        println!("'{{branch}}'  -> the checked out branch in the git shell");
+        println!("'{{shell}}'   -> the current shell, e.g. wcli, cmd or git");
+        println!("'{{status}}'  -> the connection status in the main prompt");
     }
 
     /// Retrieves the system username with Linux `whoami` command.
@@ -592,12 +7415,602 @@ pub mod helpers {
         }
     }
 
+    /// Prints example commands by category, for new users who don't know what to type. Each
+    /// starts with the shell to type it in.
+    ///
+    pub fn cheatsheet() {
+        println!("\n{}", "Files".cyan().bold());
+        println!("  cmd > ls -lh /var/log                 list a directory with readable sizes");
+        println!("  cmd > view app/config.py              print a file with syntax highlighting");
+        println!("  cmd > follow /var/log/messages        print lines as they're added to a log");
+        println!("  cmd > du -sh * | sort -h              find what is using the disk");
+        println!("  cmd > push / pull                     copy a file to or from the EC2");
+        println!("\n{}", "Services".cyan().bold());
+        println!("  cmd > sudo systemctl status nginx     check whether a service is running");
+        println!("  cmd > sudo systemctl restart nginx    restart a service");
+        println!("  cmd > sudo journalctl -u nginx -n 50  show a service's recent logs");
+        println!("  cmd > -t htop                         see what is using the CPU and memory");
+        println!("  cmd > install docker                  install a package");
+        println!("\n{}", "Git".cyan().bold());
+        println!("  git > status                          see what has changed in the repository");
+        println!("  git > pull                            fetch and merge the latest commits");
+        println!("  git > log --oneline -10               list the last ten commits");
+        println!("  git > change                          switch to another repository");
+        println!("\n{}", "SQL".cyan().bold());
+        println!("  sql > tables                          list the tables in the database");
+        println!("  sql > describe Users                  show the columns of a table");
+        println!("  sql > SELECT * FROM Users LIMIT 10;   show the first rows of a table");
+        println!("  sql > export users.csv                save the last result to a CSV file");
+        println!("\n{}", "WCLI".cyan().bold());
+        println!("  status                                show the uptime, load, disk and memory");
+        println!("  profile staging                       switch to another host");
+        println!("  load deploy.wcli                      run a file of commands");
+        println!("  shell                                 open a full ssh shell on the EC2");
+        println!();
+    }
+
+    /// Returns a duration the way a person would write it: `850µs` or `191ms` below a second,
+    /// `1.5s` below a minute, then `1m 5s` and `2h 3m 10s`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+    /// ```
+    pub fn format_duration(duration: Duration) -> String {
+        let seconds: u64 = duration.as_secs();
+
+        match seconds {
+            0 if duration.as_micros() < 1 => format!("{}ns", duration.as_nanos()),
+            0 if duration.as_millis() < 1 => format!("{}µs", duration.as_micros()),
+            0 => format!("{}ms", duration.as_millis()),
+            1..60 => format!("{:.1}s", duration.as_secs_f64()),
+            60..3600 => format!("{}m {}s", seconds / 60, seconds % 60),
+            _ => format!(
+                "{}h {}m {}s",
+                seconds / 3600,
+                seconds % 3600 / 60,
+                seconds % 60
+            ),
+        }
+    }
+
     /// Capitalises user's name.
-    /// 
+    ///
+    /// An empty name is returned unchanged, and non-ASCII first letters are uppercased too.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(capitalise("élodie"), "Élodie");
+    /// ```
     pub fn capitalise(user: &str) -> String {
-        let mut chars: Vec<char> = user.chars().collect();
-        chars[0].make_ascii_uppercase();
+        let mut chars: std::str::Chars<'_> = user.chars();
+
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    }
+
+    /// Returns the text with every occurrence of the password replaced by `****`.
+    ///
+    /// Anything that echoes a command or its errors back to the user, or into the audit log,
+    /// goes through this first.
+    ///
+    /// # Examples
+    /// ```rust
+    /// // sudo didn't need the password, so mariadb read it from stdin as sql
+    /// let stderr: &str = "ERROR 1064 (42000) at line 1: ... near 'hunter2' at line 1";
+    /// assert!(redact(stderr, "hunter2").ends_with("near '****' at line 1"));
+    /// ```
+    pub fn redact(text: &str, password: &str) -> String {
+        if password.is_empty() {
+            text.to_string()
+        } else {
+            text.replace(password, "****")
+        }
+    }
+
+    /// Lines of unchanged output shown around each change in [`unified_diff`].
+    ///
+    const DIFF_CONTEXT: usize = 3;
+
+    /// Returns the hunks of a unified diff from `old` to `new`, each starting with an `@@` line,
+    /// or nothing if they are identical.
+    ///
+    /// The lines are compared with `similar`, whose memory use grows with the size of the
+    /// outputs rather than their product, so long outputs are safe to compare.
+    ///
+    /// # Examples
+    /// ```rust
+    /// assert_eq!(unified_diff("a\nb\n", "a\nc\n"), ["@@ -1,2 +1,2 @@", " a", "-b", "+c"]);
+    /// ```
+    pub fn unified_diff(old: &str, new: &str) -> Vec<String> {
+        let diff: TextDiff<'_, '_, str> = TextDiff::from_lines(old, new);
+        let mut unified: UnifiedDiff<'_, '_, '_, str> = diff.unified_diff();
+        let mut lines: Vec<String> = Vec::new();
+
+        for hunk in unified.context_radius(DIFF_CONTEXT).iter_hunks() {
+            lines.push(hunk.header().to_string());
+            lines.extend(hunk.iter_changes().map(|change| {
+                let line: &str = change.value().trim_end_matches(['\n', '\r']);
+                format!("{}{line}", change.tag())
+            }));
+        }
+
+        lines
+    }
+
+    /// Returns the text with the sudo password and `DB_PASS` redacted, see [`redact`].
+    ///
+    pub fn redact_secrets(text: &str, config: &Config) -> String {
+        let text: String = redact(text, &config.pass);
+
+        match &config.db_pass {
+            Some(db_pass) => redact(&text, db_pass),
+            None => text,
+        }
+    }
+
+    /// Prints a command to stderr before it is spawned, with the password redacted, if
+    /// `--verbose` is set. Returns when it started so [`trace_exit`] can time it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let start: Instant = trace_command(config, &ssh_cmd);
+    /// let child: Child = Command::new("bash").arg("-c").arg(&ssh_cmd).spawn()?;
+    /// trace_spawn(config, &child, start);
+    /// ```
+    pub fn trace_command(config: &Config, command: &str) -> Instant {
+        if config.verbose > 0 {
+            let command: String = redact(command, &config.pass);
+            eprintln!("{} {}", "+".dimmed(), command.dimmed());
+        }
+
+        Instant::now()
+    }
+
+    /// Prints the pid of a spawned command and how long it took to start, if `--verbose` is
+    /// set.
+    ///
+    pub fn trace_spawn(config: &Config, child: &Child, start: Instant) {
+        if config.verbose > 0 {
+            let spawned: String = format!(
+                "  spawned pid {} in {}",
+                child.id(),
+                format_duration(start.elapsed())
+            );
+            eprintln!("{}", spawned.dimmed());
+        }
+    }
+
+    /// Prints how long a command took if `--verbose` is set, and with `-vv` its exit code and,
+    /// when it was captured, the size of its output.
+    ///
+    pub fn trace_exit(
+        config: &Config,
+        start: Instant,
+        status: &ExitStatus,
+        output: Option<&Output>,
+    ) {
+        if config.verbose > 0 {
+            let finished: String = format!("  finished in {}", format_duration(start.elapsed()));
+            eprintln!("{}", finished.dimmed());
+        }
+
+        if config.verbose > 1 {
+            let code: String = match status.code() {
+                Some(code) => format!("exit {code}"),
+                None => "killed by a signal".to_string(),
+            };
+            let detail: String = match output {
+                Some(output) => format!(
+                    "  {code}, stdout {} bytes, stderr {} bytes",
+                    output.stdout.len(),
+                    output.stderr.len()
+                ),
+                None => format!("  {code}"),
+            };
+
+            eprintln!("{}", detail.dimmed());
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::time::Duration;
+
+        use super::{capitalise, format_duration, redact, truncate, unified_diff};
+
+        #[test]
+        fn redact_hides_password_echoed_in_stderr() {
+            let password: &str = "p @$x'y";
+            let stderr: String = format!(
+                "ERROR 1064 (42000) at line 1: You have an error in your SQL syntax; check the \
+                 manual for the right syntax to use near '{password}' at line 1"
+            );
+
+            let redacted: String = redact(&stderr, password);
+
+            assert!(!redacted.contains(password));
+            assert!(redacted.ends_with("syntax to use near '****' at line 1"));
+        }
+
+        #[test]
+        fn redact_leaves_text_alone_without_password() {
+            assert_eq!(redact("sudo -S ls", ""), "sudo -S ls");
+        }
+
+        #[test]
+        fn unified_diff_shows_changed_lines_with_context() {
+            assert!(unified_diff("a\nb\n", "a\nb\n").is_empty());
+
+            let old: &str = "1\n2\n3\n4\n5\n6\n7\n8\n";
+            let new: &str = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+            let lines: Vec<String> = unified_diff(old, new);
+            assert_eq!(lines[0], "@@ -2,7 +2,8 @@");
+            assert_eq!(
+                lines[1..],
+                [" 2", " 3", " 4", "-5", "+five", " 6", " 7", " 8", "+9"]
+            );
+            assert_eq!(unified_diff("", "a\n"), ["@@ -0,0 +1 @@", "+a"]);
+        }
+
+        #[test]
+        fn unified_diff_handles_long_outputs() {
+            let old: String = (0..20_000).map(|i| format!("{i}\n")).collect();
+            let new: String = format!("changed\n{}", &old[2..]);
+
+            let lines: Vec<String> = unified_diff(&old, &new);
+            assert_eq!(lines[..3], ["@@ -1,4 +1,4 @@", "-0", "+changed"]);
+        }
+
+        #[test]
+        fn format_duration_picks_a_unit() {
+            assert_eq!(format_duration(Duration::from_micros(999)), "999µs");
+            assert_eq!(format_duration(Duration::from_micros(1000)), "1ms");
+            assert_eq!(format_duration(Duration::from_millis(999)), "999ms");
+            assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+            assert_eq!(format_duration(Duration::from_secs(65)), "1m 5s");
+            assert_eq!(format_duration(Duration::from_secs(3723)), "1h 2m 3s");
+        }
+
+        #[test]
+        fn truncate_cuts_on_a_char_boundary() {
+            assert_eq!(truncate("héllo", 2), Some("h"));
+            assert_eq!(truncate("hello", 5), None);
+            assert_eq!(truncate("hello", 0), None);
+        }
+
+        #[test]
+        fn capitalise_handles_short_and_unicode_names() {
+            assert_eq!(capitalise(""), "");
+            assert_eq!(capitalise("j"), "J");
+            assert_eq!(capitalise("josh"), "Josh");
+            assert_eq!(capitalise("élodie"), "Élodie");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        collections::BTreeMap,
+        os::unix::process::ExitStatusExt,
+        process::{ExitStatus, Output},
+        time::Duration,
+    };
+
+    use super::{
+        CommandRunner, PromptVars, SaveTo, cd_target, change_dir, complete, connect, custom,
+        dangerous_pattern, expand_history, expand_template, in_cwd, install_package, is_builtin,
+        mark_timed_out, needs_tty, package::PackageManager, parse_retry, parse_save, parse_var,
+        parse_watch, render_prompt, shell_commands, sudo_request, with_env, with_remote_timeout,
+    };
+    use crate::config::Config;
+
+    /// Records each command it is given, with the stdin written to it, and answers every one
+    /// with a successful exit and `stdout`.
+    ///
+    pub(super) struct MockRunner {
+        stdout: &'static str,
+        pub calls: RefCell<Vec<(String, Option<String>)>>,
+    }
+
+    impl MockRunner {
+        pub fn new(stdout: &'static str) -> MockRunner {
+            MockRunner {
+                stdout,
+                calls: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn record(&self, bash_cmd: &str, input: Option<&str>) -> Output {
+            let input: Option<String> = input.map(str::to_string);
+            self.calls.borrow_mut().push((bash_cmd.to_string(), input));
+
+            Output {
+                status: ExitStatus::from_raw(0),
+                stdout: self.stdout.as_bytes().to_vec(),
+                stderr: Vec::new(),
+            }
+        }
+    }
+
+    impl CommandRunner for MockRunner {
+        fn run(&self, _config: &Config, bash_cmd: &str) -> Output {
+            self.record(bash_cmd, None)
+        }
+
+        fn run_sudo(&self, config: &Config, sudo_cmd: &str) -> Output {
+            self.record(sudo_cmd, Some(&format!("{}\n", config.pass)))
+        }
+
+        fn run_input(&self, _config: &Config, bash_cmd: &str, input: &str) -> Output {
+            self.record(bash_cmd, Some(input))
+        }
+    }
+
+    #[test]
+    fn expand_template_fills_in_arguments() {
+        let template: &str = "tail -n {2} /var/log/{1} | awk '{print $1}'";
+
+        assert_eq!(
+            expand_template(template, &["syslog", "20"]),
+            Ok("tail -n 20 /var/log/syslog | awk '{print $1}'".to_string())
+        );
+        assert!(expand_template(template, &["syslog"]).is_err());
+        assert!(expand_template(template, &["syslog", "20", "x"]).is_err());
+        assert!(expand_template("echo {0}", &["x"]).is_err());
+        assert!(expand_template("sudo systemctl restart nginx", &["now"]).is_err());
+    }
 
-        String::from_iter(chars)
+    #[test]
+    fn custom_commands_run_sudo_with_the_password() {
+        let runner: MockRunner = MockRunner::new("");
+        let template: &str = "sudo systemctl restart {1}";
+        custom(&runner, &Config::test(), "restart", template, "nginx");
+        custom(&runner, &Config::test(), "restart", template, "");
+
+        let calls: Vec<(String, Option<String>)> = runner.calls.into_inner();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0, "sudo systemctl restart nginx");
+        assert_eq!(calls[0].1.as_deref(), Some("hunter2\n"));
+    }
+
+    #[test]
+    fn sudo_passwords_are_only_sent_on_stdin() {
+        let mut config: Config = Config::test();
+        config.pass = "p @$x'y".to_string();
+
+        let (bash_cmd, input) = sudo_request(&config, "sudo yum update -y");
+        assert_eq!(bash_cmd, "sudo -S yum update -y");
+        assert!(!connect::ssh(&config, &bash_cmd).contains(&config.pass));
+        assert_eq!(input, "p @$x'y\n");
+    }
+
+    #[test]
+    fn remote_timeouts_stop_the_command_on_the_ec2() {
+        let bash_cmd: String = with_remote_timeout("cd /app && make build", 1800);
+
+        assert!(bash_cmd.starts_with("(sleep 1800 >/dev/null 2>&1; "));
+        assert!(bash_cmd.contains("echo \"timed out after 1800s\" >&2; kill -TERM 0) &"));
+        assert!(bash_cmd.contains("( cd /app && make build\n); wcli_status=$?;"));
+        assert!(bash_cmd.ends_with("exit $wcli_status"));
+        assert!(!bash_cmd.contains('\''));
+    }
+
+    #[test]
+    fn timed_out_commands_keep_their_stderr() {
+        let diagnostic: &str = "error[E0308]: mismatched types";
+        let mut stopped: Output = Output {
+            status: ExitStatus::from_raw(143 << 8),
+            stdout: Vec::new(),
+            stderr: format!("{diagnostic}\ntimed out after 1800s\n").into_bytes(),
+        };
+        mark_timed_out(&mut stopped, false, 1800);
+
+        let stderr: String = String::from_utf8_lossy(&stopped.stderr).to_string();
+        assert_eq!(stopped.status.code(), Some(124));
+        assert_eq!(stderr, format!("{diagnostic}\ntimed out after 1800s\n"));
+
+        let mut killed: Output = Output {
+            status: ExitStatus::from_raw(libc::SIGTERM),
+            stdout: Vec::new(),
+            stderr: diagnostic.as_bytes().to_vec(),
+        };
+        mark_timed_out(&mut killed, true, 1800);
+
+        let stderr: String = String::from_utf8_lossy(&killed.stderr).to_string();
+        assert_eq!(killed.status.code(), Some(124));
+        assert_eq!(stderr, format!("{diagnostic}\ntimed out after 1800s\n"));
+
+        let mut finished: Output = Output {
+            status: ExitStatus::from_raw(0),
+            stdout: Vec::new(),
+            stderr: b"timed out after 1800s\n".to_vec(),
+        };
+        mark_timed_out(&mut finished, false, 1800);
+        assert!(finished.status.success());
+    }
+
+    #[test]
+    fn working_directories_are_quoted() {
+        let cwd: Option<String> = Some("/srv/my \"app\" $HOME `id`".to_string());
+        assert_eq!(
+            in_cwd(&cwd, "ls"),
+            "cd \"/srv/my \\\"app\\\" \\$HOME \\`id\\`\" && ls"
+        );
+
+        assert_eq!(cd_target("my dir"), "\"my dir\"");
+        assert_eq!(cd_target("\"my dir\""), "\"my dir\"");
+        assert_eq!(cd_target("~/logs/$(date)"), "~/\"logs/\\$(date)\"");
+    }
+
+    #[test]
+    fn dry_run_cd_keeps_the_working_directory() {
+        let mut config: Config = Config::test();
+        config.dry_run = true;
+
+        let cwd: Option<String> = Some("/var/log".to_string());
+        assert_eq!(change_dir(&config, cwd.clone(), "/var/www"), cwd);
+        assert_eq!(change_dir(&config, None, "/var/www"), None);
+    }
+
+    #[test]
+    fn builtins_are_not_confirmed() {
+        assert!(is_builtin("alias", "clean = \"rm -rf build\""));
+        assert!(is_builtin("unalias", "clean"));
+        assert!(is_builtin("history", ""));
+        assert!(is_builtin("timings", "on"));
+        assert!(is_builtin("timeout", "30"));
+        assert!(is_builtin("local", "rm -rf build"));
+
+        assert!(!is_builtin("rm", "-rf build"));
+        assert!(!is_builtin("history", "| xargs rm -rf"));
+        assert!(!is_builtin("timeout", "5 rm -rf /tmp/cache"));
+    }
+
+    #[test]
+    fn install_runs_the_package_manager_with_sudo() {
+        let runner: MockRunner = MockRunner::new("");
+        install_package(&runner, &Config::test(), PackageManager::Dnf, "nginx");
+        install_package(&runner, &Config::test(), PackageManager::Apt, "nginx");
+
+        let calls: Vec<(String, Option<String>)> = runner.calls.into_inner();
+        assert_eq!(calls[0].0, "sudo dnf install -y nginx");
+        assert_eq!(calls[0].1.as_deref(), Some("hunter2\n"));
+        assert_eq!(
+            calls[1].0,
+            "sudo env DEBIAN_FRONTEND=noninteractive apt-get install -y nginx"
+        );
+    }
+
+    #[test]
+    fn render_prompt_fills_placeholders() {
+        let vars: PromptVars = PromptVars {
+            user: "josh",
+            cwd: "/var/www",
+            shell: "cmd",
+            ..PromptVars::default()
+        };
+
+        let template: &str = "{user}@{host}:{cwd} ({shell}){branch}$ {other}";
+        assert_eq!(
+            render_prompt(template, "staging", &vars),
+            "josh@staging:/var/www (cmd)$ {other}"
+        );
+    }
+
+    #[test]
+    fn complete_offers_commands_for_the_shell() {
+        let git: Vec<String> = complete(shell_commands("git"), "c");
+
+        assert_eq!(git, vec!["change", "clone", "checkout", "clear"]);
+        assert_eq!(
+            complete(shell_commands("wcli"), "s"),
+            vec!["sql", "status", "shell"]
+        );
+        assert!(complete(shell_commands("sql"), "install").is_empty());
+    }
+
+    #[test]
+    fn expand_history_recalls_by_index() {
+        let entries: [&str; 2] = ["cd /var/log", "ls -l"];
+
+        assert_eq!(
+            expand_history(&entries, "!!\n"),
+            Ok(Some("ls -l\n".to_string()))
+        );
+        assert_eq!(
+            expand_history(&entries, "!1 && pwd\n"),
+            Ok(Some("cd /var/log && pwd\n".to_string()))
+        );
+        assert_eq!(expand_history(&entries, "echo hi!\n"), Ok(None));
+        assert_eq!(expand_history(&entries, "! grep x\n"), Ok(None));
+        assert!(expand_history(&entries, "!3\n").is_err());
+        assert!(expand_history(&entries, "!0\n").is_err());
+        assert!(expand_history(&[], "!!\n").is_err());
+    }
+
+    #[test]
+    fn needs_tty_spots_terminal_errors() {
+        assert!(needs_tty("ls | less", true, b"less: not a terminal"));
+        assert!(needs_tty("sudo htop", false, b""));
+        assert!(!needs_tty("top -bn1", true, b""));
+        assert!(!needs_tty("grep top log.txt", false, b""));
+    }
+
+    #[test]
+    fn parse_retry_caps_the_attempts() {
+        assert_eq!(parse_retry("3 make test"), Ok((3, "make test")));
+        assert!(parse_retry("0 make test").is_err());
+        assert!(parse_retry("11 make test").is_err());
+        assert!(parse_retry("3").is_err());
+    }
+
+    #[test]
+    fn parse_save_only_takes_the_save_sentinel() {
+        let save = |path: &'static str, append: bool| Some(SaveTo { path, append });
+
+        assert_eq!(
+            parse_save("ps aux | grep nginx | save ps.txt"),
+            ("ps aux | grep nginx", save("ps.txt", false))
+        );
+        assert_eq!(
+            parse_save("tail -n 50 app.log |>> logs/app.log"),
+            ("tail -n 50 app.log", save("logs/app.log", true))
+        );
+        assert_eq!(parse_save("ls | grep save"), ("ls | grep save", None));
+        assert_eq!(parse_save("ls | save"), ("ls | save", None));
+        assert_eq!(parse_save("false || save x"), ("false || save x", None));
+    }
+
+    #[test]
+    fn dangerous_pattern_ignores_case_and_spacing() {
+        let patterns: Vec<String> = vec!["rm -rf".to_string(), "drop table".to_string()];
+
+        assert_eq!(
+            dangerous_pattern(&patterns, "sudo rm  -rf /tmp/x"),
+            Some("rm -rf")
+        );
+        assert_eq!(
+            dangerous_pattern(&patterns, "DROP TABLE Users;"),
+            Some("drop table")
+        );
+        assert_eq!(dangerous_pattern(&patterns, "rm -r build"), None);
+        assert_eq!(dangerous_pattern(&[], "rm -rf /"), None);
+    }
+
+    #[test]
+    fn complete_stops_after_the_first_word() {
+        assert!(complete(shell_commands("cmd"), "cd d").is_empty());
+    }
+
+    #[test]
+    fn with_env_quotes_values() {
+        let (key, value) = parse_var("GREETING=\"it's $HOME\"").unwrap();
+        let vars: BTreeMap<String, String> = BTreeMap::from([(key, value)]);
+
+        assert_eq!(
+            with_env(&vars, "echo $GREETING"),
+            "export GREETING=\"it'\\''s \\$HOME\"; echo $GREETING"
+        );
+        assert_eq!(with_env(&BTreeMap::new(), "ls"), "ls");
+    }
+
+    #[test]
+    fn parse_watch_reads_interval_and_command() {
+        assert_eq!(
+            parse_watch("2 docker ps"),
+            Ok((Duration::from_secs(2), "docker ps"))
+        );
+        assert_eq!(
+            parse_watch("0.5 uptime"),
+            Ok((Duration::from_millis(500), "uptime"))
+        );
+        assert!(parse_watch("docker ps").is_err());
+        assert!(parse_watch("2").is_err());
+        assert!(parse_watch("0 uptime").is_err());
     }
 }
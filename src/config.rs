@@ -0,0 +1,984 @@
+//! This module provides runtime configuration loaded from a `.env` file or a TOML config file.
+//!
+//! Hosts are defined in `~/.wcli/config.toml`, each under its own `[hosts.<name>]` section.
+//!
+//! ```toml
+//! [hosts.default]
+//! EC2 = 'ec2-user@ec2-xxxxxxxx.compute.amazonaws.com'
+//! PEM = '/home/user/<your_file.pem>'
+//! PASS = 'password'
+//! ```
+//!
+//! `EC2` can be just the instance address, in which case the user is taken from `SSH_USER`,
+//! `ec2-user` by default. `SSH_PORT` sets the ssh port, 22 by default. `PACKAGE_MANAGER` forces
+//! `apt`, `dnf` or `yum` instead of detecting it.
+//!
+//! `PASS_PROMPT = true` asks for the sudo password when WCLI starts instead of reading `PASS`,
+//! so it never has to be written down. It is kept in memory for the session and overwritten
+//! when WCLI exits.
+//!
+//! The sql shell logs in to mariadb as `DB_USER`, `root` by default. `root` without `DB_PASS`
+//! goes through sudo for unix socket auth, any other login passes `DB_PASS` to mariadb and
+//! doesn't need sudo.
+//!
+//! `DB_HOST` and `DB_PORT` point the sql shell at a database the EC2 can reach, like an RDS
+//! endpoint, instead of the one running on the instance. mariadb still runs on the EC2, so it
+//! uses the instance's network access.
+//!
+//! `STRICT_HOST_KEY_CHECKING` is passed to ssh's `StrictHostKeyChecking`. The default,
+//! `accept-new`, trusts the key of a host seen for the first time and refuses to connect if the
+//! key of a known host changes, so the first connection doesn't stop at ssh's yes/no question.
+//! `yes` only connects to hosts already in `known_hosts`, which guards against a spoofed host
+//! on the first connection too. `no` accepts any key and should only be used for throwaway
+//! instances. `KNOWN_HOSTS_FILE` replaces `~/.ssh/known_hosts`.
+//!
+//! If no hosts are configured the `.env` file is used as the `default` host. It is the file given
+//! with `--env-file`, otherwise `.env` in the current directory or one of its parents, otherwise
+//! `~/.wcli/.env`.
+//!
+//! A setting that can be given more than one way is taken from the command line flag first,
+//! then the `NO_COLOR` or `PROMPT` environment variable, then this file, then the default. The
+//! other settings are only read from the environment for the `.env` host, a `[hosts.<name>]`
+//! section isn't changed by them.
+//! A key the file doesn't know is reported as a warning, so a typo doesn't go unnoticed.
+//!
+//! Every command is recorded in `~/.wcli/audit.log`, set `audit_log = false` at the top of the
+//! file to turn this off.
+//!
+//! The connection test is tried up to 5 times with a growing delay between attempts, set
+//! `connect_attempts` at the top of the file to change this.
+//!
+//! The main prompt shows whether the connection is up and its latency, set
+//! `prompt_status = false` to hide it. `prompt` replaces the prompt of every shell with a
+//! template, see [`crate::cmd::render_prompt`].
+//!
+//! Commands like `rm -rf` or `DROP TABLE` have to be confirmed before they run. Set
+//! `dangerous_patterns` to replace the list, or `confirm_dangerous = false` to turn it off.
+//!
+//! The sql shell refuses a `DELETE` or `UPDATE` without a `WHERE` unless it is run with
+//! `force`, set `sql_safe_mode = false` to turn this off.
+//!
+//! Output over 1024 KB is cut short, set `output_limit_kb` to change this or `0` to turn it off.
+//!
+//! `color = false` turns off colored output, like `--no-color` and `NO_COLOR`.
+//!
+//! `command_timeout_secs` stops any command that runs for longer, `0` by default for no limit.
+//! `--timeout` overrides it for `exec` and `timeout` in the cmd shell for the session.
+//!
+//! ssh sends a keepalive after 30 seconds without traffic and drops the connection after 3
+//! go unanswered, set `keepalive_interval_secs` and `keepalive_count_max` to change this or
+//! `keepalive_interval_secs = 0` to turn keepalives off.
+//!
+//! WCLI disconnects after 30 minutes without input at the main prompt, set
+//! `idle_timeout_minutes` to change this or `0` to turn it off.
+//!
+//! `[commands]` adds commands to the main prompt, each name mapped to a remote command where
+//! `{1}`, `{2}` and so on are the arguments typed after it.
+//!
+//! ```toml
+//! [commands]
+//! restart-nginx = "sudo systemctl restart nginx"
+//! tail-log = "tail -n {2} /var/log/{1}"
+//! ```
+//!
+//! `[aliases]` sets cmd shell aliases, like the ones saved with `alias`. An alias saved in the
+//! shell with the same name replaces the one here.
+//!
+//! ```toml
+//! [aliases]
+//! deploy = "cd /app && ./deploy.sh"
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (C) Josh Bassett. All rights reserved.
+//!
+//! Apache 2.0
+//!
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Once,
+};
+
+use colored::Colorize;
+use dotenv::dotenv;
+use serde::Deserialize;
+
+/// Port ssh connects to unless `SSH_PORT` is set.
+///
+const SSH_PORT: u16 = 22;
+
+/// Exit code WCLI uses when the config is missing or invalid, sysexits' `EX_CONFIG`.
+///
+pub const EXIT_CONFIG: i32 = 78;
+
+/// ssh's `StrictHostKeyChecking` unless `STRICT_HOST_KEY_CHECKING` is set.
+///
+const HOST_KEY_CHECKING: &str = "accept-new";
+
+/// Holds the credentials needed to reach the EC2, and the settings that apply to it.
+///
+pub struct Config {
+    /// Name of the host, `default` for the `.env` file.
+    pub host: String,
+    /// Sudo password, empty until it is asked for when `pass_prompt` is set.
+    pub pass: String,
+    /// Whether `pass` is asked for at startup, see [`Config::prompt_password`].
+    pub pass_prompt: bool,
+    /// mariadb login, where `root` without `db_pass` logs in through sudo.
+    pub db_user: String,
+    /// mariadb password for `db_user`.
+    pub db_pass: Option<String>,
+    /// Database server the sql shell connects to, the EC2 itself if unset.
+    pub db_host: Option<String>,
+    /// Port of `db_host`, mariadb's default if unset.
+    pub db_port: Option<u16>,
+    /// Path to the private key ssh logs in with.
+    pub pem: String,
+    /// Address of the EC2, with or without a user.
+    pub ec2: String,
+    /// User ssh logs in as when `ec2` doesn't name one.
+    pub ssh_user: String,
+    /// Port ssh connects to.
+    pub ssh_port: u16,
+    /// Passed to ssh as `StrictHostKeyChecking`.
+    pub host_key_checking: String,
+    /// Passed to ssh as `UserKnownHostsFile`, ssh's own default if unset.
+    pub known_hosts: Option<String>,
+    /// ssh ControlMaster socket shared by every command, when enabled.
+    pub control_path: Option<PathBuf>,
+    /// Whether every command is recorded in `~/.wcli/audit.log`.
+    pub audit_log: bool,
+    /// Whether commands are printed instead of run.
+    pub dry_run: bool,
+    /// How many `-v` flags were given.
+    pub verbose: u8,
+    /// How many times the connection test is tried before giving up.
+    pub connect_attempts: u32,
+    /// Whether the main prompt shows the connection status.
+    pub prompt_status: bool,
+    /// Package manager to use instead of the detected one.
+    pub package_manager: Option<String>,
+    /// Template every shell's prompt is rendered from, if set.
+    pub prompt: Option<String>,
+    /// Commands that have to be confirmed before they run.
+    pub dangerous_patterns: Vec<String>,
+    /// Whether the sql shell refuses a `DELETE` or `UPDATE` without a `WHERE`.
+    pub sql_safe_mode: bool,
+    /// How much of a command's output is printed, or `0` for all of it.
+    pub output_limit_kb: usize,
+    /// How long the main prompt waits for input before disconnecting, or `0` to wait forever.
+    pub idle_timeout_minutes: u64,
+    /// Custom commands from `[commands]`, each name mapped to its template.
+    pub commands: BTreeMap<String, String>,
+    /// cmd shell aliases from `[aliases]`.
+    pub aliases: BTreeMap<String, String>,
+    /// False if the config file turns colored output off.
+    pub color: bool,
+    /// How long a command may run before it is stopped, or `0` for as long as it takes.
+    pub command_timeout_secs: u64,
+    /// Passed to ssh as `ServerAliveInterval`.
+    pub keepalive_interval_secs: u64,
+    /// Passed to ssh as `ServerAliveCountMax`.
+    pub keepalive_count_max: u32,
+    /// File the credentials were read from, or [`None`] if they came from environment variables.
+    pub source: Option<PathBuf>,
+}
+
+/// Layout of `~/.wcli/config.toml`.
+///
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    hosts: BTreeMap<String, HostEntry>,
+    #[serde(default = "enabled")]
+    audit_log: bool,
+    #[serde(default = "connect_attempts")]
+    connect_attempts: u32,
+    #[serde(default = "enabled")]
+    prompt_status: bool,
+    #[serde(default)]
+    prompt: Option<String>,
+    #[serde(default = "enabled")]
+    confirm_dangerous: bool,
+    #[serde(default = "dangerous_patterns")]
+    dangerous_patterns: Vec<String>,
+    #[serde(default = "enabled")]
+    sql_safe_mode: bool,
+    #[serde(default = "output_limit_kb")]
+    output_limit_kb: usize,
+    #[serde(default = "idle_timeout_minutes")]
+    idle_timeout_minutes: u64,
+    #[serde(default)]
+    commands: BTreeMap<String, String>,
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+    #[serde(default = "enabled")]
+    color: bool,
+    #[serde(default)]
+    command_timeout_secs: u64,
+    #[serde(default = "keepalive_interval_secs")]
+    keepalive_interval_secs: u64,
+    #[serde(default = "keepalive_count_max")]
+    keepalive_count_max: u32,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> ConfigFile {
+        ConfigFile {
+            hosts: BTreeMap::new(),
+            audit_log: enabled(),
+            connect_attempts: connect_attempts(),
+            prompt_status: enabled(),
+            prompt: None,
+            confirm_dangerous: enabled(),
+            dangerous_patterns: dangerous_patterns(),
+            sql_safe_mode: enabled(),
+            output_limit_kb: output_limit_kb(),
+            idle_timeout_minutes: idle_timeout_minutes(),
+            commands: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            color: enabled(),
+            command_timeout_secs: 0,
+            keepalive_interval_secs: keepalive_interval_secs(),
+            keepalive_count_max: keepalive_count_max(),
+            unknown: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Returns the patterns that need confirming, or none if `confirm_dangerous` is off.
+    ///
+    fn confirmed_patterns(&self) -> Vec<String> {
+        if self.confirm_dangerous {
+            self.dangerous_patterns.clone()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns the prompt template, `PROMPT` from the environment if set, otherwise `prompt`
+    /// from this file.
+    ///
+    fn prompt_template(&self, env_prompt: Option<String>) -> Option<String> {
+        env_prompt.or_else(|| self.prompt.clone())
+    }
+}
+
+/// A single `[hosts.<name>]` section.
+///
+#[derive(Deserialize)]
+struct HostEntry {
+    #[serde(rename = "EC2")]
+    ec2: String,
+    #[serde(rename = "PEM")]
+    pem: String,
+    #[serde(rename = "PASS")]
+    pass: Option<String>,
+    #[serde(rename = "PASS_PROMPT", default)]
+    pass_prompt: bool,
+    #[serde(rename = "DB_USER", default = "db_user")]
+    db_user: String,
+    #[serde(rename = "DB_PASS")]
+    db_pass: Option<String>,
+    #[serde(rename = "DB_HOST")]
+    db_host: Option<String>,
+    #[serde(rename = "DB_PORT")]
+    db_port: Option<i64>,
+    #[serde(rename = "SSH_USER", default = "ssh_user")]
+    ssh_user: String,
+    #[serde(rename = "SSH_PORT")]
+    ssh_port: Option<i64>,
+    #[serde(rename = "PACKAGE_MANAGER")]
+    package_manager: Option<String>,
+    #[serde(rename = "STRICT_HOST_KEY_CHECKING")]
+    host_key_checking: Option<String>,
+    #[serde(rename = "KNOWN_HOSTS_FILE")]
+    known_hosts: Option<String>,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// Loads the config for the named host.
+    ///
+    /// The host is looked up in `~/.wcli/config.toml`, falling back to the `.env` file and
+    /// environment variables for the `default` host when no hosts are configured.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if the config file can't be read or parsed.
+    /// - Returns [`Err`](Result) listing the available hosts if the host doesn't exist.
+    /// - Returns [`Err`](Result) if the `--env-file` can't be read.
+    /// - Returns [`Err`](Result) naming each of `PASS`, `PEM` and `EC2` that is not set.
+    /// - Returns [`Err`](Result) if `SSH_PORT` isn't a port from 1 to 65535.
+    /// - Returns [`Err`](Result) if `STRICT_HOST_KEY_CHECKING` isn't `accept-new`, `yes` or `no`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let config: Config = Config::load("staging", None)?;
+    /// ```
+    pub fn load(host: &str, env_file: Option<&str>) -> Result<Config, String> {
+        let file: ConfigFile = load_file()?;
+
+        if file.hosts.is_empty() {
+            return Config::load_env(host, env_file, &file);
+        }
+
+        match file.hosts.get(host) {
+            Some(entry) => Config::from_entry(host, entry, &file),
+            None => Err(unknown_host(host, file.hosts.keys())),
+        }
+    }
+
+    /// Loads the config for every host in `~/.wcli/config.toml`, in name order, or just the
+    /// `default` host from the `.env` file when no hosts are configured.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if the config file can't be read or parsed.
+    /// - Returns [`Err`](Result) if the `.env` file is needed and can't be read or is incomplete.
+    /// - Returns [`Err`](Result) if any host has an invalid `SSH_PORT` or
+    ///   `STRICT_HOST_KEY_CHECKING`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let configs: Vec<Config> = Config::load_all(None)?;
+    /// ```
+    pub fn load_all(env_file: Option<&str>) -> Result<Vec<Config>, String> {
+        let file: ConfigFile = load_file()?;
+
+        if file.hosts.is_empty() {
+            return Ok(vec![Config::load_env("default", env_file, &file)?]);
+        }
+
+        file.hosts
+            .iter()
+            .map(|(host, entry)| Config::from_entry(host, entry, &file))
+            .collect()
+    }
+
+    /// Returns the name of every host in `~/.wcli/config.toml`, in name order, or just `default`
+    /// when no hosts are configured.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if the config file can't be read or parsed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let hosts: Vec<String> = Config::host_names()?;
+    /// ```
+    pub fn host_names() -> Result<Vec<String>, String> {
+        let file: ConfigFile = load_file()?;
+
+        if file.hosts.is_empty() {
+            return Ok(vec!["default".to_string()]);
+        }
+
+        Ok(file.hosts.into_keys().collect())
+    }
+
+    /// Builds the config for a `[hosts.<name>]` section.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if `PASS` is missing and `PASS_PROMPT` isn't set.
+    /// - Returns [`Err`](Result) if `DB_USER` or `DB_HOST` have characters other than letters,
+    ///   digits, `_`, `-` and `.`.
+    /// - Returns [`Err`](Result) if `SSH_PORT` or `DB_PORT` isn't a port from 1 to 65535.
+    /// - Returns [`Err`](Result) if `STRICT_HOST_KEY_CHECKING` isn't `accept-new`, `yes` or `no`.
+    ///
+    fn from_entry(host: &str, entry: &HostEntry, file: &ConfigFile) -> Result<Config, String> {
+        let pass: String = match &entry.pass {
+            Some(pass) => pass.clone(),
+            None if entry.pass_prompt => String::new(),
+            None => {
+                return Err(format!(
+                    "host '{host}' needs PASS, or PASS_PROMPT = true to be asked for it"
+                ));
+            }
+        };
+
+        Ok(Config {
+            host: host.to_string(),
+            pass,
+            pass_prompt: entry.pass_prompt,
+            db_user: parse_name("DB_USER", &entry.db_user)?,
+            db_pass: entry.db_pass.clone(),
+            db_host: match &entry.db_host {
+                Some(db_host) => Some(parse_name("DB_HOST", db_host)?),
+                None => None,
+            },
+            db_port: match entry.db_port {
+                Some(port) => Some(parse_port("DB_PORT", &port.to_string())?),
+                None => None,
+            },
+            pem: entry.pem.clone(),
+            ec2: entry.ec2.clone(),
+            ssh_user: entry.ssh_user.clone(),
+            ssh_port: match entry.ssh_port {
+                Some(port) => parse_port("SSH_PORT", &port.to_string())?,
+                None => SSH_PORT,
+            },
+            host_key_checking: match &entry.host_key_checking {
+                Some(value) => parse_host_key_checking(value)?,
+                None => HOST_KEY_CHECKING.to_string(),
+            },
+            known_hosts: entry.known_hosts.clone(),
+            control_path: None,
+            audit_log: file.audit_log,
+            dry_run: false,
+            verbose: 0,
+            connect_attempts: file.connect_attempts,
+            prompt_status: file.prompt_status,
+            package_manager: entry.package_manager.clone(),
+            prompt: file.prompt_template(env::var("PROMPT").ok()),
+            dangerous_patterns: file.confirmed_patterns(),
+            sql_safe_mode: file.sql_safe_mode,
+            output_limit_kb: file.output_limit_kb,
+            idle_timeout_minutes: file.idle_timeout_minutes,
+            commands: parse_commands(&file.commands)?,
+            aliases: parse_aliases(&file.aliases)?,
+            color: file.color,
+            command_timeout_secs: file.command_timeout_secs,
+            keepalive_interval_secs: file.keepalive_interval_secs,
+            keepalive_count_max: file.keepalive_count_max,
+            source: config_path(),
+        })
+    }
+
+    /// Loads the `default` host from the `.env` file and environment variables, with the
+    /// remaining settings taken from the config file.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if the host isn't `default`.
+    /// - Returns [`Err`](Result) if the `--env-file` can't be read.
+    /// - Returns [`Err`](Result) naming each of `PASS`, `PEM` and `EC2` that is not set, `PASS`
+    ///   isn't needed when `PASS_PROMPT=true`.
+    /// - Returns [`Err`](Result) if `DB_USER` or `DB_HOST` have characters other than letters,
+    ///   digits, `_`, `-` and `.`.
+    /// - Returns [`Err`](Result) if `SSH_PORT` or `DB_PORT` isn't a port from 1 to 65535.
+    /// - Returns [`Err`](Result) if `STRICT_HOST_KEY_CHECKING` isn't `accept-new`, `yes` or `no`.
+    ///
+    fn load_env(host: &str, env_file: Option<&str>, file: &ConfigFile) -> Result<Config, String> {
+        if host != "default" {
+            return Err(unknown_host(host, ["default".to_string()].iter()));
+        }
+
+        let source: Option<PathBuf> = load_dotenv(env_file)?;
+        let pass_prompt: bool = env::var("PASS_PROMPT").is_ok_and(|value| value == "true");
+        let (pass, pem, ec2) = if pass_prompt {
+            let [pem, ec2] = required(["PEM", "EC2"], source.as_deref())?;
+            (String::new(), pem, ec2)
+        } else {
+            let [pass, pem, ec2] = required(["PASS", "PEM", "EC2"], source.as_deref())?;
+            (pass, pem, ec2)
+        };
+
+        Ok(Config {
+            host: host.to_string(),
+            pass,
+            pass_prompt,
+            db_user: match env::var("DB_USER") {
+                Ok(user) => parse_name("DB_USER", &user)?,
+                Err(_) => db_user(),
+            },
+            db_pass: env::var("DB_PASS").ok(),
+            db_host: match env::var("DB_HOST") {
+                Ok(db_host) => Some(parse_name("DB_HOST", &db_host)?),
+                Err(_) => None,
+            },
+            db_port: match env::var("DB_PORT") {
+                Ok(port) => Some(parse_port("DB_PORT", &port)?),
+                Err(_) => None,
+            },
+            pem,
+            ec2,
+            ssh_user: env::var("SSH_USER").unwrap_or_else(|_| ssh_user()),
+            ssh_port: match env::var("SSH_PORT") {
+                Ok(port) => parse_port("SSH_PORT", &port)?,
+                Err(_) => SSH_PORT,
+            },
+            host_key_checking: match env::var("STRICT_HOST_KEY_CHECKING") {
+                Ok(value) => parse_host_key_checking(&value)?,
+                Err(_) => HOST_KEY_CHECKING.to_string(),
+            },
+            known_hosts: env::var("KNOWN_HOSTS_FILE").ok(),
+            control_path: None,
+            audit_log: file.audit_log,
+            dry_run: false,
+            verbose: 0,
+            connect_attempts: file.connect_attempts,
+            prompt_status: file.prompt_status,
+            package_manager: env::var("PACKAGE_MANAGER").ok(),
+            prompt: file.prompt_template(env::var("PROMPT").ok()),
+            dangerous_patterns: file.confirmed_patterns(),
+            sql_safe_mode: file.sql_safe_mode,
+            output_limit_kb: file.output_limit_kb,
+            idle_timeout_minutes: file.idle_timeout_minutes,
+            commands: parse_commands(&file.commands)?,
+            aliases: parse_aliases(&file.aliases)?,
+            color: file.color,
+            command_timeout_secs: file.command_timeout_secs,
+            keepalive_interval_secs: file.keepalive_interval_secs,
+            keepalive_count_max: file.keepalive_count_max,
+            source,
+        })
+    }
+
+    /// Asks for the sudo password without echoing it, if `PASS_PROMPT` is set.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Err`](Result) if the password can't be read from the terminal.
+    ///
+    /// # Examples
+    /// ```plaintext
+    /// Sudo password for staging:
+    /// ```
+    pub fn prompt_password(&mut self) -> Result<(), String> {
+        if !self.pass_prompt {
+            return Ok(());
+        }
+
+        let prompt: String = format!("Sudo password for {}: ", self.host);
+        self.pass = rpassword::prompt_password(prompt)
+            .map_err(|e| format!("unable to read the password: {e}"))?;
+
+        Ok(())
+    }
+
+    /// Overwrites a prompted password in memory so it doesn't outlive the session.
+    ///
+    /// A password read from `PASS` is left alone, since it is still in the environment and the
+    /// file it came from.
+    ///
+    pub fn forget_password(&mut self) {
+        if !self.pass_prompt {
+            return;
+        }
+
+        let len: usize = self.pass.len();
+        self.pass.clear();
+        self.pass.extend(std::iter::repeat_n('\0', len));
+        std::hint::black_box(&self.pass);
+        self.pass.clear();
+    }
+}
+
+impl Drop for Config {
+    fn drop(&mut self) {
+        self.forget_password();
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// Returns the config of a `test` host with the defaults from the config file, for tests
+    /// that build commands without connecting.
+    ///
+    pub fn test() -> Config {
+        let file: ConfigFile = toml::from_str(
+            "[hosts.test]\n\
+             EC2 = 'ec2-user@ec2.test'\n\
+             PEM = '/tmp/test.pem'\n\
+             PASS = 'hunter2'\n",
+        )
+        .expect("the test config is valid");
+
+        Config::from_entry("test", &file.hosts["test"], &file).expect("the test host is valid")
+    }
+}
+
+/// Loads the `.env` file into the environment and returns its path, or [`None`] if there isn't
+/// one and the credentials have to come from the environment.
+///
+/// The file given with `--env-file` is used if set, otherwise `.env` in the current directory
+/// or one of its parents, otherwise `~/.wcli/.env`.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the `--env-file` or `~/.wcli/.env` can't be read.
+///
+fn load_dotenv(env_file: Option<&str>) -> Result<Option<PathBuf>, String> {
+    if let Some(path) = env_file {
+        return read_dotenv(Path::new(path)).map(Some);
+    }
+
+    if let Ok(path) = dotenv() {
+        return Ok(Some(path));
+    }
+
+    match wcli_dir().map(|dir: PathBuf| dir.join(".env")) {
+        Some(path) if path.exists() => read_dotenv(&path).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Loads a `.env` file into the environment and returns its path.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the file can't be read or parsed.
+///
+fn read_dotenv(path: &Path) -> Result<PathBuf, String> {
+    dotenv::from_path(path).map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+
+    Ok(path.to_path_buf())
+}
+
+/// Returns the user's home directory.
+///
+pub fn home_dir() -> Option<PathBuf> {
+    env::var("HOME").ok().map(PathBuf::from)
+}
+
+/// Returns the path to `~/.wcli`, where the config file and audit log are kept.
+///
+pub fn wcli_dir() -> Option<PathBuf> {
+    home_dir().map(|home: PathBuf| home.join(".wcli"))
+}
+
+/// Returns the path to `~/.wcli/config.toml`.
+///
+fn config_path() -> Option<PathBuf> {
+    wcli_dir().map(|dir: PathBuf| dir.join("config.toml"))
+}
+
+/// Returns the config file, or an empty one if the file doesn't exist.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the config file can't be read or parsed.
+///
+fn load_file() -> Result<ConfigFile, String> {
+    let path: PathBuf = match config_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(ConfigFile::default()),
+    };
+
+    let contents: String =
+        fs::read_to_string(&path).map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+    let file: ConfigFile = toml::from_str(&contents)
+        .map_err(|e| format!("unable to parse {}: {e}", path.display()))?;
+
+    UNKNOWN_KEYS.call_once(|| {
+        for key in unknown_keys(&file) {
+            eprintln!(
+                "{} unknown key '{key}' in {}",
+                "warning:".yellow(),
+                path.display()
+            );
+        }
+    });
+
+    Ok(file)
+}
+
+/// Makes sure unknown keys are only warned about once, however often the file is read.
+///
+static UNKNOWN_KEYS: Once = Once::new();
+
+/// Returns the keys in the config file that WCLI doesn't use, with the host for keys in a
+/// `[hosts.<name>]` section, e.g. `hosts.staging.PORT`.
+///
+fn unknown_keys(file: &ConfigFile) -> Vec<String> {
+    let mut keys: Vec<String> = file.unknown.keys().cloned().collect();
+
+    for (host, entry) in &file.hosts {
+        for key in entry.unknown.keys() {
+            keys.push(format!("hosts.{host}.{key}"));
+        }
+    }
+
+    keys
+}
+
+/// Default for settings that are on unless turned off in the config file.
+///
+fn enabled() -> bool {
+    true
+}
+
+/// Default user to log in as when `EC2` doesn't include one.
+///
+fn ssh_user() -> String {
+    "ec2-user".to_string()
+}
+
+/// Default mariadb user the sql shell logs in as.
+///
+fn db_user() -> String {
+    "root".to_string()
+}
+
+/// Default number of connection attempts.
+///
+fn connect_attempts() -> u32 {
+    5
+}
+
+/// Default number of KB of output printed before the rest is cut off.
+///
+fn output_limit_kb() -> usize {
+    1024
+}
+
+/// Default number of minutes the main prompt waits for input before disconnecting.
+///
+fn idle_timeout_minutes() -> u64 {
+    30
+}
+
+/// Default number of seconds without traffic before ssh sends a keepalive.
+///
+fn keepalive_interval_secs() -> u64 {
+    30
+}
+
+/// Default number of unanswered keepalives before ssh drops the connection.
+///
+fn keepalive_count_max() -> u32 {
+    3
+}
+
+/// Default commands that have to be confirmed before they run, matched anywhere in the
+/// command ignoring case.
+///
+fn dangerous_patterns() -> Vec<String> {
+    [
+        "rm -rf",
+        "rm -fr",
+        "mkfs",
+        "dd if=",
+        "shutdown",
+        "reboot",
+        "yum remove",
+        "dnf remove",
+        "apt-get remove",
+        "apt-get purge",
+        "drop table",
+        "drop database",
+        "truncate table",
+        "git reset --hard",
+        "git push --force",
+        "git push -f",
+        "git clean -f",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// Returns an error message naming the unknown host and the available ones.
+///
+fn unknown_host<'a>(host: &str, available: impl Iterator<Item = &'a String>) -> String {
+    let names: Vec<&str> = available.map(|name: &String| name.as_str()).collect();
+
+    format!(
+        "unknown host '{host}', available hosts: {}",
+        names.join(", ")
+    )
+}
+
+/// Returns the port in a setting like `SSH_PORT`.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the value isn't a number from 1 to 65535.
+///
+fn parse_port(name: &str, value: &str) -> Result<u16, String> {
+    match value.trim().parse::<u16>() {
+        Ok(port) if port != 0 => Ok(port),
+        _ => Err(format!(
+            "{name} must be a port from 1 to 65535, got '{value}'"
+        )),
+    }
+}
+
+/// Returns the number of seconds in a timeout like `--timeout`, where `0` means no timeout.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the value is negative or isn't a whole number.
+///
+pub fn parse_timeout(name: &str, value: &str) -> Result<u64, String> {
+    let value: &str = value.trim();
+
+    match value.parse::<u64>() {
+        Ok(secs) => Ok(secs),
+        Err(_) if value.parse::<i64>().is_ok() => Err(format!("{name} can't be negative")),
+        Err(_) => Err(format!("{name} must be a number of seconds, got '{value}'")),
+    }
+}
+
+/// Returns the value of a setting like `DB_USER` or `DB_HOST`, which is put into the remote
+/// command unquoted.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the value is empty or has characters other than letters,
+///   digits, `_`, `-` and `.`.
+///
+fn parse_name(name: &str, value: &str) -> Result<String, String> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+
+    match value.trim() {
+        value if !value.is_empty() && value.chars().all(valid) => Ok(value.to_string()),
+        _ => Err(format!(
+            "{name} can only have letters, digits, '_', '-' and '.', got '{value}'"
+        )),
+    }
+}
+
+/// Returns the `[commands]` section of the config file, checking each name can be typed at the
+/// main prompt.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if a name has characters other than letters, digits, `_`, `-`
+///   and `.`.
+/// - Returns [`Err`](Result) if a name is already a built-in command, which would hide it.
+///
+fn parse_commands(commands: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>, String> {
+    for name in commands.keys() {
+        parse_name("a command name", name)?;
+
+        if crate::cmd::shell_commands("wcli").contains(&name.as_str()) {
+            return Err(format!("[commands] can't replace the built-in '{name}'"));
+        }
+    }
+
+    Ok(commands.clone())
+}
+
+/// Returns the `[aliases]` section of the config file, checking each alias as `alias` does.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if an alias fails [`crate::cmd::alias::check`].
+///
+fn parse_aliases(aliases: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>, String> {
+    for (name, command) in aliases {
+        crate::cmd::alias::check(name, command).map_err(|e| format!("[aliases] {e}"))?;
+    }
+
+    Ok(aliases.clone())
+}
+
+/// Returns the value of `STRICT_HOST_KEY_CHECKING`.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the value isn't `accept-new`, `yes` or `no`.
+///
+fn parse_host_key_checking(value: &str) -> Result<String, String> {
+    match value.trim() {
+        value @ ("accept-new" | "yes" | "no") => Ok(value.to_string()),
+        _ => Err(format!(
+            "STRICT_HOST_KEY_CHECKING must be accept-new, yes or no, got '{value}'"
+        )),
+    }
+}
+
+/// Returns the values of env variables that must be set.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) naming every variable that isn't set, with an example of the file
+///   they go in.
+///
+/// # Examples
+/// ```rust
+/// let [pass, pem, ec2] = required(["PASS", "PEM", "EC2"], None)?;
+/// ```
+fn required<const N: usize>(
+    names: [&str; N],
+    source: Option<&Path>,
+) -> Result<[String; N], String> {
+    let values: [Option<String>; N] = names.map(|name: &str| env::var(name).ok());
+    let missing: Vec<&str> = names
+        .iter()
+        .zip(&values)
+        .filter(|(_, value)| value.is_none())
+        .map(|(name, _)| *name)
+        .collect();
+
+    if !missing.is_empty() {
+        let file: String =
+            source.map_or(".env".to_string(), |path: &Path| path.display().to_string());
+
+        return Err(format!(
+            "missing: {}\nset them in {file}, for example\n\n\
+            PASS='password'\n\
+            EC2='ec2-user@ec2-xxxxxxxx.compute.amazonaws.com'\n\
+            PEM='/home/user/<your_file.pem>'",
+            missing.join(", ")
+        ));
+    }
+
+    Ok(values.map(Option::unwrap_or_default))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigFile, parse_aliases, parse_timeout, unknown_keys};
+
+    #[test]
+    fn unknown_keys_are_reported_with_their_host() {
+        let file: ConfigFile = toml::from_str(
+            "colour = false\n\
+             audit_log = false\n\
+             [hosts.web]\n\
+             EC2 = 'ec2.test'\n\
+             PEM = '/tmp/test.pem'\n\
+             PORT = 2222\n",
+        )
+        .expect("the config parses");
+
+        assert_eq!(unknown_keys(&file), ["colour", "hosts.web.PORT"]);
+        assert!(unknown_keys(&ConfigFile::default()).is_empty());
+    }
+
+    #[test]
+    fn prompt_from_the_environment_comes_before_the_file() {
+        let file: ConfigFile = toml::from_str("prompt = '{host}$ '").expect("the config parses");
+        let env_prompt: Option<String> = file.prompt_template(Some("{user}> ".to_string()));
+
+        assert_eq!(env_prompt.as_deref(), Some("{user}> "));
+        assert_eq!(file.prompt_template(None).as_deref(), Some("{host}$ "));
+        assert_eq!(ConfigFile::default().prompt_template(None), None);
+    }
+
+    #[test]
+    fn timeouts_are_whole_seconds() {
+        assert_eq!(parse_timeout("--timeout", "0"), Ok(0));
+        assert_eq!(parse_timeout("--timeout", "1800"), Ok(1800));
+        assert_eq!(
+            parse_timeout("--timeout", "-5"),
+            Err("--timeout can't be negative".to_string())
+        );
+        assert_eq!(
+            parse_timeout("--timeout", "abc"),
+            Err("--timeout must be a number of seconds, got 'abc'".to_string())
+        );
+    }
+
+    #[test]
+    fn aliases_are_checked_like_the_alias_command() {
+        let file: ConfigFile = toml::from_str("[aliases]\ndeploy = 'cd /app && ./deploy.sh'")
+            .expect("the config parses");
+        assert!(parse_aliases(&file.aliases).is_ok());
+
+        let file: ConfigFile =
+            toml::from_str("[aliases]\nunalias = 'ls'").expect("the config parses");
+        assert!(parse_aliases(&file.aliases).is_err());
+    }
+}
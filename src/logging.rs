@@ -0,0 +1,108 @@
+//! This module provides an audit trail of every command run against the EC2.
+//!
+//! Each command is appended to `~/.wcli/audit.log` as a line of JSON, with the password
+//! redacted.
+//!
+//! ```json
+//! {"timestamp":"2025-06-19T11:48:02+01:00","host":"default","shell":"cmd","command":"ls -l","status":0}
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (C) Josh Bassett. All rights reserved.
+//!
+//! Apache 2.0
+//!
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::{
+    cmd::helpers,
+    config::{self, Config},
+};
+
+/// The sub-shell commands are currently being run from.
+///
+static SHELL: Mutex<&str> = Mutex::new("wcli");
+
+/// A single line of the audit log.
+///
+/// `stdin` holds anything written to the command's stdin, such as a sql query.
+///
+#[derive(Serialize)]
+struct Entry<'a> {
+    timestamp: String,
+    host: &'a str,
+    shell: &'a str,
+    command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stdin: Option<String>,
+    status: Option<i32>,
+}
+
+/// Sets the sub-shell that following commands are logged under.
+///
+/// # Examples
+/// ```rust
+/// logging::set_shell("git");
+/// ```
+pub fn set_shell(shell: &'static str) {
+    *SHELL.lock().unwrap_or_else(|e| e.into_inner()) = shell;
+}
+
+/// Appends a command and its exit status to the audit log, unless disabled in the config.
+///
+/// The status is [`None`] if the command was killed by a signal. A failure to write the log is
+/// reported as a warning rather than stopping the command.
+///
+/// # Examples
+/// ```rust
+/// logging::record(&config, "ls -l", None, output.status.code());
+/// ```
+pub fn record(config: &Config, command: &str, stdin: Option<&str>, status: Option<i32>) {
+    if !config.audit_log {
+        return;
+    }
+
+    let shell: &str = *SHELL.lock().unwrap_or_else(|e| e.into_inner());
+    let entry: Entry = Entry {
+        timestamp: chrono::offset::Local::now().to_rfc3339(),
+        host: &config.host,
+        shell,
+        command: helpers::redact_secrets(command, config),
+        stdin: stdin.map(|input: &str| helpers::redact_secrets(input, config)),
+        status,
+    };
+
+    if let Err(e) = append(&entry) {
+        eprintln!("{} unable to write audit log: {e}", "warning:".yellow());
+    }
+}
+
+/// Writes the entry as a line at the end of `~/.wcli/audit.log`.
+///
+/// # Errors
+///
+/// - Returns [`Err`](Result) if the home directory is unknown or the file can't be written.
+///
+fn append(entry: &Entry) -> Result<(), String> {
+    let dir: PathBuf = config::wcli_dir().ok_or("HOME is not set")?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let line: String = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("audit.log"))
+        .and_then(|mut file: fs::File| writeln!(file, "{line}"))
+        .map_err(|e| e.to_string())
+}
@@ -18,17 +18,409 @@
 //! EC2='ec2-user@ec2-xxxxxxxx.compute.amazonaws.com'
 //! PEM='/home/user/<your_file.pem>'
 //! ```
-//! Then build the project with `cargo run` and provided the AWS credentials are ok, WCLI will
-//! connect successfully.
+//! `EC2` can also be just the instance address, with the user to log in as set in `SSH_USER`. It
+//! defaults to `ec2-user`, so Ubuntu AMIs need `SSH_USER='ubuntu'`. If ssh listens on a port other
+//! than 22 set it in `SSH_PORT`.
+//!
+//! To avoid keeping the sudo password in a file, set `PASS_PROMPT=true` instead of `PASS` and WCLI
+//! asks for it, without echoing, when it starts. It is only held in memory for the session and
+//! overwritten on exit. Scripts and cron jobs can keep using `PASS`, which is still the default.
+//!
+//! The sql shell logs in to mariadb as `root` through sudo by default. To use a database user with
+//! only the privileges it needs, set `DB_USER` and its password in `DB_PASS`, which is separate
+//! from the sudo `PASS`. mariadb is then run without sudo and the password is passed in
+//! `MYSQL_PWD`, so it doesn't show up in `ps`.
+//! ```env
+//! DB_USER='app'
+//! DB_PASS='database password'
+//! ```
+//!
+//! If the database isn't on the EC2, for example an RDS endpoint, set `DB_HOST` and `DB_PORT`.
+//! mariadb still runs on the EC2 and connects from there, so it uses the instance's network access.
+//! Without them the sql shell connects to the database on the EC2.
+//! ```env
+//! DB_HOST='mydb.xxxxxxxx.eu-west-2.rds.amazonaws.com'
+//! DB_PORT='3306'
+//! ```
+//!
+//! The `.env` file is read at runtime, so the same binary can be pointed at a different EC2 by
+//! changing the file. Run the project with `cargo run` and provided the AWS credentials are ok,
+//! WCLI will connect successfully.
+//!
+//! If any of `PASS`, `EC2` or `PEM` is missing WCLI lists all of them and exits with 78, as it
+//! does for any other problem with the config, so scripts can tell it apart from a failed command.
+//!
+//! `exit` at the main prompt saves the history, closes the ssh connection and exits with 0. Ctrl-D
+//! does the same but exits with 3, and if the connection test fails at startup WCLI exits with 69.
+//!
+//! To keep the credentials somewhere else pass `--env-file <path>`. Otherwise WCLI uses the `.env`
+//! in the current directory or one of its parents, then `~/.wcli/.env`. Add `--verbose` to print
+//! which file was loaded.
+//!
+//! ### Multiple hosts
+//!
+//! To manage several instances, define each one in `~/.wcli/config.toml` and select it with
+//! `--host`. Without the flag the `default` host is used.
+//!
+//! ```toml
+//! [hosts.default]
+//! EC2 = 'ec2-user@ec2-xxxxxxxx.compute.amazonaws.com'
+//! PEM = '/home/user/<your_file.pem>'
+//! PASS = 'password'
+//!
+//! [hosts.staging]
+//! EC2 = 'ec2-user@ec2-yyyyyyyy.compute.amazonaws.com'
+//! PEM = '/home/user/<staging.pem>'
+//! PASS = 'password'
+//! ```
+//! ```bash
+//! wcli --host staging
+//! ```
+//!
+//! Run `profile` to list the hosts, with the active one marked, and `profile staging` to switch to
+//! another without restarting WCLI. The new host's connection is tested first, and if it fails you
+//! stay on the current one.
+//!
+//! Once hosts are defined in the config file `.env` is no longer read, it is only used for the
+//! single host case, and the `SSH_USER`, `DB_USER` and other host settings in the environment are
+//! ignored too. When a setting can be given more than one way the command line flag wins, then
+//! the `NO_COLOR` or `PROMPT` environment variable, then `~/.wcli/config.toml`, then the default.
+//! A key WCLI doesn't know, like a misspelt `conect_attempts`, is reported as a warning at
+//! startup.
+//!
+//! ### Host keys
+//!
+//! WCLI passes `StrictHostKeyChecking=accept-new` to ssh, so the first connection to an instance
+//! trusts its host key instead of stopping at ssh's yes/no question, and a later change of key is
+//! refused. Set `STRICT_HOST_KEY_CHECKING` in `.env` or a host's section to change this:
+//!
+//! - `accept-new`, the default, is safe once the first connection has been made, but a spoofed
+//!   host on that first connection would be trusted.
+//! - `yes` only connects to hosts already in `known_hosts`, add the key with `ssh-keyscan` first.
+//! - `no` accepts any key, even one that changed, and should only be used for throwaway instances.
+//!
+//! `KNOWN_HOSTS_FILE` points ssh at a different `known_hosts` file.
+//!
+//! ### Passphrase protected keys
+//!
+//! If the PEM file has a passphrase WCLI adds it to the running ssh-agent at startup with
+//! `ssh-add`, which asks for the passphrase once. Every command after that authenticates through
+//! the agent, so encrypted keys work without a prompt per command. Keys the agent already holds
+//! aren't added again, and unencrypted keys are used directly as before. Start an agent with
+//! `eval $(ssh-agent)` if `SSH_AUTH_SOCK` isn't set.
+//!
+//! ### Reusing the ssh connection
+//!
+//! Every command normally opens a new ssh connection. Pass `--multiplex` to open a ControlMaster
+//! socket once at startup and share it between commands, which removes the handshake from each
+//! round trip. The socket is closed when WCLI exits.
+//!
+//! ssh sends a keepalive after 30 seconds without traffic, so a firewall or NAT doesn't drop an
+//! idle connection, and gives up after 3 go unanswered. With `--multiplex` WCLI also runs a no-op
+//! over the shared connection at the same interval, which stops it closing during a long pause.
+//! Set `keepalive_interval_secs` and `keepalive_count_max` at the top of `~/.wcli/config.toml` to
+//! change this, or `keepalive_interval_secs = 0` to turn keepalives off.
+//!
+//! If the connection drops, for example after the instance reboots, run `reconnect` from the main
+//! prompt to test it again and open a fresh ssh session without restarting WCLI.
+//!
+//! ### Connection status
+//!
+//! The main prompt shows a green dot and the latency of the last round trip while connected, or a
+//! red dot if the EC2 stopped answering. The status is refreshed every minute, or straight away
+//! with `ping`. To hide it add `prompt_status = false` to the top of `~/.wcli/config.toml`.
+//! ```plaintext
+//! [user@wcli ~][●12ms]$ ping
+//! Connected in 11ms
+//! ```
+//!
+//! ### Custom prompt
+//!
+//! Set `prompt` at the top of `~/.wcli/config.toml`, or the `PROMPT` environment variable, to
+//! replace the prompt of every shell with a template. `{user}`, `{host}`, `{cwd}`, `{branch}`,
+//! `{shell}` and `{status}` are filled in, run `help` for what each one holds.
+//! ```toml
+//! prompt = "{user}@{host}:{cwd} ({shell})$"
+//! ```
+//!
+//! ### Connection retries
+//!
+//! If the connection test fails WCLI retries up to 5 times, waiting 1s, 2s, 4s and 8s in between,
+//! which gives an instance started from stopped time to boot. Authentication failures aren't
+//! retried. Set `connect_attempts` at the top of `~/.wcli/config.toml` to change the number of
+//! attempts.
+//! ```toml
+//! connect_attempts = 3
+//! ```
+//!
+//! An instance's public DNS name changes when it is stopped and started again. If the `EC2`
+//! address no longer resolves WCLI says so, rather than reporting a timeout, and suggests updating
+//! `EC2` in `.env` or `~/.wcli/config.toml` with the instance's new address.
+//!
+//! ### Idle timeout
+//!
+//! If nothing is typed at the main prompt for 30 minutes WCLI prints a warning, closes the ssh
+//! connection and exits, so a forgotten session doesn't stay open. Set `idle_timeout_minutes` at
+//! the top of `~/.wcli/config.toml` to change the wait, or `0` to turn it off.
+//! ```toml
+//! idle_timeout_minutes = 60
+//! ```
+//!
+//! ### Command timeout
+//!
+//! Set `command_timeout_secs` at the top of `~/.wcli/config.toml` to stop any command that runs for
+//! longer, which then exits with 124. It is `0` by default, which waits as long as the command
+//! takes. Override it for one `exec` with `--timeout`, or for the rest of a session with
+//! `timeout <secs>` in the cmd shell, where `timeout` alone shows the current limit. `stream`,
+//! `follow` and `-t` commands aren't stopped. The command is stopped on the EC2 itself, along
+//! with anything it started, and the local ssh is stopped too if it hasn't returned 5 seconds
+//! later.
+//! ```bash
+//! wcli --timeout 1800 exec "make build"
+//! ```
+//!
+//! ### One-shot commands
+//!
+//! For scripts and cron jobs, `exec` runs a single command without the interactive shell and exits
+//! with the remote command's exit code. Add `--sudo` to run it as root. If the ssh connection
+//! itself fails WCLI exits with 69 instead of ssh's 255.
+//! ```bash
+//! wcli exec "ls -l /var/log"
+//! wcli exec --sudo "yum update -y"
+//! ```
+//! Pass `--format json` to print the result as a single JSON object with `host`, `stdout`,
+//! `stderr`, `exit_code` and `duration_ms` instead, for programs that drive WCLI. Output that
+//! isn't valid UTF-8 has the bad bytes replaced.
+//! ```bash
+//! wcli --format json exec "uptime"
+//! ```
+//!
+//! ### Broadcast
+//!
+//! `broadcast` runs the same command on every host in `~/.wcli/config.toml` at once, up to 8 at a
+//! time. Each host's output is printed under its name, followed by a summary of which hosts
+//! succeeded and which failed. WCLI exits with 1 if the command failed on any host.
+//! ```bash
+//! wcli broadcast "df -h /"
+//! wcli broadcast --sudo "yum update -y"
+//! ```
+//!
+//! ### Diff
+//!
+//! `diff` runs the same command on two hosts at once and prints a unified diff of their output,
+//! to catch config drift between hosts like staging and prod. It prints that the hosts are
+//! identical if the output matches. WCLI exits with 0 if the output is identical, 1 if it differs
+//! and 2 if the command failed on either host.
+//! ```bash
+//! wcli diff staging prod "cat /etc/nginx/nginx.conf"
+//! wcli diff --sudo staging prod "cat /etc/sudoers"
+//! ```
+//!
+//! ### Checking the config
+//!
+//! Pass `--config-check` to check everything WCLI needs without connecting, so it works offline.
+//! It loads the host's config, checks the PEM file exists and is only readable by you, that `EC2`
+//! looks like a host name, and that `ssh` and `scp` are installed. Each check is printed with a ✓
+//! or ✗, and WCLI exits with 78 if any failed.
+//! ```bash
+//! wcli --host staging --config-check
+//! ```
+//!
+//! ### Dry run
+//!
+//! Pass `--dry-run` to print each ssh command, and anything sent to its stdin, instead of running
+//! it. The password is shown as `****`. This is useful for checking how a git or sql command is
+//! quoted before it reaches the EC2.
+//! ```bash
+//! wcli --dry-run exec "ls -l /var/log"
+//! ```
+//!
+//! ### Verbose output
+//!
+//! Pass `-v` or `--verbose` to see what WCLI does under the hood. It prints which file the
+//! credentials came from, then each ssh or scp command as it is spawned, with the password shown
+//! as `****`, and how long it took. `-vv` adds the exit code and the size of the output. All of it
+//! goes to stderr, so piped output isn't affected.
+//! ```bash
+//! wcli -vv exec "uptime"
+//! ```
+//!
+//! ### Destructive commands
+//!
+//! Commands that match a dangerous pattern, like `rm -rf`, `git reset --hard` or `DROP TABLE`,
+//! show the host they will run on and only run once you type `yes`. Anything else cancels the
+//! command, and stops a loaded script. Replace the patterns, or turn the check off, in
+//! `~/.wcli/config.toml`.
+//! ```toml
+//! confirm_dangerous = true
+//! dangerous_patterns = ["rm -rf", "drop table", "git push --force"]
+//! ```
+//!
+//! ### Audit log
+//!
+//! Every command run against the EC2 is appended to `~/.wcli/audit.log` as a line of JSON with
+//! the time, host, sub-shell, command and exit status. The password is replaced with `****`
+//! before anything is written. To turn the log off add `audit_log = false` to the top of
+//! `~/.wcli/config.toml`.
+//!
+//! ### Colored output
+//!
+//! Colors are turned off when output is piped to a file or another program, when the `NO_COLOR`
+//! environment variable is set, when WCLI is run with `--no-color`, or when `color = false` is
+//! at the top of `~/.wcli/config.toml`.
+//!
+//! ### Runbooks
+//!
+//! `load <file>` runs a local file of WCLI commands as if they were typed, one per line, including
+//! switching into sub-shells and answering their prompts. It stops at the first command that fails,
+//! add `--continue` to run the rest anyway.
+//! ```plaintext
+//! # deploy.wcli
+//! cmd
+//! cd /var/www/app && git pull
+//! sudo systemctl restart app
+//! exit
+//! ```
+//! ```plaintext
+//! [user@wcli ~]$ load deploy.wcli
+//! ```
+//!
+//! ### Custom commands
+//!
+//! Add a `[commands]` section to `~/.wcli/config.toml` to give your own commands a name at the main
+//! prompt. `{1}`, `{2}` and so on are replaced by the words typed after the name, and a command
+//! starting with `sudo` is run with the password. Using a placeholder with no argument, or passing
+//! more arguments than the command uses, is an error.
+//! ```toml
+//! [commands]
+//! restart-nginx = "sudo systemctl restart nginx"
+//! tail-log = "tail -n {2} /var/log/{1}"
+//! ```
+//! ```plaintext
+//! [user@wcli ~]$ tail-log nginx/error.log 20
+//! tail -n 20 /var/log/nginx/error.log
+//! ```
 //!
 //! ## Examples and Usage
 //!
 //! WCLI is designed to feel like an interactive shell, you can run some predefined commands
 //! to help abstract from repetitve tasks.
 //!
+//! Run `cheatsheet` for example commands grouped into files, services, git and sql, each with a
+//! line on what it does. `help` stays the short list of commands.
+//!
 //! `cmd` opens a shell for direct commands to the EC2.
 //!
-//! _Note a limitation of the shell is that each command needs to be one line._
+//! End a line with `\` to continue a command on the next line, or open a `<<EOF` heredoc and
+//! finish it with a lone `EOF`.
+//!
+//! Blank lines and lines starting with `#` are skipped in every shell, so annotated snippets can be
+//! pasted straight in.
+//!
+//! Pasting several lines at once runs them one after another, each shown at the prompt as it
+//! runs, so a runbook can be pasted straight from a wiki. If one fails you are asked whether to
+//! carry on with the rest.
+//!
+//! `cd` is remembered between commands, so the shell behaves like a normal session. Run `pwd` to
+//! see the current directory.
+//!
+//! The output of `ls -l`, or `ll`, is shown with its columns aligned and directories in blue,
+//! executables in green and symlinks in cyan, since the remote `ls` can't color captured output.
+//! Anything piped or chained after it, or a listing in another format, is shown as it came.
+//!
+//! `local <command>` runs a command on your own machine instead of the EC2, for a quick
+//! `local git status` or `local ls ~/Downloads` without switching terminals. Its output is headed
+//! by `[local]` so it isn't mistaken for the EC2's. It works in the main prompt too.
+//!
+//! `env set KEY=VALUE` sends an environment variable with every following command, which is handy
+//! for things like `FLASK_ENV=dev`. Use `env unset KEY` to stop sending it and `env list` to see
+//! what is set.
+//!
+//! `alias deploy = "cd /app && ./deploy.sh"` saves a shortcut, so typing `deploy` runs the whole
+//! command. Anything typed after an alias is added to the end of it. Run `alias` to list them and
+//! `unalias deploy` to delete one. Aliases are kept in `~/.wcli/aliases`.
+//!
+//! Aliases can also be set in an `[aliases]` section of `~/.wcli/config.toml`. One saved with
+//! `alias` under the same name replaces it, and `unalias` puts it back.
+//! ```toml
+//! [aliases]
+//! deploy = "cd /app && ./deploy.sh"
+//! ```
+//!
+//! Each command is followed by how long it took, e.g. `(done in 1.3s)`. Run `timings off` to hide
+//! this and `timings on` to bring it back.
+//!
+//! To review a session like a log, run `timestamps on` in the cmd, git or sql shell. The output of
+//! every command is then headed with a dim local time, in every shell, until `timestamps off`.
+//! ```plaintext
+//! >>>  uptime
+//! [2025-06-19 11:48:02]
+//!  11:48:02 up 12 days,  3:04,  1 user,  load average: 0.00, 0.01, 0.05
+//! ```
+//!
+//! `watch <seconds> <command>` reruns a command on an interval, clearing the screen in between, so
+//! something like `watch 2 docker ps` can be left running. Press any key or Ctrl-C to stop.
+//!
+//! `retry <attempts> <command>` reruns a command that fails, waiting 2s between attempts, until it
+//! succeeds or has been tried that many times, at most 10. It's handy for flaky network installs,
+//! and the attempt that worked is printed along with the output.
+//!
+//! `follow <path>` prints lines as they are added to a file, like `tail -f`, until Ctrl-C. The
+//! remote `tail` is stopped too rather than left running on the EC2.
+//!
+//! End a command with `| save <path>` to also write its output to a local file, replacing it, or
+//! `|>> <path>` to add to the end of one. The number of bytes written is printed afterwards. Only
+//! a last pipe into `save` is caught, so `ls | grep save` still runs on the EC2 as typed.
+//!
+//! Full-screen or interactive tools like `top`, `htop`, `vim`, `nano`, `less`, `man` and `tmux`
+//! need a terminal, which normal commands don't get since their output is captured. Start the
+//! line with `-t`, e.g. `-t htop`, to run it with a terminal attached. Its output can't be paged or
+//! saved. If a command fails in a way that looks like it wanted a terminal, WCLI suggests `-t`.
+//!
+//! Output over 1 MB, such as an accidental `cat` of a binary, is cut short with a note of its full
+//! size, and you are offered to save all of it to a local file instead. Set `output_limit_kb` at
+//! the top of `~/.wcli/config.toml` to change the limit, or `0` to turn it off.
+//!
+//! Output taller than the terminal is shown a page at a time: space shows the next page, enter the
+//! next line and `q` stops. If `PAGER` is set, for example to `less -R`, it is used instead. Run
+//! WCLI with `--no-pager` to always print output in full.
+//!
+//! Commands starting with `sudo` are sent the password on stdin, so they can't answer a question
+//! like `yum`'s `Is this ok [y/N]`. If one is still running after 30s the spinner warns that it
+//! may be waiting for input, add `-y` or press Ctrl-C. `install` and `remove` always pass `-y`.
+//!
+//! `install` and `remove` use whichever of `apt-get`, `dnf` or `yum` the EC2 has, checked once per
+//! session. Set `PACKAGE_MANAGER` to `apt`, `dnf` or `yum` in `.env`, or in a host's section of
+//! `~/.wcli/config.toml`, to choose one yourself.
+//!
+//! Before installing, `install` checks `df` for `/` and `/var`. If either is 90% full or more it
+//! warns that the install may run out of space and asks whether to go ahead.
+//!
+//! `update` refreshes the package lists and `upgrade` applies every available update, after asking
+//! first, then prints how many packages were upgraded.
+//!
+//! Press Ctrl-C to stop a long running command and get back to the prompt, WCLI itself keeps
+//! running.
+//!
+//! The `cmd`, `git` and `sql` shells support line editing, up-arrow history and Ctrl-R reverse
+//! search. History is kept in `~/.wcli_history`, `~/.wcli_history_git` and `~/.wcli_history_sql`.
+//! Press Tab to complete the commands available in the current shell.
+//!
+//! Run `history` in any shell to list its past commands with a number, then `!N` to run number
+//! `N` again or `!!` to run the last one. Anything typed after them is added to the end.
+//!
+//! Use `push` and `pull` inside `cmd` to copy files between your machine and the EC2 with scp.
+//!
+//! `view <path>` prints a file from the EC2, with syntax highlighting when its extension is a
+//! known language and the output is a terminal. Only the first 200 lines are shown, use
+//! `view --all <path>` for the whole file.
+//!
+//! `ps` lists the processes on the EC2 as a table of PID, user, CPU, memory and command, and
+//! `ps <filter>` shows only the lines containing the filter, like piping it through `grep`.
+//! `kill <pid>` or `kill <name>` shows the matching processes and, after asking, stops them.
+//! A name matches the program, so `kill nginx` stops every nginx process. If the processes
+//! belong to another user, `kill` is retried with sudo. Options like `ps -ef` or `kill -9 <pid>`
+//! are run on the EC2 as they are.
 //!
 //! ```plaintext
 //! [user@wcli ~]$ cmd
@@ -38,23 +430,50 @@
 //! total 16
 //! drwxr-xr-x. 4 ec2-user ec2-user    33 Jun 18 13:47 Directory1
 //! drwxr-xr-x. 8 ec2-user ec2-user 16384 Jun 19 11:48 Directory2
+//!
+//! >>>  cat <<EOF > notes.txt
+//! ...  first line
+//! ...  EOF
 //! ```
 //!
-//! `git` opens a shell with a prompt for the repository.
+//! `git` opens a shell with a prompt for the repository. The prompt shows the checked out branch,
+//! or `(not a repo)` if the path isn't a git repository.
 //! ```plaintext
 //! [user@wcli ~]$ git
 //! Repo path: Documents/repository
 //! Run 'help' for commands
 //!
-//! >>>  git status
+//! (dev) >>>  status
 //! On branch dev
 //! Your branch is up to date with 'origin/dev'.
 //! ```
 //!
+//! `git` can be left off, so `status`, `pull` and `log --oneline` work as typed. The shell's own
+//! `change`, `clone`, `branches`, `clear`, `help` and `exit` commands are never sent to git. A
+//! subcommand WCLI doesn't recognise, usually a typo like `stauts`, is only sent after you confirm
+//! it.
+//!
+//! A git command that fails, like a `pull` with a merge conflict, is followed by a red
+//! `git command failed (exit 1)` line with the number of failures so far in the git shell, so it
+//! can't be mistaken for one that worked.
+//!
+//! `branches` lists the local and remote branches with the checked out one in green. A
+//! `checkout <branch>` checks the branch exists locally or on a remote before switching, and the
+//! prompt follows the new branch. With a detached HEAD the prompt shows `(detached at 3f51a4b)`.
+//! ```plaintext
+//! (dev) >>>  branches
+//! * dev
+//!   main
+//!   remotes/origin/main
+//!
+//! (dev) >>>  checkout mian
+//! error: no branch named 'mian', run 'branches' to list them
+//! ```
+//!
 //! `sql` opens a shell that attempts a connection to mariadb provided you are running MySQL.
 //! ```plaintext
 //! [user@wcli ~]$ sql
-//! Connected to mariadb in 191ns
+//! Connected to mariadb on localhost in 191ms
 //!
 //! Database: mydb
 //! Run 'help' for commands
@@ -63,20 +482,80 @@
 //! name
 //! Beth
 //! Josh
+//! 2 rows in set
+//! ```
+//!
+//! Each query is followed by a dim footer like the mysql client's, `2 rows in set` for a query
+//! that returns rows and `Query OK, 1 row affected` for an `INSERT`, `UPDATE` or `DELETE`.
+//!
+//! `rerun` runs the last query again and `edit` prints it so it can be copied and tweaked. Run
+//! `export <file>` after a query to save its result to a local CSV file. The query is run again,
+//! so only a `SELECT`, `SHOW`, `DESCRIBE` or `EXPLAIN` can be exported.
+//!
+//! `source <file>` runs a local sql script, such as a migration, on the database. The file is
+//! written to mariadb's stdin, so statements spanning several lines and quotes need no escaping.
+//! mariadb stops at the first statement that fails and WCLI prints the error. A script that
+//! contains `DROP` or `TRUNCATE` has to be confirmed by typing `yes` first.
+//! ```plaintext
+//! >>>  source migrations/0004_add_orders.sql
+//! Sourced migrations/0004_add_orders.sql
+//! ```
+//!
+//! Safe mode refuses a `DELETE` or `UPDATE` without a `WHERE`, so a stray `DELETE FROM Users;`
+//! can't empty the table. Prefix the query with `force` to run it anyway, or set
+//! `sql_safe_mode = false` in `~/.wcli/config.toml` to turn safe mode off.
+//! ```plaintext
+//! >>>  DELETE FROM Users;
+//! warning: refused DELETE without a WHERE, it would change every row
+//! Run 'force DELETE FROM Users;' if that's what you want
+//! ```
+//!
+//! `begin` holds the following queries in a transaction, with the number held shown in the prompt.
+//! `commit` sends them to mariadb together so they all apply or none do, and `rollback` throws
+//! them away without running them. A committed transaction isn't kept for `rerun`, `edit` or
+//! `export`, so it can't be applied twice by accident.
+//! ```plaintext
+//! >>>  begin
+//! Transaction started, queries run together on 'commit'
+//!
+//! (tx 0) >>>  UPDATE Accounts SET balance = balance - 10 WHERE id = 1;
+//! (tx 1) >>>  UPDATE Accounts SET balance = balance + 10 WHERE id = 2;
+//! (tx 2) >>>  commit
+//! Committed 2 statements
+//! ```
+//!
+//! `shell` opens a full interactive ssh session on the EC2, with a terminal, for tools like `vim`
+//! or `top`. Run `exit` in it to come back to WCLI.
+//!
+//! `status` prints a quick health summary of the EC2 from a single round trip. Anything the
+//! instance can't report, for example `free` on a minimal image, is shown as `n/a`.
+//! ```plaintext
+//! [user@wcli ~]$ status
+//! Host      ip-172-31-0-1
+//! System    Linux 6.1.0-1.amzn2023 x86_64
+//! Uptime    3 days, 4:05
+//! Load      0.00, 0.01, 0.05
+//! Disk      4.1G of 8.0G used (52%), 3.9G free
+//! Memory    812M of 1949M used, 1137M available
 //! ```
 //!
 //! `test` opens a shell that attempts to run Python unittests within a given directory. You
 //! also need to provide a virtual environment path from your repository.
 //!
-//! _Note future release will automatically create a .venv for you._
+//! If the venv doesn't exist it is created and the repository's `requirements.txt` is installed
+//! into it.
+//!
+//! Tests run with `unittest` by default, answer `pytest` at the runner prompt to use pytest
+//! instead.
 //!
 //! ```plaintext
 //! [user@wcli ~]$ test
 //! Repo path: Documents/repository
 //! venv name: .venv
 //! Tests path: app/tests
+//! Runner [unittest/pytest]:
 //!
-//! All tests passed in 6s
+//! 12 passed, 0 failed in 6.2s
 //! ``````
 //!
 //! ## License
@@ -86,24 +565,124 @@
 //! Apache 2.0
 //!
 
-// Rust analyser genrates warning as env files are only known at compile time
-include!(concat!(env!("OUT_DIR"), "/config.rs"));
-
 use colored::Colorize;
-use dotenv::dotenv;
 use std::{
-    io::{self, Write},
+    env,
+    io::{self, IsTerminal},
     process,
+    time::Duration,
 };
 
+use args::Args;
+use cmd::connect::Ping;
+use config::Config;
+use session::Session;
+
+mod args;
 mod cmd;
+mod config;
+mod logging;
+mod session;
+
+const VERSION: &str = "1.0.0";
+
+/// Exit code WCLI uses when the main prompt's input ends, from Ctrl-D or a closed stdin, rather
+/// than with `exit`.
+///
+const EXIT_EOF: i32 = 3;
 
 fn main() {
-    dotenv().ok();
+    let args: Result<Args, String> = Args::parse();
+    init_color(args.as_ref().is_ok_and(|args: &Args| args.no_color));
+
+    let args: Args = match args {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{} {e}", "error:".red());
+            process::exit(1);
+        }
+    };
+
+    if args.version {
+        println!("WCLI {VERSION}");
+        process::exit(0);
+    }
+
+    if args.help {
+        args::usage();
+        cmd::helpers::help();
+        process::exit(0);
+    }
+
+    cmd::helpers::set_paging(!args.no_pager && args.broadcast.is_none());
+
+    if let Some(command) = &args.broadcast {
+        let mut configs: Vec<Config> = match Config::load_all(args.env_file.as_deref()) {
+            Ok(configs) => configs,
+            Err(e) => {
+                eprintln!("{} {e}", "error:".red());
+                process::exit(config::EXIT_CONFIG);
+            }
+        };
+
+        for config in &mut configs {
+            config.dry_run = args.dry_run;
+            config.verbose = args.verbose;
+            apply_color(config);
+            prompt_password(config);
+            cmd::connect::add_to_agent(config);
+        }
+
+        process::exit(cmd::broadcast(&configs, command, args.sudo));
+    }
+
+    if let Some((old, new, command)) = &args.diff {
+        let mut configs: [Config; 2] = [load(old, &args), load(new, &args)];
+
+        for config in &mut configs {
+            config.dry_run = args.dry_run;
+            config.verbose = args.verbose;
+            prompt_password(config);
+            cmd::connect::add_to_agent(config);
+        }
+
+        process::exit(cmd::diff(&configs, command, args.sudo));
+    }
+
+    let mut config: Config = load(&args.host, &args);
+
+    if args.config_check {
+        process::exit(cmd::connect::check_config(&config));
+    }
+
+    if args.verbose > 0 {
+        print_source(&config);
+    }
+
+    if args.multiplex {
+        config.control_path = Some(cmd::connect::control_path());
+    }
+
+    config.dry_run = args.dry_run;
+    config.verbose = args.verbose;
+    prompt_password(&mut config);
+    cmd::connect::add_to_agent(&config);
+
+    if let Some(command) = &args.exec {
+        if let Some(timeout) = args.timeout {
+            config.command_timeout_secs = timeout;
+        }
+        let code: i32 = cmd::exec(&config, command, args.sudo, args.json);
+
+        cmd::connect::close_master(&config);
+        config.forget_password();
+        process::exit(code);
+    }
+
+    cmd::helpers::set_output_limit(config.output_limit_kb.saturating_mul(1024));
 
-    let password: String = PASS.to_string();
     let title: &'static str = "WCLI 2025";
-    let version: &'static str = "Version 1.0.0";
+    let version: String = format!("Version {VERSION}");
     let website: &'static str = "https://github.com/Taghunter98/wcli.git";
 
     let logo: colored::ColoredString = format!(
@@ -127,17 +706,168 @@ fn main() {
         cmd::helpers::capitalise(&user)
     );
 
-    cmd::connect::test_connection();
+    if let Err(e) = cmd::connect::test_connection(&config) {
+        print_connect_error(&e);
+        cmd::connect::close_master(&config);
+        process::exit(cmd::helpers::EXIT_SSH_FAILED);
+    }
 
-    main_loop(password, user);
+    cmd::handle_interrupt();
+    let mut session: Session = Session::new(config, user, args.env_file);
+    let code: i32 = main_loop(&mut session);
+
+    cmd::connect::close_master(&session.config);
+    session.config.forget_password();
+    process::exit(code);
+}
+
+/// Disables colored output when asked to with `--no-color` or `NO_COLOR`, or when stdout isn't a
+/// terminal so that piped output isn't full of escape codes.
+///
+fn init_color(no_color: bool) {
+    let no_color_env: bool = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+    if no_color || no_color_env || !io::stdout().is_terminal() {
+        colored::control::set_override(false);
+    }
+}
+
+/// Loads the config for a host, exiting if it can't be loaded.
+///
+fn load(host: &str, args: &Args) -> Config {
+    match Config::load(host, args.env_file.as_deref()) {
+        Ok(config) => {
+            apply_color(&config);
+            config
+        }
+        Err(e) => {
+            eprintln!("{} {e}", "error:".red());
+            process::exit(config::EXIT_CONFIG);
+        }
+    }
+}
+
+/// Turns colored output off if the config file sets `color = false`. It can't turn colors back
+/// on once `--no-color` or `NO_COLOR` turned them off.
+///
+fn apply_color(config: &Config) {
+    if !config.color {
+        colored::control::set_override(false);
+    }
+}
+
+/// Asks for the sudo password if the host has `PASS_PROMPT` set, exiting if it can't be read.
+///
+fn prompt_password(config: &mut Config) {
+    if let Err(e) = config.prompt_password() {
+        eprintln!("{} {e}", "error:".red());
+        process::exit(config::EXIT_CONFIG);
+    }
+}
+
+/// Prints which file the credentials for the host were loaded from, for `--verbose`.
+///
+fn print_source(config: &Config) {
+    let source: String = match &config.source {
+        Some(path) => path.display().to_string(),
+        None => "environment variables".to_string(),
+    };
+
+    eprintln!("{} '{}' from {source}", "Loaded".green(), config.host);
+}
+
+/// Prints a connection error with a hint on how to fix it.
+///
+fn print_connect_error(e: &cmd::connect::ConnectError) {
+    eprintln!("{} {e}", "error:".red());
+    eprintln!("{} {}\n", "hint:".yellow(), e.hint());
+}
+
+/// Prints the hosts that `profile` can switch to, marking the active one.
+///
+/// # Examples
+/// ```plaintext
+/// [user@wcli ~]$ profile
+/// * default
+///   staging
+/// ```
+fn list_profiles(config: &Config) {
+    match Config::host_names() {
+        Ok(hosts) => {
+            for host in hosts {
+                if host == config.host {
+                    println!("{} {}", "*".green(), host.green());
+                } else {
+                    println!("  {host}");
+                }
+            }
+            println!();
+        }
+        Err(e) => println!("{} {e}\n", "error:".red()),
+    }
+}
+
+/// Switches the active host mid-session and returns whether it did.
+///
+/// The shared ssh connection to the current host is closed and the new host's connection is
+/// tested first, so a host that can't be reached leaves the session where it was. The
+/// `--dry-run`, `--verbose` and `--multiplex` flags carry over.
+///
+/// # Examples
+/// ```plaintext
+/// [user@wcli ~]$ profile staging
+/// Connected to staging on Wed Jun 19 at 11:48:02 in 191ms
+/// ```
+fn switch_profile(session: &mut Session, name: &str) -> bool {
+    if name == session.host() {
+        println!("Already on {name}\n");
+        return false;
+    }
+
+    let config: &mut Config = &mut session.config;
+    let mut next: Config = match Config::load(name, session.env_file.as_deref()) {
+        Ok(next) => next,
+        Err(e) => {
+            println!("{} {e}\n", "error:".red());
+            return false;
+        }
+    };
+
+    next.dry_run = config.dry_run;
+    next.verbose = config.verbose;
+    next.control_path = config.control_path.clone();
+
+    if let Err(e) = next.prompt_password() {
+        println!("{} {e}\n", "error:".red());
+        return false;
+    }
+
+    cmd::connect::close_master(config);
+    cmd::connect::add_to_agent(&next);
+
+    if let Err(e) = cmd::connect::test_connection(&next) {
+        print_connect_error(&e);
+        cmd::connect::close_master(&next);
+        println!("Staying on {}\n", config.host);
+        return false;
+    }
+
+    *config = next;
+    true
 }
 
 /// Main loop, takes prompt from user and matches it with args for running Linux commands.
 ///
+/// Returns the exit code, 0 after `exit` and [`EXIT_EOF`] when the input ends. The history is
+/// saved on the way out, the ssh connection is left for the caller to close.
+///
+/// After `idle_timeout_minutes` without input a warning is printed and the loop ends as if
+/// `exit` was typed.
+///
 /// # Examples
 ///
 /// ```rust
-/// main_loop(password);
+/// let code: i32 = main_loop(&mut session);
 /// ```
 /// Running a command
 /// ```plaintext
@@ -155,29 +885,104 @@ fn main() {
 /// Enter repo path >>> Documents/repository
 /// >>> git pull
 /// ```
-fn main_loop(password: String, user: String) {
-    loop {
-        print!("[{}@wcli ~]$ ", user);
+fn main_loop(session: &mut Session) -> i32 {
+    let mut editor: cmd::LineEditor = cmd::LineEditor::new("wcli");
+    let mut ping: Option<Ping> = None;
 
-        io::stdout().flush().expect("failed to flush stdout");
+    let idle_minutes: u64 = session.config.idle_timeout_minutes;
+    editor.set_idle_timeout((idle_minutes > 0).then(|| Duration::from_secs(idle_minutes * 60)));
 
-        let mut input: String = String::new();
+    loop {
+        let config: &Config = &session.config;
+        let user: &str = &session.user;
+        let auto_ping: bool = config.prompt_status && !config.dry_run;
+        if auto_ping && ping.as_ref().is_none_or(Ping::is_stale) {
+            ping = Some(Ping::now(config));
+        }
 
-        io::stdin()
-            .read_line(&mut input)
-            .expect("failed to read line");
+        let status: String = match &ping {
+            Some(ping) if config.prompt_status => ping.indicator(),
+            _ => String::new(),
+        };
+        let prompt_vars: cmd::PromptVars = cmd::PromptVars {
+            user,
+            cwd: "~",
+            shell: "wcli",
+            status: &status,
+            ..cmd::PromptVars::default()
+        };
+        let prompt: String =
+            cmd::shell_prompt(config, &format!("[{}@wcli ~]{status}$", user), &prompt_vars);
+        let input: io::Result<String> = editor.prompt_input(&prompt);
+        if let Err(e) = &input
+            && e.kind() == io::ErrorKind::TimedOut
+        {
+            println!(
+                "{} no input for {idle_minutes} min, disconnecting\n",
+                "warning:".yellow()
+            );
+            break 0;
+        }
+
+        let Some(input) = cmd::or_exit(input) else {
+            break EXIT_EOF;
+        };
 
         let prompt: &str = input.trim();
+        if prompt.is_empty() || prompt.starts_with('#') {
+            continue;
+        }
 
         match prompt {
-            "cmd" => cmd::cmd(&password),
-            "git" => cmd::git::run_git(),
-            "sql" => cmd::sql::run_sql(password.as_str()),
-            "test" => cmd::test::run_unittests(),
+            "cmd" => cmd::cmd(session),
+            "git" => cmd::git::run_git(session),
+            "sql" => cmd::sql::run_sql(session),
+            "test" => cmd::test::run_unittests(config),
+            "status" => cmd::status::status(config),
+            "shell" => cmd::shell(config),
+            cmd if cmd == "profile" || cmd.starts_with("profile ") => {
+                let name: &str = cmd.trim_start_matches("profile").trim();
+                if name.is_empty() {
+                    list_profiles(config);
+                } else if switch_profile(session, name) {
+                    ping = None;
+                }
+            }
+            "ping" => {
+                let result: Ping = Ping::now(config);
+                println!("{result}\n");
+                ping = Some(result);
+            }
+            "reconnect" => {
+                if let Err(e) = cmd::connect::reconnect(config) {
+                    print_connect_error(&e);
+                }
+            }
+            cmd if cmd == "load" || cmd.starts_with("load ") => {
+                let args: Vec<&str> = cmd.split_whitespace().skip(1).collect();
+                let keep_going: bool = args.contains(&"--continue");
+                let path: Vec<&str> = args
+                    .into_iter()
+                    .filter(|arg: &&str| *arg != "--continue")
+                    .collect();
+
+                cmd::load(&path.join(" "), keep_going);
+            }
+            cmd if cmd == "local" || cmd.starts_with("local ") => {
+                cmd::local(config, cmd.trim_start_matches("local").trim())
+            }
+            "history" => editor.print_history(),
             "clear" => cmd::helpers::clear(),
             "help" => cmd::helpers::help(),
-            "exit" => process::exit(1),
-            _ => println!("invalid command, run 'help' for commands"),
+            "cheatsheet" => cmd::helpers::cheatsheet(),
+            "exit" => break 0,
+            _ => {
+                let (name, args) = prompt.split_once(' ').unwrap_or((prompt, ""));
+                match config.commands.get(name) {
+                    Some(template) => cmd::custom(&cmd::Ssh, config, name, template, args),
+                    None => println!("invalid command, run 'help' for commands"),
+                }
+            }
         }
     }
 }
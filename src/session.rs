@@ -0,0 +1,63 @@
+//! This module provides the state of an interactive WCLI session.
+//!
+//! ## License
+//!
+//! Copyright (C) Josh Bassett. All rights reserved.
+//!
+//! Apache 2.0
+//!
+
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+
+/// Holds everything the shells share for the length of a session, so it is passed around as
+/// one value rather than as loose arguments.
+///
+/// `config` is the active host's config, replaced by `profile`, and `user` is the local user
+/// shown in the prompts. `env_file` is the `--env-file` hosts are loaded from, if given. `cwd`,
+/// `vars` and `timings` are the cmd shell's working directory, the variables sent with each
+/// command and whether each command is timed, see [`Session::open_cmd`].
+///
+pub struct Session {
+    pub config: Config,
+    pub user: String,
+    pub env_file: Option<String>,
+    pub cwd: Option<String>,
+    pub vars: BTreeMap<String, String>,
+    pub timings: bool,
+}
+
+impl Session {
+    /// Starts a session on the host in `config`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let session: Session = Session::new(config, user, args.env_file.clone());
+    /// ```
+    pub fn new(config: Config, user: String, env_file: Option<String>) -> Session {
+        Session {
+            config,
+            user,
+            env_file,
+            cwd: None,
+            vars: BTreeMap::new(),
+            timings: true,
+        }
+    }
+
+    /// Returns the name of the active host.
+    ///
+    pub fn host(&self) -> &str {
+        &self.config.host
+    }
+
+    /// Resets the cmd shell's state, so each time it's opened it starts in the home directory
+    /// with no variables and timings on.
+    ///
+    pub fn open_cmd(&mut self) {
+        self.cwd = None;
+        self.vars.clear();
+        self.timings = true;
+    }
+}